@@ -0,0 +1,156 @@
+//! Alarm-style reminders: they play a sound and come back every few minutes
+//! until they are marked done or snoozed, up to a number of times.
+//!
+//! Sounds are played with whichever of `pw-play`, `paplay` and `aplay` is
+//! installed. Alarms without a sound file of their own get a short beep
+//! that is generated here, so nothing has to be installed alongside Rewind.
+
+use std::f32::consts::TAU;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use crate::file_actions;
+
+pub const DEFAULT_EVERY_MINUTES: u32 = 5;
+pub const DEFAULT_REPEATS: u32 = 6;
+// The most the dialog offers. Stored alarms are kept within them as well.
+pub const MAX_EVERY_MINUTES: u32 = 120;
+pub const MAX_REPEATS: u32 = 100;
+
+// Tried in this order, the first one that is installed plays the sound
+const PLAYERS: [&str; 3] = ["pw-play", "paplay", "aplay"];
+const DEFAULT_SOUND_FILE: &str = "alarm.wav";
+const SAMPLE_RATE: u32 = 22050;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alarm {
+    /// The sound to play, the built-in beep when `None`.
+    pub sound: Option<PathBuf>,
+    /// Minutes between repeats, from 1 to `MAX_EVERY_MINUTES`.
+    pub every_minutes: u32,
+    /// How many times it repeats at most after the first alert, 0 only plays the sound.
+    pub repeats: u32,
+}
+
+impl Default for Alarm {
+    fn default() -> Self {
+        Alarm { sound: None, every_minutes: DEFAULT_EVERY_MINUTES, repeats: DEFAULT_REPEATS }
+    }
+}
+
+impl Alarm {
+    /// Plays the alarm's sound without waiting for it to finish.
+    pub fn ring(&self) {
+        let sound = self.sound.clone();
+        thread::spawn(move || {
+            let file = match sound {
+                Some(file) => file,
+                None => match default_sound() {
+                    Ok(file) => file,
+                    Err(e) => {
+                        println!("Could not write the alarm sound: {}", e);
+                        return;
+                    }
+                },
+            };
+            if let Err(e) = play(&PLAYERS, &file) {
+                println!("Could not play {}: {}", file.display(), e);
+            }
+        });
+    }
+}
+
+/// Plays `file` with the first of `players` that is installed, and waits for it.
+fn play(players: &[&str], file: &Path) -> Result<(), String> {
+    for player in players {
+        let status = Command::new(player)
+            .arg(file)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => return Ok(()),
+            Ok(status) => return Err(format!("{} failed: {}", player, status)),
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(format!("{} could not be started: {}", player, e)),
+        }
+    }
+    Err(format!("none of {} is installed", PLAYERS.join(", ")))
+}
+
+/// The built-in beep, written next to the reminders the first time it's needed.
+fn default_sound() -> std::io::Result<PathBuf> {
+    let path = file_actions::data_dir().join(DEFAULT_SOUND_FILE);
+    if !path.exists() {
+        fs::create_dir_all(file_actions::data_dir())?;
+        fs::write(&path, beep())?;
+    }
+    Ok(path)
+}
+
+/// Three short beeps as a 16-bit mono WAV file.
+pub fn beep() -> Vec<u8> {
+    const TONE: f32 = 880.0;
+    const BEEP_SECONDS: f32 = 0.15;
+    const GAP_SECONDS: f32 = 0.1;
+
+    let beep_samples = (SAMPLE_RATE as f32 * BEEP_SECONDS) as usize;
+    let gap_samples = (SAMPLE_RATE as f32 * GAP_SECONDS) as usize;
+    let mut samples = Vec::new();
+    for _ in 0..3 {
+        for n in 0..beep_samples {
+            // Faded in and out, so it doesn't click
+            let fade = (n.min(beep_samples - n) as f32 / 200.0).min(1.0);
+            let value = (TAU * TONE * n as f32 / SAMPLE_RATE as f32).sin() * fade * 0.6;
+            samples.push((value * i16::MAX as f32) as i16);
+        }
+        samples.extend(std::iter::repeat_n(0, gap_samples));
+    }
+
+    let data_size = (samples.len() * 2) as u32;
+    let mut wav = Vec::with_capacity(44 + data_size as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    wav.extend_from_slice(&1u16.to_le_bytes()); // mono
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); // bytes per second
+    wav.extend_from_slice(&2u16.to_le_bytes()); // bytes per sample
+    wav.extend_from_slice(&16u16.to_le_bytes()); // bits per sample
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_beep_is_a_valid_wav_file() {
+        let wav = beep();
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav[4..8].try_into().unwrap()) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u32::from_le_bytes(wav[40..44].try_into().unwrap()) as usize, wav.len() - 44);
+        // Three beeps of 0.15 seconds and their gaps
+        assert_eq!((wav.len() - 44) / 2, 3 * (3307 + 2205));
+    }
+
+    #[test]
+    fn sounds_play_with_the_first_installed_player() {
+        let file = Path::new("/nonexistent/alarm.wav");
+        assert!(play(&["rewind-no-such-player"], file).unwrap_err().contains("none of"));
+        assert!(play(&["rewind-no-such-player", "true"], file).is_ok());
+        assert!(play(&["false", "true"], file).unwrap_err().starts_with("false failed"));
+    }
+}
@@ -0,0 +1,80 @@
+use std::path::PathBuf;
+
+use gtk::prelude::{BoxExt, Cast, CheckButtonExt, EditableExt, EntryExt, WidgetExt};
+use relm4::gtk;
+
+use crate::alarm::{self, Alarm};
+use crate::widgets::labelled_row;
+
+/// The "Alarm" part of the reminder dialog.
+pub struct AlarmSection {
+    pub root: gtk::Expander,
+    enabled: gtk::CheckButton,
+    sound: gtk::Entry,
+    every: gtk::SpinButton,
+    repeats: gtk::SpinButton,
+}
+
+impl AlarmSection {
+    pub fn new() -> Self {
+        let root = gtk::Expander::new(Some("Alarm"));
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(5)
+            .build();
+
+        let enabled = gtk::CheckButton::with_label("Play a sound and repeat until marked done or snoozed");
+        let sound = gtk::Entry::new();
+        sound.set_placeholder_text(Some("Sound file (leave empty for the built-in beep)"));
+        let every = gtk::SpinButton::with_range(1.0, alarm::MAX_EVERY_MINUTES as f64, 1.0);
+        every.set_value(alarm::DEFAULT_EVERY_MINUTES as f64);
+        let repeats = gtk::SpinButton::with_range(0.0, alarm::MAX_REPEATS as f64, 1.0);
+        repeats.set_value(alarm::DEFAULT_REPEATS as f64);
+        let every_row = labelled_row("Repeat every", &every, "minutes");
+        let repeats_row = labelled_row("At most", &repeats, "times");
+
+        content.append(&enabled);
+        content.append(&sound);
+        content.append(&every_row);
+        content.append(&repeats_row);
+        root.set_child(Some(&content));
+
+        // The details only matter for alarms
+        let details: [gtk::Widget; 3] = [sound.clone().upcast(), every_row.upcast(), repeats_row.upcast()];
+        let update_sensitivity = move |enabled: &gtk::CheckButton| {
+            for widget in &details {
+                widget.set_sensitive(enabled.is_active());
+            }
+        };
+        update_sensitivity(&enabled);
+        enabled.connect_toggled(update_sensitivity);
+
+        AlarmSection { root, enabled, sound, every, repeats }
+    }
+
+    /// Shows an existing alarm in the widgets.
+    pub fn fill(&self, alarm: &Alarm) {
+        self.enabled.set_active(true);
+        self.sound.set_text(&alarm.sound.as_ref().map(|sound| sound.display().to_string()).unwrap_or_default());
+        self.every.set_value(alarm.every_minutes as f64);
+        self.repeats.set_value(alarm.repeats as f64);
+        self.root.set_expanded(true);
+    }
+
+    /// Builds the alarm described by the widgets, `None` when it is off.
+    pub fn read(&self) -> Result<Option<Alarm>, String> {
+        if !self.enabled.is_active() {
+            return Ok(None);
+        }
+        let text = self.sound.text().trim().to_string();
+        let sound = (!text.is_empty()).then(|| PathBuf::from(&text));
+        if sound.as_ref().is_some_and(|sound| !sound.is_file()) {
+            return Err(format!("There is no sound file at {}", text));
+        }
+        Ok(Some(Alarm {
+            sound,
+            every_minutes: self.every.value_as_int().max(1) as u32,
+            repeats: self.repeats.value_as_int().max(0) as u32,
+        }))
+    }
+}
@@ -0,0 +1,96 @@
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// A simple reminder app
+#[derive(Parser, Debug)]
+#[command(name = "rewind", version, about)]
+pub struct Cli {
+    /// Keep reminders in DIR instead of $XDG_DATA_HOME/rewind
+    #[arg(long, env = "REWIND_DATA_DIR", value_name = "DIR")]
+    pub data_dir: Option<PathBuf>,
+
+    /// Read settings from FILE instead of $XDG_CONFIG_HOME/rewind/config.toml
+    #[arg(long, env = "REWIND_CONFIG", value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Run in the background without a window and send notifications when reminders are due
+    #[arg(long, conflicts_with = "command")]
+    pub daemon: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+/// Runs instead of opening the window when given.
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Add a reminder, e.g. `rewind add "Water plants every monday 10:00"`
+    Add {
+        /// The name, followed by when it is due unless --at is given
+        name: String,
+        /// When it is due, e.g. "2026-10-20 14:00", "tomorrow 9am" or "in 20 minutes"
+        #[arg(long, value_name = "TIME")]
+        at: Option<String>,
+        /// Repeat it, following an iCalendar RRULE such as "FREQ=WEEKLY;BYDAY=MO"
+        #[arg(long, value_name = "RRULE")]
+        repeat: Option<String>,
+    },
+    /// List reminders in the order they come up
+    List {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Delete a reminder
+    Rm {
+        /// The reminder's id, or enough of its start to tell it apart
+        id: String,
+    },
+    /// Mark a reminder done: single reminders move to the history, repeating ones skip their next occurrence
+    Done {
+        /// The reminder's id, or enough of its start to tell it apart
+        id: String,
+    },
+    /// Have a reminder come up again in a few minutes, skipping any repeats before then
+    Snooze {
+        /// The reminder's id, or enough of its start to tell it apart
+        id: String,
+        #[arg(default_value_t = 10)]
+        minutes: u32,
+    },
+    /// Show the reminders that were marked done, most recent first
+    History {
+        /// Only show the ones whose name contains this
+        query: Option<String>,
+        /// Delete the ones completed more than DAYS days ago instead
+        #[arg(long, value_name = "DAYS", conflicts_with = "query")]
+        purge: Option<u32>,
+    },
+    /// Add the reminders from an iCalendar (.ics) file, skipping ones already present
+    Import {
+        file: PathBuf,
+    },
+    /// Write every reminder to an iCalendar (.ics) file
+    Export {
+        file: PathBuf,
+    },
+    /// Start the background service at every login, or stop doing so
+    Service {
+        #[command(subcommand)]
+        action: ServiceAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ServiceAction {
+    /// Start the background service now and at every login, through a systemd user unit where possible
+    Install {
+        /// Use an XDG autostart entry even when systemd manages the session
+        #[arg(long)]
+        autostart: bool,
+    },
+    /// Stop starting the background service at login
+    Remove,
+    /// Show whether the background service is running and when the next reminder is due
+    Status,
+}
@@ -0,0 +1,356 @@
+//! The command-line operations, which work on the store without starting GTK.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, TimeZone};
+use serde_json::json;
+
+use crate::cli::{Command, ServiceAction};
+use crate::history;
+use crate::ics;
+use crate::natural_time::{self, When};
+use crate::recurrence::Recurrence;
+use crate::reminder::{self, Reminder};
+use crate::service;
+use crate::store::ReminderStore;
+
+// How due times are shown in `rewind list` and in messages
+const DISPLAY_TIME_FORMAT: &str = "%a %e %b %Y %H:%M";
+
+/// `daemon_args` are the options a daemon started from here should get.
+/// Returns whether the reminders changed, so a running daemon can be told.
+pub fn run(command: Command, store: &mut dyn ReminderStore, daemon_args: &[OsString]) -> Result<bool, Box<dyn Error>> {
+    let changed = match command {
+        Command::Add { name, at, repeat } => {
+            let now = Local::now();
+            let (name, when) = match at {
+                Some(at) => (name.trim().to_string(), parse_at(&at, now)?),
+                None => natural_time::split_quick_add(&name, now)?,
+            };
+            if name.is_empty() {
+                return Err("the name must not be empty".into());
+            }
+            let mut reminder = Reminder::new(name, when.time);
+            reminder.recurrence = match (when.recurrence, repeat) {
+                (Some(_), Some(_)) => return Err("say how it repeats either with \"every\" or with --repeat, not both".into()),
+                (Some(rule), None) => Some(rule),
+                (None, Some(rrule)) => Some(Recurrence::parse(&rrule).map_err(|e| format!("--repeat: {}", e))?),
+                (None, None) => None,
+            };
+            if reminder.next_occurrence(now.fixed_offset()).is_none() {
+                return Err("The Reminder must be in the Future!".into());
+            }
+            store.insert(&reminder)?;
+            println!("Added '{}' for {} ({})", reminder.name, show_time(reminder.time), short_id(&reminder.id));
+            true
+        }
+        Command::List { json } => {
+            let loaded = store.load()?;
+            for error in &loaded.errors {
+                eprintln!("Skipped {}", error);
+            }
+            let now = Local::now().fixed_offset();
+            let listed = upcoming_first(loaded.reminders, now);
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&to_json(&listed, now))?);
+            } else if listed.is_empty() {
+                println!("No reminders");
+            } else {
+                for (reminder, next) in &listed {
+                    let due = next.map(show_time).unwrap_or_else(|| "over".to_string());
+                    let repeats = reminder.recurrence.as_ref().map(|rule| format!(" — {}", rule)).unwrap_or_default();
+                    let snoozed = match reminder.snoozed(now) {
+                        Some(_) => format!(" (snoozed {}×)", reminder.snooze_count),
+                        None => String::new(),
+                    };
+                    println!("{}  {:<21}  {}{}{}", short_id(&reminder.id), due, reminder.name, repeats, snoozed);
+                }
+            }
+            false
+        }
+        Command::Rm { id } => {
+            let reminder = find(store, &id)?;
+            store.delete(&reminder.id)?;
+            println!("Deleted '{}'", reminder.name);
+            true
+        }
+        Command::Done { id } => {
+            let mut reminder = find(store, &id)?;
+            let now = Local::now().fixed_offset();
+            // Unless a snooze is pending, that one is done instead
+            let skipped = reminder.next_occurrence(now).filter(|_| reminder.snoozed(now).is_none());
+            let active = reminder.done(now);
+            store.update(&reminder)?;
+            match skipped {
+                Some(next) if active => println!("Skipped '{}' on {}", reminder.name, show_time(next)),
+                _ if active => println!("Marked '{}' done for now", reminder.name),
+                _ => println!("Marked '{}' done", reminder.name),
+            }
+            true
+        }
+        Command::Snooze { id, minutes } => {
+            let mut reminder = find(store, &id)?;
+            let now = Local::now().fixed_offset();
+            reminder.snooze(minutes, now);
+            store.update(&reminder)?;
+            let until = reminder.snoozed_until.unwrap_or(now);
+            println!("Snoozed '{}' until {} ({}×)", reminder.name, show_time(until), reminder.snooze_count);
+            true
+        }
+        Command::History { purge: Some(days), .. } => {
+            let before = Local::now().fixed_offset() - chrono::Duration::days(days.into());
+            let purged = history::purge(store, before)?;
+            println!("Deleted {} completed reminders from before {}", purged.len(), show_time(before));
+            false
+        }
+        Command::History { query, purge: None } => {
+            let reminders = store.load()?.reminders;
+            let found = history::search(&reminders, query.as_deref().unwrap_or(""));
+            if found.is_empty() {
+                println!("Nothing in the history");
+            }
+            for reminder in found {
+                let completed = reminder.completed_at.map(show_time).unwrap_or_default();
+                println!("{}  {:<21}  {}", short_id(&reminder.id), completed, reminder.name);
+            }
+            false
+        }
+        Command::Import { file } => {
+            let text = fs::read_to_string(&file).map_err(|e| format!("could not read {}: {}", file.display(), e))?;
+            let report = ics::import_calendar(store, &text)?;
+            println!("{}", report);
+            for error in &report.errors {
+                eprintln!("Skipped {}", error);
+            }
+            true
+        }
+        Command::Export { file } => {
+            let sink = File::create(&file).map_err(|e| format!("could not create {}: {}", file.display(), e))?;
+            let count = ics::export_calendar(store, BufWriter::new(sink))?;
+            println!("Exported {} reminders to {}", count, file.display());
+            false
+        }
+        Command::Service { action } => {
+            let report = match action {
+                ServiceAction::Install { autostart } => service::install(daemon_args, autostart)?,
+                ServiceAction::Remove => service::remove()?,
+                ServiceAction::Status => service::status(store)?,
+            };
+            println!("{}", report);
+            false
+        }
+    };
+    Ok(changed)
+}
+
+/// The reminders that are not completed, with when they next alert.
+/// Upcoming reminders come first, soonest at the top, then those that are over.
+fn upcoming_first(reminders: Vec<Reminder>, now: DateTime<FixedOffset>) -> Vec<(Reminder, Option<DateTime<FixedOffset>>)> {
+    let mut listed: Vec<(Reminder, Option<DateTime<FixedOffset>>)> = reminders
+        .into_iter()
+        .filter(|reminder| reminder.completed_at.is_none())
+        .map(|reminder| {
+            let next = reminder.next_alert(now);
+            (reminder, next)
+        })
+        .collect();
+    listed.sort_by_key(|(reminder, next)| (next.is_none(), next.unwrap_or(reminder.time)));
+    listed
+}
+
+/// What `rewind list --json` prints.
+fn to_json(listed: &[(Reminder, Option<DateTime<FixedOffset>>)], now: DateTime<FixedOffset>) -> serde_json::Value {
+    listed
+        .iter()
+        .map(|(reminder, next)| {
+            json!({
+                "id": reminder.id,
+                "name": reminder.name,
+                "time": reminder::format_time(&reminder.time),
+                "next": next.map(|due| reminder::format_time(&due)),
+                "recurrence": reminder.recurrence.as_ref().map(|rule| rule.to_rrule()),
+                "tags": reminder.tags,
+                "email": reminder.email,
+                "snoozed_until": reminder.snoozed(now).map(|until| reminder::format_time(&until)),
+                "snooze_count": reminder.snooze_count,
+            })
+        })
+        .collect()
+}
+
+/// Looks a reminder up by its id, or by a prefix that only one id starts with.
+fn find(store: &mut dyn ReminderStore, id: &str) -> Result<Reminder, Box<dyn Error>> {
+    let reminders = store.load()?.reminders;
+    if let Some(reminder) = reminders.iter().find(|reminder| reminder.id == id) {
+        return Ok(reminder.clone());
+    }
+
+    let mut matches = reminders.into_iter().filter(|reminder| !id.is_empty() && reminder.id.starts_with(id));
+    match (matches.next(), matches.next()) {
+        (Some(reminder), None) => Ok(reminder),
+        (None, _) => Err(format!("there is no reminder with id {}", id).into()),
+        (Some(_), Some(_)) => Err(format!("more than one reminder id starts with {}, give more of it", id).into()),
+    }
+}
+
+/// Parses `--at`: RFC 3339, local time like `2026-10-20 14:00`, or a phrase like "tomorrow 9am".
+fn parse_at(text: &str, now: DateTime<Local>) -> Result<When, String> {
+    let text = text.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(When { time, recurrence: None });
+    }
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(text, format) {
+            let time = Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .ok_or_else(|| format!("{} does not exist in the local time zone", text))?;
+            return Ok(When { time: time.fixed_offset(), recurrence: None });
+        }
+    }
+    natural_time::parse(text, now)
+}
+
+fn show_time(time: DateTime<FixedOffset>) -> String {
+    time.with_timezone(&Local).format(DISPLAY_TIME_FORMAT).to_string()
+}
+
+/// The start of an id, which is enough to tell reminders apart on the command line.
+fn short_id(id: &str) -> &str {
+    id.get(..8).unwrap_or(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_actions::XmlStore;
+    use crate::test_support::scratch_store;
+
+    fn with_id(id: &str, name: &str, time: DateTime<FixedOffset>) -> Reminder {
+        let mut reminder = Reminder::new(name.to_string(), time);
+        reminder.id = id.to_string();
+        reminder
+    }
+
+    fn time(text: &str) -> DateTime<FixedOffset> {
+        reminder::parse_time(text).unwrap()
+    }
+
+    #[test]
+    fn ids_are_found_by_a_prefix_only_one_starts_with() {
+        let (_dir, mut store) = scratch_store("commands-find", "reminders.xml", XmlStore::new);
+        let at = time("2030-10-20T14:00:00+02:00");
+        store.insert_all(&[with_id("ab", "Short", at), with_id("ab12", "Tea", at), with_id("ab34", "Laundry", at)]).unwrap();
+
+        assert_eq!(find(&mut store, "ab").unwrap().name, "Short", "an exact id wins over longer ones");
+        assert_eq!(find(&mut store, "ab1").unwrap().name, "Tea");
+        assert_eq!(find(&mut store, "ab34").unwrap().name, "Laundry");
+        let ambiguous = find(&mut store, "a").unwrap_err().to_string();
+        assert!(ambiguous.contains("more than one"), "{}", ambiguous);
+        let missing = find(&mut store, "zz").unwrap_err().to_string();
+        assert!(missing.contains("no reminder with id zz"), "{}", missing);
+        assert!(find(&mut store, "").is_err(), "an empty id matches nothing");
+    }
+
+    #[test]
+    fn at_takes_exact_times_and_phrases() {
+        let now = Local::now();
+        let exact = parse_at(" 2030-10-20T14:00:00+02:00 ", now).unwrap();
+        assert_eq!(exact.time, time("2030-10-20T14:00:00+02:00"));
+        assert_eq!(exact.time.offset(), time("2030-10-20T14:00:00+02:00").offset());
+        let local = parse_at("2030-10-20 14:00", now).unwrap();
+        assert_eq!(local.time.with_timezone(&Local).format("%F %R").to_string(), "2030-10-20 14:00");
+        assert_eq!(parse_at("2030-10-20T14:00:30", now).unwrap().time, local.time + chrono::Duration::seconds(30));
+
+        assert!(parse_at("2030-02-30 14:00", now).is_err());
+        assert!(parse_at("2030-10-20 25:00", now).is_err());
+        assert!(parse_at("whenever", now).is_err());
+        assert!(parse_at("", now).is_err());
+    }
+
+    #[test]
+    fn lists_show_upcoming_reminders_soonest_first() {
+        let now = time("2030-10-20T12:00:00+02:00");
+        let later = with_id("later", "Later", time("2030-10-22T09:00:00+02:00"));
+        let soon = with_id("soon", "Soon", time("2030-10-21T09:00:00+02:00"));
+        let over = with_id("over", "Over", time("2030-10-19T09:00:00+02:00"));
+        let mut daily = with_id("daily", "Daily", time("2030-10-01T08:00:00+02:00"));
+        daily.recurrence = Some(Recurrence::parse("FREQ=DAILY").unwrap());
+        let mut snoozed = with_id("snoozed", "Snoozed", time("2030-10-20T11:00:00+02:00"));
+        snoozed.snooze(30, now);
+        let mut done = with_id("done", "Done", time("2030-10-20T18:00:00+02:00"));
+        done.complete(now);
+
+        let listed = upcoming_first(vec![later, over, done, daily, soon, snoozed], now);
+        let order: Vec<&str> = listed.iter().map(|(reminder, _)| reminder.id.as_str()).collect();
+        assert_eq!(order, ["snoozed", "daily", "soon", "later", "over"]);
+
+        let json = to_json(&listed, now);
+        assert_eq!(json.as_array().unwrap().len(), 5);
+        assert_eq!(json[0]["snoozed_until"], "2030-10-20T12:30:00+02:00");
+        assert_eq!(json[0]["snooze_count"], 1);
+        // Repeats are worked out in local time, so the offset can differ
+        assert_eq!(time(json[1]["next"].as_str().unwrap()), time("2030-10-21T08:00:00+02:00"));
+        assert_eq!(json[1]["recurrence"], "FREQ=DAILY");
+        assert_eq!(json[2]["name"], "Soon");
+        assert_eq!(json[2]["snoozed_until"], serde_json::Value::Null);
+        assert_eq!(json[4]["time"], "2030-10-19T09:00:00+02:00");
+        assert_eq!(json[4]["next"], serde_json::Value::Null);
+        assert_eq!(json[4]["tags"], json!([]));
+    }
+
+    #[test]
+    fn done_skips_the_next_occurrence_of_a_repeating_reminder() {
+        let (_dir, mut store) = scratch_store("commands-done", "reminders.xml", XmlStore::new);
+        let start = Local::now().fixed_offset() + chrono::Duration::hours(1);
+        let start = time(&reminder::format_time(&start));
+        let mut daily = with_id("daily", "Water the plants", start);
+        daily.recurrence = Some(Recurrence::parse("FREQ=DAILY").unwrap());
+        store.insert(&daily).unwrap();
+
+        assert!(run(Command::Done { id: "dai".to_string() }, &mut store, &[]).unwrap(), "a daemon is told about it");
+        let stored = find(&mut store, "daily").unwrap();
+        assert_eq!(stored.completed_at, None, "it keeps repeating");
+        assert_eq!(stored.recurrence.as_ref().unwrap().exceptions, [start]);
+        assert_eq!(stored.next_occurrence(start), Some(start + chrono::Duration::days(1)));
+
+        run(Command::Done { id: "daily".to_string() }, &mut store, &[]).unwrap();
+        let stored = find(&mut store, "daily").unwrap();
+        assert_eq!(stored.recurrence.unwrap().exceptions, [start, start + chrono::Duration::days(1)]);
+    }
+
+    #[test]
+    fn snoozing_an_unknown_id_changes_nothing() {
+        let (_dir, mut store) = scratch_store("commands-snooze", "reminders.xml", XmlStore::new);
+        let tea = with_id("ab12", "Tea", time("2030-10-20T14:00:00+02:00"));
+        store.insert(&tea).unwrap();
+
+        let error = run(Command::Snooze { id: "cd".to_string(), minutes: 10 }, &mut store, &[]).unwrap_err();
+        assert!(error.to_string().contains("no reminder with id cd"), "{}", error);
+        let stored = find(&mut store, "ab12").unwrap();
+        assert_eq!(stored.snoozed_until, None);
+        assert_eq!(stored.snooze_count, 0);
+    }
+
+    #[test]
+    fn adding_a_time_that_is_over_changes_nothing() {
+        let (_dir, mut store) = scratch_store("commands-add-past", "reminders.xml", XmlStore::new);
+        let add = |at: &str, repeat: Option<&str>| Command::Add {
+            name: "Tea".to_string(),
+            at: Some(at.to_string()),
+            repeat: repeat.map(str::to_string),
+        };
+
+        let error = run(add("2020-10-20 14:00", None), &mut store, &[]).unwrap_err();
+        assert!(error.to_string().contains("must be in the Future"), "{}", error);
+        assert!(store.load().unwrap().reminders.is_empty());
+
+        assert!(run(add("2020-10-20 14:00", Some("FREQ=DAILY")), &mut store, &[]).unwrap(), "repeating ones still come up");
+        assert!(run(add("2030-10-20 14:00", None), &mut store, &[]).unwrap());
+        assert_eq!(store.load().unwrap().reminders.len(), 2);
+    }
+}
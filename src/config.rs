@@ -0,0 +1,126 @@
+//! Settings read from `$XDG_CONFIG_HOME/rewind/config.toml`.
+//!
+//! Every setting has a default, so the file and any section in it may be left out.
+
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use crate::file_actions;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub storage: StorageConfig,
+    pub notifications: NotificationConfig,
+    pub webhook: WebhookConfig,
+    pub email: EmailConfig,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct StorageConfig {
+    pub backend: Backend,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Backend {
+    /// `reminders.xml`, rewritten on every change, with backups kept next to it.
+    #[default]
+    Xml,
+    /// `reminders.sqlite`, for long lists.
+    Sqlite,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    pub backend: NotifierBackend,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifierBackend {
+    /// The desktop's notification server, and `notify-send` when that fails.
+    #[default]
+    Auto,
+    /// Only the desktop's notification server, over D-Bus.
+    Freedesktop,
+    /// Only the `notify-send` program, which can't show buttons.
+    NotifySend,
+    /// No desktop notifications, for when reminders only go to a webhook.
+    None,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct WebhookConfig {
+    /// Where fired reminders are posted. Nothing is sent when it is empty.
+    pub url: String,
+    /// The JSON to send, `webhook::DEFAULT_TEMPLATE` when not given.
+    pub template: Option<String>,
+    /// How many more times a failed delivery is tried, at most `webhook::MAX_RETRIES`.
+    pub retries: u32,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        WebhookConfig { url: String::new(), template: None, retries: 3 }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct EmailConfig {
+    /// The SMTP server. No email is sent when it is empty.
+    pub host: String,
+    /// The usual port for `security` when not given.
+    pub port: Option<u16>,
+    pub security: Security,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: String,
+    pub to: String,
+    /// Reminders are collected for this many minutes and sent in one email,
+    /// 0 sends each one right away.
+    pub digest_minutes: u32,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Security {
+    /// Plain connection upgraded with STARTTLS, port 587.
+    #[default]
+    Starttls,
+    /// TLS from the start, port 465.
+    Tls,
+    /// Unencrypted, port 25. Only for servers on the same machine.
+    None,
+}
+
+/// `$XDG_CONFIG_HOME/rewind/config.toml`, falling back to `~/.config/rewind/config.toml`.
+pub fn default_config_path() -> PathBuf {
+    config_home().join("rewind").join("config.toml")
+}
+
+/// `$XDG_CONFIG_HOME`, falling back to `~/.config`.
+pub fn config_home() -> PathBuf {
+    // The spec says relative values must be ignored
+    match env::var_os("XDG_CONFIG_HOME").map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir,
+        _ => file_actions::home_dir().join(".config"),
+    }
+}
+
+/// Reads the configuration at `path`. A missing file means all defaults.
+pub fn load(path: &Path) -> Result<Config, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text).map_err(|e| format!("{} is not valid: {}", path.display(), e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("{} could not be read: {}", path.display(), e)),
+    }
+}
@@ -0,0 +1,1082 @@
+//! The background service that fires notifications without any window.
+//!
+//! It owns `org.rewind.Reminders` on the session bus, so only one copy runs
+//! at a time and the window can tell it when reminders change. Closing the
+//! window leaves it running. Other programs can use the same interface to
+//! add, list, change and snooze reminders, and listen for `ReminderFired`.
+//! Reminders that came up while it was not running, or while the computer
+//! was asleep, are reported together once it gets going again.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, FixedOffset, Local};
+use zbus::fdo;
+use zbus::object_server::SignalEmitter;
+
+use crate::config::Config;
+use crate::email::{Mailer, Outbox};
+use crate::hook;
+use crate::missed::{self, Missed, RunState};
+use crate::notifier::{self, Alert, Notifier, OnAction};
+use crate::recurrence::Recurrence;
+use crate::reminder::{self, Reminder};
+use crate::scheduler::{Clock, Due, Scheduler, SystemClock};
+use crate::store::{self, ReminderStore};
+use crate::webhook::{self, Webhook};
+
+pub const BUS_NAME: &str = "org.rewind.Reminders";
+pub const OBJECT_PATH: &str = "/org/rewind/Reminders";
+
+// How long the window waits for a daemon it started to show up on the bus
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+// The snooze buttons on notifications, in minutes
+const SNOOZE_CHOICES: [u32; 3] = [5, 15, 60];
+
+// The store is shared between the scheduler loop and the bus calls
+type SharedStore = Arc<Mutex<Box<dyn ReminderStore>>>;
+// So are the last run time and the missed reminders
+type SharedState = Arc<Mutex<RunState>>;
+
+/// A reminder as `List` returns it: id, name, time of the first occurrence,
+/// RRULE (empty when it doesn't repeat) and the next time it is due (empty when it's over).
+type ListedReminder = (String, String, String, String, String);
+
+/// A missed reminder as `Missed` returns it: id, name and the time it was due.
+type ListedMissed = (String, String, String);
+
+enum Wake {
+    Reload,
+    Quit,
+}
+
+/// The object served on the bus. Calls are handled on zbus' own thread,
+/// the scheduler loop is woken up whenever one changes something.
+struct Service {
+    store: SharedStore,
+    state: SharedState,
+    wake: mpsc::Sender<Wake>,
+}
+
+impl Service {
+    fn store(&self) -> MutexGuard<'_, Box<dyn ReminderStore>> {
+        lock(&self.store)
+    }
+
+    fn find(&self, id: &str) -> fdo::Result<Reminder> {
+        let loaded = self.store().load().map_err(failed)?;
+        loaded
+            .reminders
+            .into_iter()
+            .find(|reminder| reminder.id == id)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("There is no reminder with id {}", id)))
+    }
+
+    /// Saves the change and has the scheduler look at it straight away.
+    fn changed(&self, result: Result<(), Box<dyn Error>>) -> fdo::Result<()> {
+        result.map_err(failed)?;
+        let _ = self.wake.send(Wake::Reload);
+        Ok(())
+    }
+}
+
+#[zbus::interface(name = "org.rewind.Reminders")]
+impl Service {
+    /// Checks the store again right away, after reminders were changed elsewhere.
+    fn reload(&self) {
+        let _ = self.wake.send(Wake::Reload);
+    }
+
+    fn quit(&self) {
+        let _ = self.wake.send(Wake::Quit);
+    }
+
+    /// Adds a reminder and returns its id. `time` is RFC 3339, or local time
+    /// without an offset such as `2026-10-20T14:00:00`. `recurrence` is an
+    /// RRULE like `FREQ=WEEKLY;BYDAY=MO`, or empty for a single reminder.
+    fn add(&self, name: &str, time: &str, recurrence: &str) -> fdo::Result<String> {
+        let mut reminder = Reminder::new(String::new(), Local::now().fixed_offset());
+        fill_reminder(&mut reminder, name, time, recurrence)?;
+        self.changed(self.store().insert(&reminder))?;
+        println!("Added reminder '{}' over D-Bus", reminder.name);
+        Ok(reminder.id)
+    }
+
+    /// Every reminder that is not completed, see `ListedReminder` for the fields.
+    fn list(&self) -> fdo::Result<Vec<ListedReminder>> {
+        let loaded = self.store().load().map_err(failed)?;
+        let now = Local::now().fixed_offset();
+        Ok(loaded
+            .reminders
+            .iter()
+            .filter(|reminder| reminder.completed_at.is_none())
+            .map(|reminder| {
+                (
+                    reminder.id.clone(),
+                    reminder.name.clone(),
+                    reminder::format_time(&reminder.time),
+                    reminder.recurrence.as_ref().map(|rule| rule.to_rrule()).unwrap_or_default(),
+                    reminder.next_alert(now).map(|due| reminder::format_time(&due)).unwrap_or_default(),
+                )
+            })
+            .collect())
+    }
+
+    /// Replaces the name, time and recurrence of a reminder, taking the same
+    /// values as `Add`. Skipped dates are kept as long as the RRULE stays the same.
+    fn update(&self, id: &str, name: &str, time: &str, recurrence: &str) -> fdo::Result<()> {
+        let mut reminder = self.find(id)?;
+        fill_reminder(&mut reminder, name, time, recurrence)?;
+        self.changed(self.store().update(&reminder))
+    }
+
+    fn delete(&self, id: &str) -> fdo::Result<()> {
+        self.find(id)?;
+        self.changed(self.store().delete(id))
+    }
+
+    /// Has a reminder come up again `minutes` from now and returns that time.
+    /// Occurrences of repeating reminders before then are skipped.
+    fn snooze(&self, id: &str, minutes: u32) -> fdo::Result<String> {
+        let now = Local::now().fixed_offset();
+        let mut reminder = self.find(id)?;
+        reminder.snooze(minutes, now);
+        self.changed(self.store().update(&reminder))?;
+        Ok(reminder.snoozed(now).map(|until| reminder::format_time(&until)).unwrap_or_default())
+    }
+
+    /// Reminders that came up while the computer was off or asleep and were
+    /// not dismissed yet, see `ListedMissed` for the fields.
+    fn missed(&self) -> Vec<ListedMissed> {
+        lock(&self.state)
+            .missed
+            .iter()
+            .map(|missed| (missed.id.clone(), missed.name.clone(), reminder::format_time(&missed.due)))
+            .collect()
+    }
+
+    /// Dismisses the missed reminder `id`, or every one when `id` is empty.
+    async fn dismiss_missed(&self, id: &str, #[zbus(signal_emitter)] emitter: SignalEmitter<'_>) -> fdo::Result<()> {
+        {
+            let mut state = lock(&self.state);
+            if !state.dismiss(id) {
+                return Ok(());
+            }
+            state.save().map_err(failed)?;
+        }
+        Self::missed_changed(&emitter).await?;
+        Ok(())
+    }
+
+    /// Sent when a reminder's notification goes out, with the time it was due.
+    #[zbus(signal)]
+    async fn reminder_fired(emitter: &SignalEmitter<'_>, id: &str, name: &str, due: &str) -> zbus::Result<()>;
+
+    /// Sent when reminders were missed, or missed ones were dismissed or done.
+    #[zbus(signal)]
+    async fn missed_changed(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+}
+
+/// Sets a reminder up from the strings `Add` and `Update` take.
+fn fill_reminder(reminder: &mut Reminder, name: &str, time: &str, recurrence: &str) -> fdo::Result<()> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(fdo::Error::InvalidArgs("The name must not be empty".to_string()));
+    }
+    let time = reminder::parse_legacy_time(time.trim()).map_err(fdo::Error::InvalidArgs)?;
+    let recurrence = match (recurrence.trim(), reminder.recurrence.take()) {
+        ("", _) => None,
+        (rrule, Some(existing)) if existing.to_rrule() == rrule => Some(existing),
+        (rrule, _) => Some(Recurrence::parse(rrule).map_err(fdo::Error::InvalidArgs)?),
+    };
+
+    reminder.name = name.to_string();
+    reminder.time = time;
+    reminder.recurrence = recurrence;
+    reminder.unsnooze();
+    Ok(())
+}
+
+fn failed(e: Box<dyn Error>) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+fn lock<T>(shared: &Arc<Mutex<T>>) -> MutexGuard<'_, T> {
+    // A panic halfway through a call leaves nothing behind that the next one can't handle
+    shared.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[zbus::proxy(
+    interface = "org.rewind.Reminders",
+    default_service = "org.rewind.Reminders",
+    default_path = "/org/rewind/Reminders"
+)]
+trait Reminders {
+    fn reload(&self) -> zbus::Result<()>;
+
+    fn quit(&self) -> zbus::Result<()>;
+
+    fn missed(&self) -> zbus::Result<Vec<ListedMissed>>;
+
+    fn dismiss_missed(&self, id: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn missed_changed(&self) -> zbus::Result<()>;
+}
+
+/// Runs the daemon until it is asked to quit.
+pub fn run(config: &Config) -> Result<(), Box<dyn Error>> {
+    let store: SharedStore = Arc::new(Mutex::new(store::open_store(config.storage.backend)));
+    let state: SharedState = Arc::new(Mutex::new(RunState::load(missed::state_path())));
+    // What came up since the last run is worked out before claiming the name,
+    // so the window never asks for missed reminders before they are known
+    let last_run = lock(&state).last_run;
+    let mut scheduler = Scheduler::new(SystemClock::new(), last_run);
+    let modified = store_modified(&store);
+    read_window(&store, &state, &mut scheduler);
+    let due = scheduler.poll();
+    lock(&state).add_missed(due.missed.clone());
+
+    let (wake, woken) = mpsc::channel();
+    let wake_alerts = wake.clone();
+    let connection = serve(zbus::blocking::connection::Builder::session()?, store.clone(), state.clone(), wake)?;
+    println!("Rewind daemon running as {}", BUS_NAME);
+
+    // The window gets the same --data-dir and --config as the daemon
+    let window_args = std::env::args_os().skip(1).filter(|arg| arg != "--daemon").collect();
+    let webhook = Webhook::from_config(&config.webhook, webhook::log_path()).unwrap_or_else(|e| {
+        println!("Not using the webhook: {}", e);
+        None
+    });
+    let alerts = Alerts {
+        notifier: notifier::from_config(config.notifications.backend).into(),
+        webhook,
+        responder: Responder { store: store.clone(), wake: wake_alerts, window_args },
+    };
+    let outbox = match Mailer::from_config(&config.email) {
+        Ok(mailer) => mailer.map(|mailer| Outbox::new(mailer, config.email.digest_minutes)),
+        Err(e) => {
+            println!("Not sending emails: {}", e);
+            None
+        }
+    };
+    let daemon = Daemon { store, state, scheduler, connection, alerts, outbox, modified };
+    daemon.run(due, woken);
+    println!("Rewind daemon stopped");
+    Ok(())
+}
+
+/// What a running daemon works with once it is set up.
+struct Daemon<C: Clock> {
+    store: SharedStore,
+    state: SharedState,
+    scheduler: Scheduler<C>,
+    connection: zbus::blocking::Connection,
+    alerts: Alerts,
+    outbox: Option<Outbox>,
+    // When the store last changed on disk, as far as the daemon knows
+    modified: Option<SystemTime>,
+}
+
+impl<C: Clock> Daemon<C> {
+    /// Handles `due`, then everything that comes due after it, until `woken` says to quit.
+    fn run(mut self, mut due: Due, woken: mpsc::Receiver<Wake>) {
+        loop {
+            // The last run is only saved when something came up, and on the way
+            // out. Should the daemon die in between, the older time it has saved
+            // finds nothing that was handled since.
+            lock(&self.state).last_run = Some(self.scheduler.handled_until());
+            let fired = !due.fire.is_empty();
+            if !due.missed.is_empty() {
+                report_missed(&self.connection, &self.alerts, &self.state, due.missed);
+            }
+            for (reminder, time) in due.fire {
+                if self.alerts.show(&reminder, time) {
+                    announce(&self.connection, &reminder, time);
+                }
+                // Alarms repeat the notification, not what else the reminder does
+                if reminder.alarm_repeat(time).is_some() {
+                    continue;
+                }
+                hook::start(&reminder, time);
+                if let Some(outbox) = &mut self.outbox {
+                    outbox.add(&reminder, time);
+                }
+            }
+            if fired {
+                save_last_run(&self.state);
+            }
+
+            let digest = self.outbox.as_ref().and_then(Outbox::next_send);
+            let wait = digest.map_or(self.scheduler.next_wake(), |digest| digest.min(self.scheduler.next_wake()));
+            let reload = match woken.recv_timeout(wait) {
+                Ok(Wake::Reload) => true,
+                // Someone may have changed the store without telling, like a second
+                // copy of the window without a session bus
+                Err(RecvTimeoutError::Timeout) => store_modified(&self.store) != self.modified,
+                Ok(Wake::Quit) | Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if reload {
+                self.modified = store_modified(&self.store);
+                if let Some(reminders) = read_window(&self.store, &self.state, &mut self.scheduler) {
+                    forget_finished(&self.connection, &self.state, &reminders);
+                }
+            } else if self.scheduler.needs_reload() {
+                read_window(&self.store, &self.state, &mut self.scheduler);
+            }
+            if let Some(outbox) = &mut self.outbox {
+                outbox.send_due();
+            }
+            due = self.scheduler.poll();
+        }
+        // A digest still collecting would be lost otherwise
+        if let Some(outbox) = &mut self.outbox {
+            outbox.send_now();
+        }
+        lock(&self.state).last_run = Some(self.scheduler.handled_until());
+        save_last_run(&self.state);
+    }
+}
+
+fn save_last_run(state: &SharedState) {
+    if let Err(e) = lock(state).save() {
+        println!("Failed to save the last run time: {}", e);
+    }
+}
+
+/// Hands the scheduler the reminders that alert within its window, reading
+/// only that part of the store. Reminders that were missed are read along,
+/// so the ones done or deleted since can be told apart. Returns what was read.
+fn read_window(store: &SharedStore, state: &SharedState, scheduler: &mut Scheduler<impl Clock>) -> Option<Vec<Reminder>> {
+    let (from, to) = scheduler.window();
+    let from = lock(state).missed.iter().map(|missed| missed.due).fold(from, DateTime::min);
+    let read = lock(store).due_between(from, to);
+    match read {
+        Ok(reminders) => {
+            scheduler.reload(reminders.clone(), to);
+            Some(reminders)
+        }
+        Err(e) => {
+            println!("Failed to read reminders: {}", e);
+            None
+        }
+    }
+}
+
+/// When the store's file last changed, to notice changes nobody announced.
+fn store_modified(store: &SharedStore) -> Option<SystemTime> {
+    let location = lock(store).location();
+    fs::metadata(location).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Keeps missed reminders until they are dismissed, and tells about them in one notification.
+fn report_missed(connection: &zbus::blocking::Connection, alerts: &Alerts, state: &SharedState, missed: Vec<Missed>) {
+    println!("Missed {} reminders", missed.len());
+    alerts.show_missed(&missed);
+    {
+        let mut state = lock(state);
+        state.add_missed(missed.clone());
+        if let Err(e) = state.save() {
+            println!("Failed to save missed reminders: {}", e);
+        }
+    }
+    for entry in &missed {
+        let sent = SignalEmitter::new(connection.inner(), OBJECT_PATH).and_then(|emitter| {
+            zbus::block_on(Service::reminder_fired(&emitter, &entry.id, &entry.name, &reminder::format_time(&entry.due)))
+        });
+        if let Err(e) = sent {
+            println!("Failed to announce reminder '{}': {}", entry.name, e);
+        }
+    }
+    announce_missed_changed(connection);
+}
+
+/// Drops missed reminders that were done or deleted since.
+fn forget_finished(connection: &zbus::blocking::Connection, state: &SharedState, reminders: &[Reminder]) {
+    let mut state = lock(state);
+    if state.forget_finished(reminders) {
+        if let Err(e) = state.save() {
+            println!("Failed to save missed reminders: {}", e);
+        }
+        drop(state);
+        announce_missed_changed(connection);
+    }
+}
+
+fn announce_missed_changed(connection: &zbus::blocking::Connection) {
+    let sent = SignalEmitter::new(connection.inner(), OBJECT_PATH)
+        .and_then(|emitter| zbus::block_on(Service::missed_changed(&emitter)));
+    if let Err(e) = sent {
+        println!("Failed to announce missed reminders: {}", e);
+    }
+}
+
+/// Claims the bus name on the connection `builder` makes and serves the interface there.
+fn serve(
+    builder: zbus::blocking::connection::Builder<'_>,
+    store: SharedStore,
+    state: SharedState,
+    wake: mpsc::Sender<Wake>,
+) -> Result<zbus::blocking::Connection, Box<dyn Error>> {
+    builder
+        .name(BUS_NAME)?
+        .serve_at(OBJECT_PATH, Service { store, state, wake })?
+        .build()
+        .map_err(|e| match e {
+            zbus::Error::NameTaken => "another Rewind daemon is already running".into(),
+            e => Box::<dyn Error>::from(e),
+        })
+}
+
+/// Emits `ReminderFired` for a reminder whose notification just went out.
+fn announce(connection: &zbus::blocking::Connection, reminder: &Reminder, due: DateTime<FixedOffset>) {
+    let sent = SignalEmitter::new(connection.inner(), OBJECT_PATH).and_then(|emitter| {
+        zbus::block_on(Service::reminder_fired(&emitter, &reminder.id, &reminder.name, &reminder::format_time(&due)))
+    });
+    if let Err(e) = sent {
+        println!("Failed to announce reminder '{}': {}", reminder.name, e);
+    }
+}
+
+/// A button on a reminder's notification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Action {
+    Snooze(u32),
+    Done,
+    Open,
+}
+
+impl Action {
+    fn key(self) -> String {
+        match self {
+            Action::Snooze(minutes) => format!("snooze-{}", minutes),
+            Action::Done => "done".to_string(),
+            Action::Open => "open".to_string(),
+        }
+    }
+
+    fn from_key(key: &str) -> Option<Action> {
+        match key {
+            "done" => Some(Action::Done),
+            // "default" is clicking the notification itself
+            "open" | "default" => Some(Action::Open),
+            _ => key.strip_prefix("snooze-")?.parse().ok().map(Action::Snooze),
+        }
+    }
+
+    fn label(self) -> String {
+        match self {
+            Action::Snooze(60) => "Snooze 1 hour".to_string(),
+            Action::Snooze(minutes) => format!("Snooze {} min", minutes),
+            Action::Done => "Mark done".to_string(),
+            Action::Open => "Open".to_string(),
+        }
+    }
+}
+
+/// Carries out what was clicked on a notification, on the notification's own thread.
+#[derive(Clone)]
+struct Responder {
+    store: SharedStore,
+    wake: mpsc::Sender<Wake>,
+    /// Passed on when opening the window.
+    window_args: Vec<OsString>,
+}
+
+impl Responder {
+    /// The buttons a reminder's notification gets.
+    fn actions() -> Vec<Action> {
+        let mut actions = vec![Action::Done];
+        actions.extend(SNOOZE_CHOICES.map(Action::Snooze));
+        actions.push(Action::Open);
+        actions
+    }
+
+    fn respond(&self, action: Action, id: &str, due: DateTime<FixedOffset>) -> Result<String, Box<dyn Error>> {
+        if action == Action::Open {
+            Command::new(std::env::current_exe()?)
+                .args(&self.window_args)
+                .stdin(Stdio::null())
+                .process_group(0)
+                .spawn()?;
+            return Ok("Opened the window".to_string());
+        }
+
+        let now = Local::now().fixed_offset();
+        let mut store = lock(&self.store);
+        let mut reminder = store
+            .load()?
+            .reminders
+            .into_iter()
+            .find(|reminder| reminder.id == id)
+            .ok_or("it was deleted in the meantime")?;
+        let report = match action {
+            Action::Snooze(minutes) => {
+                reminder.snooze(minutes, now);
+                store.update(&reminder)?;
+                format!("Snoozed '{}' for {} minutes", reminder.name, minutes)
+            }
+            _ if reminder.mark_done(due, now) => {
+                store.update(&reminder)?;
+                format!("Marked '{}' done for now", reminder.name)
+            }
+            _ => {
+                store.update(&reminder)?;
+                format!("Marked '{}' done", reminder.name)
+            }
+        };
+        drop(store);
+        let _ = self.wake.send(Wake::Reload);
+        Ok(report)
+    }
+}
+
+/// Sends the notifications for reminders, and handles their buttons.
+struct Alerts {
+    notifier: Arc<dyn Notifier>,
+    /// Also gets every reminder that fires, when one is configured.
+    webhook: Option<Webhook>,
+    responder: Responder,
+}
+
+impl Alerts {
+    /// Sends one notification listing reminders that went off unnoticed.
+    fn show_missed(&self, missed: &[Missed]) {
+        let (title, body) = missed::describe(missed);
+        let alert = Alert {
+            title,
+            body,
+            icon: "appointment-missed".to_string(),
+            urgent: false,
+            timeout: None,
+            persistent: false,
+            actions: vec![(Action::Open.key(), Action::Open.label())],
+            tag: None,
+        };
+        let responder = self.responder.clone();
+        let now = Local::now().fixed_offset();
+        let on_action: OnAction = Arc::new(move |key| {
+            if Action::from_key(key) == Some(Action::Open)
+                && let Err(e) = responder.respond(Action::Open, "", now)
+            {
+                println!("'{}' did not work: {}", Action::Open.label(), e);
+            }
+        });
+        if let Err(e) = self.notifier.notify(&alert, on_action) {
+            println!("Failed to send notification: {}", e);
+        }
+    }
+
+    /// Notifies about a reminder that is due at `due`. Returns whether that worked.
+    fn show(&self, reminder: &Reminder, due: DateTime<FixedOffset>) -> bool {
+        println!("Sending notification for: {}", reminder.name);
+        let repeated = reminder.alarm_repeat(due);
+        let body = match (repeated, &reminder.alarm) {
+            (Some((alert, repeat)), Some(alarm)) => format!(
+                "Your reminder '{}' was due at {} (reminder {} of {})",
+                reminder.name,
+                alert.with_timezone(&Local).format("%H:%M"),
+                repeat,
+                alarm.repeats
+            ),
+            _ => format!("Your reminder '{}' is due now!", reminder.name),
+        };
+        let alert = Alert {
+            title: format!("Reminder: {}", reminder.name),
+            body,
+            icon: "appointment-soon".to_string(),
+            urgent: true,
+            timeout: Some(Duration::from_secs(10)),
+            // Alarms stay until they are answered, instead of going by unseen
+            persistent: reminder.alarm.is_some(),
+            actions: Responder::actions().into_iter().map(|action| (action.key(), action.label())).collect(),
+            // Each alert takes the place of the reminder's previous one, so
+            // repeats of an alarm don't pile up on screen
+            tag: Some(reminder.id.clone()),
+        };
+        if let Some(webhook) = self.webhook.as_ref().filter(|_| repeated.is_none()) {
+            webhook.send(reminder, due);
+        }
+        if let Some(alarm) = &reminder.alarm {
+            alarm.ring();
+        }
+        let (responder, notifier) = (self.responder.clone(), self.notifier.clone());
+        let id = reminder.id.clone();
+        // Buttons on a repeat answer the alert it repeats
+        let due = repeated.map_or(due, |(alert, _)| alert);
+        let on_action: OnAction = Arc::new(move |key| {
+            let Some(action) = Action::from_key(key) else {
+                return;
+            };
+            match responder.respond(action, &id, due) {
+                Ok(report) => println!("{}", report),
+                Err(e) => println!("'{}' did not work: {}", action.label(), e),
+            }
+            // Resident alarms stay on screen after a click until taken down
+            if action != Action::Open {
+                notifier.close(&id);
+            }
+        });
+        match self.notifier.notify(&alert, on_action) {
+            Ok(()) => {
+                println!("Notification sent for: {}", reminder.name);
+                true
+            }
+            Err(e) => {
+                println!("Failed to send notification: {}", e);
+                false
+            }
+        }
+    }
+}
+
+/// Whether a daemon currently owns the bus name.
+pub fn is_running() -> Result<bool, Box<dyn Error>> {
+    let connection = zbus::blocking::Connection::session()?;
+    let bus = zbus::blocking::fdo::DBusProxy::new(&connection)?;
+    Ok(bus.name_has_owner(BUS_NAME.try_into()?)?)
+}
+
+/// Asks a running daemon to quit and waits until it is gone.
+/// Returns whether there was one.
+pub fn stop_running() -> Result<bool, Box<dyn Error>> {
+    let connection = zbus::blocking::Connection::session()?;
+    let bus = zbus::blocking::fdo::DBusProxy::new(&connection)?;
+    let name = zbus::names::BusName::try_from(BUS_NAME)?;
+    if !bus.name_has_owner(name.clone())? {
+        return Ok(false);
+    }
+
+    RemindersProxyBlocking::new(&connection)?.quit()?;
+    let mut waited = Duration::ZERO;
+    while bus.name_has_owner(name.clone())? {
+        if waited >= STARTUP_TIMEOUT {
+            return Err("the running background service did not stop".into());
+        }
+        thread::sleep(Duration::from_millis(100));
+        waited += Duration::from_millis(100);
+    }
+    Ok(true)
+}
+
+/// Tells a running daemon that reminders were changed. Nothing happens when none runs.
+pub fn reload_running() -> Result<(), Box<dyn Error>> {
+    let connection = zbus::blocking::Connection::session()?;
+    let bus = zbus::blocking::fdo::DBusProxy::new(&connection)?;
+    if bus.name_has_owner(BUS_NAME.try_into()?)? {
+        RemindersProxyBlocking::new(&connection)?.reload()?;
+    }
+    Ok(())
+}
+
+/// The window's side of the daemon.
+pub struct DaemonClient {
+    proxy: RemindersProxyBlocking<'static>,
+}
+
+impl DaemonClient {
+    /// Connects to the running daemon, starting it with `args` first if there is none.
+    /// Returns the client and whether the daemon had to be started.
+    pub fn connect(args: &[OsString]) -> Result<(Self, bool), Box<dyn Error>> {
+        let connection = zbus::blocking::Connection::session()?;
+        let bus = zbus::blocking::fdo::DBusProxy::new(&connection)?;
+        let name = zbus::names::BusName::try_from(BUS_NAME)?;
+
+        let started = !bus.name_has_owner(name.clone())?;
+        if started {
+            let executable = std::env::current_exe()?;
+            Command::new(executable)
+                .arg("--daemon")
+                .args(args)
+                .stdin(Stdio::null())
+                // Its own process group, so Ctrl+C in the terminal that opened the window doesn't stop it
+                .process_group(0)
+                .spawn()?;
+
+            let mut waited = Duration::ZERO;
+            while !bus.name_has_owner(name.clone())? {
+                if waited >= STARTUP_TIMEOUT {
+                    return Err("the background service did not start".into());
+                }
+                thread::sleep(Duration::from_millis(100));
+                waited += Duration::from_millis(100);
+            }
+        }
+
+        let proxy = RemindersProxyBlocking::new(&connection)?;
+        Ok((DaemonClient { proxy }, started))
+    }
+
+    /// Tells the daemon that reminders were changed.
+    pub fn reminders_changed(&self) {
+        if let Err(e) = self.proxy.reload() {
+            println!("Could not tell the background service about the change: {}", e);
+        }
+    }
+
+    /// Reminders that went off while nobody was around to see them.
+    pub fn missed(&self) -> Vec<Missed> {
+        let listed = match self.proxy.missed() {
+            Ok(listed) => listed,
+            Err(e) => {
+                println!("Could not ask the background service about missed reminders: {}", e);
+                return Vec::new();
+            }
+        };
+        listed
+            .into_iter()
+            .filter_map(|(id, name, due)| Some(Missed { id, name, due: reminder::parse_time(&due).ok()? }))
+            .collect()
+    }
+
+    /// Dismisses the missed reminder `id`, or all of them when `id` is empty.
+    pub fn dismiss_missed(&self, id: &str) {
+        if let Err(e) = self.proxy.dismiss_missed(id) {
+            println!("Could not dismiss missed reminders: {}", e);
+        }
+    }
+
+    /// Calls `changed` from another thread whenever the missed reminders change.
+    pub fn watch_missed(&self, changed: impl Fn() + Send + 'static) {
+        let signals = match self.proxy.receive_missed_changed() {
+            Ok(signals) => signals,
+            Err(e) => {
+                println!("Could not watch for missed reminders: {}", e);
+                return;
+            }
+        };
+        thread::spawn(move || {
+            for _ in signals {
+                changed();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Timelike;
+    use crate::file_actions::XmlStore;
+    use crate::test_support::{FakeClock, ScratchDir};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader};
+    use std::process::Child;
+
+    #[zbus::proxy(
+        interface = "org.rewind.Reminders",
+        default_service = "org.rewind.Reminders",
+        default_path = "/org/rewind/Reminders"
+    )]
+    trait Client {
+        fn add(&self, name: &str, time: &str, recurrence: &str) -> zbus::Result<String>;
+        fn list(&self) -> zbus::Result<Vec<ListedReminder>>;
+        fn update(&self, id: &str, name: &str, time: &str, recurrence: &str) -> zbus::Result<()>;
+        fn delete(&self, id: &str) -> zbus::Result<()>;
+        fn snooze(&self, id: &str, minutes: u32) -> zbus::Result<String>;
+        #[zbus(signal)]
+        fn reminder_fired(&self, id: String, name: String, due: String) -> zbus::Result<()>;
+    }
+
+    /// A dbus-daemon of our own, so the tests never touch the real session bus.
+    struct PrivateBus {
+        process: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        fn start() -> Self {
+            let mut process = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+                .expect("dbus-daemon is needed for these tests");
+            let mut address = String::new();
+            BufReader::new(process.stdout.as_mut().unwrap()).read_line(&mut address).unwrap();
+            PrivateBus { process, address: address.trim().to_string() }
+        }
+
+        fn builder(&self) -> zbus::blocking::connection::Builder<'static> {
+            zbus::blocking::connection::Builder::address(self.address.as_str()).unwrap()
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+        }
+    }
+
+    /// Serves a fresh XML store on a private bus and returns a client for it.
+    /// The store lives in the returned directory.
+    fn start_service(name: &str) -> (PrivateBus, zbus::blocking::Connection, SharedStore, mpsc::Receiver<Wake>, ScratchDir) {
+        let dir = ScratchDir::new(name);
+        let store: SharedStore = Arc::new(Mutex::new(Box::new(XmlStore::new(dir.join("reminders.xml")))));
+
+        let bus = PrivateBus::start();
+        let (wake, woken) = mpsc::channel();
+        let state = Arc::new(Mutex::new(RunState::load(dir.join("state.toml"))));
+        let service = serve(bus.builder(), store.clone(), state, wake).unwrap();
+        (bus, service, store, woken, dir)
+    }
+
+    #[test]
+    fn bus_calls_change_the_store() {
+        let (bus, _service, store, woken, _dir) = start_service("calls");
+        let client = ClientProxyBlocking::new(&bus.builder().build().unwrap()).unwrap();
+
+        let id = client.add("Deploy review", "2030-10-20T14:00:00+02:00", "").unwrap();
+        let weekly = client.add("Stand-up", "2030-10-21T09:30:00+02:00", "FREQ=WEEKLY;BYDAY=MO").unwrap();
+        assert!(client.add("  ", "2030-10-20T14:00:00+02:00", "").is_err());
+        assert!(client.add("Broken", "next tuesday", "").is_err());
+        assert!(matches!(woken.try_recv(), Ok(Wake::Reload)), "changes wake the scheduler");
+
+        let listed = client.list().unwrap();
+        assert_eq!(listed.len(), 2);
+        let stand_up = listed.iter().find(|r| r.0 == weekly).unwrap();
+        assert_eq!(stand_up.1, "Stand-up");
+        assert_eq!(stand_up.3, "FREQ=WEEKLY;BYDAY=MO");
+
+        client.update(&id, "Deploy review, moved", "2030-10-20T16:00:00+02:00", "").unwrap();
+        let stored = lock(&store).load().unwrap().reminders;
+        let moved = stored.iter().find(|r| r.id == id).unwrap();
+        assert_eq!(moved.name, "Deploy review, moved");
+        assert_eq!(reminder::format_time(&moved.time), "2030-10-20T16:00:00+02:00");
+
+        let before = Local::now().fixed_offset();
+        let snoozed = reminder::parse_time(&client.snooze(&id, 10).unwrap()).unwrap();
+        assert!(snoozed >= before + chrono::Duration::minutes(10) - chrono::Duration::seconds(1));
+        client.snooze(&weekly, 10).unwrap();
+        let stored = lock(&store).load().unwrap().reminders;
+        let stand_up = stored.iter().find(|r| r.id == weekly).unwrap();
+        assert_eq!(reminder::format_time(&stand_up.time), "2030-10-21T09:30:00+02:00", "the schedule stays put");
+        assert_eq!(stand_up.snooze_count, 1);
+
+        client.delete(&id).unwrap();
+        assert!(client.delete(&id).is_err());
+        let stored = lock(&store).load().unwrap().reminders;
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].id, weekly);
+    }
+
+    // The address of the private bus `notification_buttons_change_the_store`
+    // runs again on
+    const SESSION_BUS_VARIABLE: &str = "REWIND_TEST_SESSION_BUS";
+
+    /// Stands in for the desktop's notification server.
+    struct FakeNotifications {
+        shown: mpsc::Sender<(u32, String, Vec<String>)>,
+        next_id: std::sync::atomic::AtomicU32,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl FakeNotifications {
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: &str,
+            _replaces_id: u32,
+            _app_icon: &str,
+            summary: &str,
+            _body: &str,
+            actions: Vec<String>,
+            _hints: HashMap<String, zbus::zvariant::OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let id = self.next_id.fetch_add(1, std::sync::atomic::Ordering::SeqCst) + 1;
+            let _ = self.shown.send((id, summary.to_string(), actions));
+            id
+        }
+
+        fn get_capabilities(&self) -> Vec<String> {
+            vec!["actions".to_string(), "body".to_string()]
+        }
+
+        fn get_server_information(&self) -> (String, String, String, String) {
+            ("fake".to_string(), "rewind".to_string(), "1".to_string(), "1.2".to_string())
+        }
+
+        fn close_notification(&self, _id: u32) {}
+
+        #[zbus(signal)]
+        async fn action_invoked(emitter: &SignalEmitter<'_>, id: u32, action_key: &str) -> zbus::Result<()>;
+    }
+
+    #[test]
+    fn notification_buttons_change_the_store() {
+        // notify-rust always talks to the session bus. Changing the environment
+        // while other tests run is not safe, so this test runs again in a
+        // process of its own that starts out on the private bus.
+        let Ok(address) = std::env::var(SESSION_BUS_VARIABLE) else {
+            let bus = PrivateBus::start();
+            let test = concat!(module_path!(), "::notification_buttons_change_the_store");
+            let test = test.split_once("::").map_or(test, |(_crate, path)| path);
+            let output = Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", test, "--nocapture"])
+                .env(SESSION_BUS_VARIABLE, &bus.address)
+                .env("DBUS_SESSION_BUS_ADDRESS", &bus.address)
+                .output()
+                .unwrap();
+            let printed = String::from_utf8_lossy(&output.stdout) + String::from_utf8_lossy(&output.stderr);
+            assert!(output.status.success(), "{}", printed);
+            assert!(printed.contains("1 passed"), "the test did not run again: {}", printed);
+            return;
+        };
+        let dir = ScratchDir::new("actions");
+        let store: SharedStore = Arc::new(Mutex::new(Box::new(XmlStore::new(dir.join("reminders.xml")))));
+        let (shown, notifications) = mpsc::channel();
+        let server = zbus::blocking::connection::Builder::address(address.as_str())
+            .unwrap()
+            .name("org.freedesktop.Notifications")
+            .unwrap()
+            .serve_at("/org/freedesktop/Notifications", FakeNotifications { shown, next_id: Default::default() })
+            .unwrap()
+            .build()
+            .unwrap();
+
+        // Whole seconds, like the stored times
+        let now = Local::now().fixed_offset().with_nanosecond(0).unwrap();
+        let tea = Reminder::new("Tea".to_string(), now);
+        let laundry = Reminder::new("Laundry".to_string(), now);
+        lock(&store).insert_all(&[tea.clone(), laundry.clone()]).unwrap();
+
+        let (wake, woken) = mpsc::channel();
+        let alerts = Alerts {
+            notifier: Arc::new(notifier::Freedesktop::default()),
+            webhook: None,
+            responder: Responder { store: store.clone(), wake, window_args: Vec::new() },
+        };
+        assert!(alerts.show(&tea, now));
+        assert!(alerts.show(&laundry, now));
+
+        let emitter = SignalEmitter::new(server.inner(), "/org/freedesktop/Notifications").unwrap();
+        for _ in 0..2 {
+            let (id, summary, actions) = notifications.recv_timeout(Duration::from_secs(5)).unwrap();
+            assert_eq!(actions[..4], ["done", "Mark done", "snooze-5", "Snooze 5 min"]);
+            let key = if summary.contains("Tea") { "snooze-15" } else { "done" };
+            // The click can come before notify-rust listens for it, so keep clicking until it's handled
+            let mut waited = Duration::ZERO;
+            while !matches!(woken.try_recv(), Ok(Wake::Reload)) {
+                assert!(waited < Duration::from_secs(5), "'{}' was never handled", key);
+                zbus::block_on(FakeNotifications::action_invoked(&emitter, id, key)).unwrap();
+                thread::sleep(Duration::from_millis(50));
+                waited += Duration::from_millis(50);
+            }
+        }
+
+        let mut stored = lock(&store).load().unwrap().reminders;
+        let laundry = stored.iter().position(|r| r.id == laundry.id).unwrap();
+        assert!(stored.remove(laundry).completed_at.is_some(), "the laundry is done");
+        assert_eq!(stored[0].id, tea.id);
+        assert_eq!(stored[0].time, tea.time, "snoozing keeps the due time");
+        let until = stored[0].snoozed_until.unwrap();
+        assert!(until >= now + chrono::Duration::minutes(15) - chrono::Duration::seconds(1));
+        assert_eq!(stored[0].snooze_count, 1);
+    }
+
+    #[test]
+    fn each_alert_goes_out_once_through_the_configured_notifier() {
+        let dir = ScratchDir::new("daemon-notifier");
+        let store: SharedStore = Arc::new(Mutex::new(Box::new(XmlStore::new(dir.join("reminders.xml")))));
+        let now = Local::now().fixed_offset().with_nanosecond(0).unwrap();
+        let reminder = Reminder::new("\"; rm -rf ~; \"".to_string(), now);
+        lock(&store).insert(&reminder).unwrap();
+
+        let recording = notifier::Recording::default();
+        let (wake, woken) = mpsc::channel();
+        let responder = Responder { store: store.clone(), wake, window_args: Vec::new() };
+        let alerts = Alerts { notifier: Arc::new(recording.clone()), webhook: None, responder: responder.clone() };
+        assert!(alerts.show(&reminder, now));
+        let shown = recording.alerts();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].title, "Reminder: \"; rm -rf ~; \"");
+        assert_eq!(shown[0].tag.as_ref(), Some(&reminder.id), "a later alert replaces it");
+
+        recording.click(0, "done");
+        assert!(matches!(woken.try_recv(), Ok(Wake::Reload)));
+        assert!(lock(&store).load().unwrap().reminders[0].completed_at.is_some());
+        assert_eq!(recording.closed.lock().unwrap()[..], [reminder.id.as_str()], "answering takes it down");
+
+        let broken = notifier::Recording { broken: true, ..Default::default() };
+        let alerts = Alerts { notifier: Arc::new(broken), webhook: None, responder };
+        assert!(!alerts.show(&reminder, now), "failures are reported");
+    }
+
+    /// Waits up to a few seconds for `done` to come true.
+    fn wait_until(what: &str, done: impl Fn() -> bool) {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(std::time::Instant::now() < deadline, "{} never happened", what);
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn the_loop_fires_each_reminder_once_and_follows_the_store() {
+        let dir = ScratchDir::new("daemon-loop");
+        let store: SharedStore = Arc::new(Mutex::new(Box::new(XmlStore::new(dir.join("reminders.xml")))));
+        let state: SharedState = Arc::new(Mutex::new(RunState::load(dir.join("state.toml"))));
+        let bus = PrivateBus::start();
+        let (wake, woken) = mpsc::channel();
+        let connection = serve(bus.builder(), store.clone(), state.clone(), wake.clone()).unwrap();
+        let client = ClientProxyBlocking::new(&bus.builder().build().unwrap()).unwrap();
+
+        let clock = FakeClock::at("2026-10-18T09:00:00+02:00");
+        let now = clock.now();
+        lock(&store).insert(&Reminder::new("Tea".to_string(), now + chrono::Duration::minutes(1))).unwrap();
+        let mut scheduler = Scheduler::new(clock.clone(), Some(now));
+        read_window(&store, &state, &mut scheduler);
+        let due = scheduler.poll();
+        let recording = notifier::Recording::default();
+        let responder = Responder { store: store.clone(), wake: wake.clone(), window_args: Vec::new() };
+        let alerts = Alerts { notifier: Arc::new(recording.clone()), webhook: None, responder };
+        let modified = store_modified(&store);
+        let daemon = Daemon { store, state, scheduler, connection, alerts, outbox: None, modified };
+        let running = thread::spawn(move || daemon.run(due, woken));
+
+        // The loop sleeps on the real clock, so each step of the fake one comes with a wake-up
+        let titles = || recording.alerts().into_iter().map(|alert| alert.title).collect::<Vec<_>>();
+        clock.advance(60);
+        wake.send(Wake::Reload).unwrap();
+        wait_until("the first alert", || !titles().is_empty());
+        // Adding over the bus tells the loop to read the store again
+        let laundry = now + chrono::Duration::minutes(2);
+        client.add("Laundry", &reminder::format_time(&laundry), "").unwrap();
+        clock.advance(60);
+        wake.send(Wake::Reload).unwrap();
+        wait_until("the added reminder's alert", || titles().len() == 2);
+        // Messages are taken in order, so this wake-up is handled before quitting
+        clock.advance(3600);
+        wake.send(Wake::Reload).unwrap();
+        wake.send(Wake::Quit).unwrap();
+        running.join().unwrap();
+
+        assert_eq!(titles(), ["Reminder: Tea", "Reminder: Laundry"]);
+        let last_run = RunState::load(dir.join("state.toml")).last_run.unwrap();
+        assert!(last_run >= laundry, "the last run is saved on the way out");
+    }
+
+    #[test]
+    fn fired_reminders_are_announced() {
+        let (bus, service, _store, _woken, _dir) = start_service("signal");
+        let client = ClientProxyBlocking::new(&bus.builder().build().unwrap()).unwrap();
+        let mut fired = client.receive_reminder_fired().unwrap();
+
+        let reminder = Reminder::new("Tea".to_string(), reminder::parse_time("2030-10-20T14:00:00+02:00").unwrap());
+        announce(&service, &reminder, reminder.time);
+
+        let signal = fired.next().unwrap();
+        let args = signal.args().unwrap();
+        assert_eq!(args.id(), &reminder.id);
+        assert_eq!(args.name(), "Tea");
+        assert_eq!(args.due(), "2030-10-20T14:00:00+02:00");
+    }
+}
@@ -0,0 +1,255 @@
+//! Emails about fired reminders, for the ones that matter away from the
+//! desk. Only reminders that opted in are sent, either each on its own
+//! right away or collected into one digest per time window.
+
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, FixedOffset, Local};
+use lettre::message::header::ContentType;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::config::{EmailConfig, Security};
+use crate::reminder::Reminder;
+
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A reminder that fired, with the time it was due.
+pub type Fired = (Reminder, DateTime<FixedOffset>);
+
+/// Sends emails through the configured SMTP server.
+pub struct Mailer {
+    transport: SmtpTransport,
+    from: Mailbox,
+    to: Mailbox,
+}
+
+impl Mailer {
+    /// The mailer `config` describes, or `None` when it has no server.
+    pub fn from_config(config: &EmailConfig) -> Result<Option<Self>, String> {
+        let host = config.host.trim();
+        if host.is_empty() {
+            return Ok(None);
+        }
+        let from = config.from.parse().map_err(|e| format!("'{}' is not an email address: {}", config.from, e))?;
+        let to = config.to.parse().map_err(|e| format!("'{}' is not an email address: {}", config.to, e))?;
+
+        let (builder, port) = match config.security {
+            Security::Starttls => (SmtpTransport::starttls_relay(host).map_err(|e| e.to_string())?, 587),
+            Security::Tls => (SmtpTransport::relay(host).map_err(|e| e.to_string())?, 465),
+            Security::None => (SmtpTransport::builder_dangerous(host), 25),
+        };
+        let mut builder = builder.port(config.port.unwrap_or(port)).timeout(Some(SMTP_TIMEOUT));
+        if let Some(username) = &config.username {
+            let password = config.password.clone().unwrap_or_default();
+            builder = builder.credentials(Credentials::new(username.clone(), password));
+        }
+        Ok(Some(Mailer { transport: builder.build(), from, to }))
+    }
+
+    /// Sends one email about everything in `fired`.
+    pub fn send(&self, fired: &[Fired]) -> Result<(), String> {
+        let (subject, body) = compose(fired);
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN)
+            .body(body)
+            .map_err(|e| e.to_string())?;
+        self.transport.send(&message).map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+/// The subject and text of an email about `fired`.
+pub fn compose(fired: &[Fired]) -> (String, String) {
+    let when = |due: &DateTime<FixedOffset>| due.with_timezone(&Local).format("%a %e %b %H:%M").to_string();
+    match fired {
+        [(reminder, due)] => (
+            format!("Reminder: {}", reminder.name),
+            format!("Your reminder '{}' was due on {}.\n", reminder.name, when(due)),
+        ),
+        _ => {
+            let lines: Vec<String> = fired.iter().map(|(reminder, due)| format!("{}  {}", when(due), reminder.name)).collect();
+            (format!("{} reminders", fired.len()), format!("These reminders were due:\n\n{}\n", lines.join("\n")))
+        }
+    }
+}
+
+/// Collects the reminders to email and sends them when their time comes.
+pub struct Outbox {
+    mailer: Arc<Mailer>,
+    /// How long a digest collects reminders, `None` sends each right away.
+    window: Option<Duration>,
+    pending: Vec<Fired>,
+    send_at: Option<Instant>,
+}
+
+impl Outbox {
+    pub fn new(mailer: Mailer, digest_minutes: u32) -> Self {
+        let window = (digest_minutes > 0).then(|| Duration::from_secs(u64::from(digest_minutes) * 60));
+        Outbox { mailer: Arc::new(mailer), window, pending: Vec::new(), send_at: None }
+    }
+
+    /// Emails `reminder`, due at `due`, if it asked for that. A digest starts
+    /// with the first reminder that comes in.
+    pub fn add(&mut self, reminder: &Reminder, due: DateTime<FixedOffset>) {
+        if !reminder.email {
+            return;
+        }
+        self.pending.push((reminder.clone(), due));
+        match self.window {
+            None => self.send_pending(),
+            Some(window) => {
+                self.send_at.get_or_insert_with(|| Instant::now() + window);
+            }
+        }
+    }
+
+    /// How long until the digest is due, if one is being collected.
+    pub fn next_send(&self) -> Option<Duration> {
+        self.send_at.map(|at| at.saturating_duration_since(Instant::now()))
+    }
+
+    /// Sends the digest if its window is over.
+    pub fn send_due(&mut self) {
+        if self.send_at.is_some_and(|at| at <= Instant::now()) {
+            self.send_pending();
+        }
+    }
+
+    /// Sends whatever is collected and waits for it, before the daemon stops.
+    pub fn send_now(&mut self) {
+        self.send_at = None;
+        let fired = std::mem::take(&mut self.pending);
+        if !fired.is_empty() {
+            report(self.mailer.send(&fired), fired.len());
+        }
+    }
+
+    /// Sends what is collected on another thread, SMTP servers can be slow to answer.
+    fn send_pending(&mut self) {
+        self.send_at = None;
+        let fired = std::mem::take(&mut self.pending);
+        let mailer = self.mailer.clone();
+        thread::spawn(move || report(mailer.send(&fired), fired.len()));
+    }
+}
+
+fn report(sent: Result<(), String>, count: usize) {
+    match sent {
+        Ok(()) => println!("Emailed {} reminders", count),
+        Err(e) => println!("Failed to email {} reminders: {}", count, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// A bare-bones SMTP server that accepts everything and passes on each message.
+    fn smtp_stand_in() -> (u16, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (messages, received) = mpsc::channel();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                stream.write_all(b"220 localhost ESMTP stand-in\r\n").unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 {
+                    let command = line.trim_end().to_ascii_uppercase();
+                    let answer = if command == "DATA" {
+                        stream.write_all(b"354 go ahead\r\n").unwrap();
+                        let mut message = String::new();
+                        loop {
+                            let mut data = String::new();
+                            reader.read_line(&mut data).unwrap();
+                            if data == ".\r\n" {
+                                break;
+                            }
+                            message.push_str(&data);
+                        }
+                        messages.send(message).unwrap();
+                        "250 queued"
+                    } else if command == "QUIT" {
+                        "221 bye"
+                    } else {
+                        "250 ok"
+                    };
+                    stream.write_all(format!("{}\r\n", answer).as_bytes()).unwrap();
+                    if command == "QUIT" {
+                        break;
+                    }
+                    line.clear();
+                }
+            }
+        });
+        (port, received)
+    }
+
+    fn mailer(port: u16) -> Mailer {
+        let config = EmailConfig {
+            host: "127.0.0.1".to_string(),
+            port: Some(port),
+            security: Security::None,
+            from: "Rewind <rewind@example.com>".to_string(),
+            to: "me@example.com".to_string(),
+            ..EmailConfig::default()
+        };
+        Mailer::from_config(&config).unwrap().unwrap()
+    }
+
+    fn opted_in(name: &str) -> Reminder {
+        let mut reminder = Reminder::new(name.to_string(), Local::now().fixed_offset());
+        reminder.email = true;
+        reminder
+    }
+
+    #[test]
+    fn reminders_are_emailed_one_by_one_or_as_a_digest() {
+        let (port, received) = smtp_stand_in();
+
+        let mut outbox = Outbox::new(mailer(port), 0);
+        let server_down = opted_in("Server down");
+        outbox.add(&server_down, server_down.time);
+        outbox.add(&Reminder::new("Stretch".to_string(), server_down.time), server_down.time);
+        let message = received.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(message.contains("Subject: Reminder: Server down"), "{}", message);
+        assert!(message.contains("To: me@example.com"));
+        assert!(outbox.next_send().is_none());
+
+        let mut outbox = Outbox::new(mailer(port), 30);
+        let (renew, call) = (opted_in("Renew passport"), opted_in("Call the bank"));
+        outbox.add(&renew, renew.time);
+        outbox.add(&call, call.time);
+        assert!(outbox.next_send().is_some_and(|left| left > Duration::from_secs(29 * 60)));
+        outbox.send_due();
+        assert!(received.try_recv().is_err(), "the digest waits for its window");
+        outbox.send_now();
+        let message = received.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert!(message.contains("Subject: 2 reminders"), "{}", message);
+        assert!(message.contains("Renew passport") && message.contains("Call the bank"));
+        assert!(received.try_recv().is_err(), "Stretch never asked for email");
+    }
+
+    #[test]
+    fn addresses_are_checked() {
+        let config = EmailConfig {
+            host: "smtp.example.com".to_string(),
+            from: "not an address".to_string(),
+            to: "me@example.com".to_string(),
+            ..EmailConfig::default()
+        };
+        assert!(Mailer::from_config(&config).is_err());
+        assert!(Mailer::from_config(&EmailConfig::default()).unwrap().is_none());
+    }
+}
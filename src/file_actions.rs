@@ -1,80 +1,893 @@
-use std::io::{BufReader, Write};
-use crate::Reminder;
-use std::fs::File;
-use std::path::Path;
+use std::collections::HashSet;
+use std::io::{BufReader, BufWriter, Read, Write};
+use crate::alarm::{self, Alarm};
+use crate::hook::Hook;
+use crate::migrations::{self, CURRENT_VERSION};
+use crate::recurrence::Recurrence;
+use crate::reminder::{self, Reminder};
+use crate::store::{LoadError, LoadedReminders, ReminderStore};
+use crate::xml_tree::{self, Element};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::env;
-use xml::reader::{EventReader, XmlEvent};
-
-
-
-pub fn read_reminders() -> Result<Vec<Reminder>, Box<dyn std::error::Error>> {
-    let file = File::open(get_file_path())?;
-    let parser = EventReader::new(BufReader::new(file));
-    
-    let mut reminders = Vec::new();
-    let mut current_reminder = Reminder { name: String::new(), time: String::new() };
-    let mut current_element = String::new();
-    let mut inside_reminder = false;
-    
-    for event in parser {
-        match event? {
-            XmlEvent::StartElement { name, .. } => {
-                let element_name = name.local_name;
-                if element_name == "reminder" {
-                    inside_reminder = true;
-                    current_reminder = Reminder { name: String::new(), time: String::new() };
-                }
-                current_element = element_name;
+use std::ffi::OsString;
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+use chrono::{DateTime, FixedOffset};
+
+// How many saved versions are kept around for restoring
+const MAX_BACKUPS: usize = 10;
+
+/// The original backend: all reminders in one XML file, rewritten on every change.
+pub struct XmlStore {
+    path: PathBuf,
+    // The last reminders read or written, reused until the file changes on disk
+    cache: Option<(FileStamp, Vec<Reminder>)>,
+    // The newest format version read from the file. Saves keep it, so a file
+    // from a newer version is never marked older and migrated again by it
+    version: u32,
+    // Elements beside the reminders that this version doesn't know, written back on save
+    extra: Vec<Element>,
+}
+
+// Enough to notice that another process replaced the file
+type FileStamp = Option<(SystemTime, u64)>;
+
+fn file_stamp(path: &Path) -> FileStamp {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+impl XmlStore {
+    pub fn new(path: PathBuf) -> Self {
+        XmlStore { path, cache: None, version: CURRENT_VERSION, extra: Vec::new() }
+    }
+
+    fn read(&mut self) -> Result<LoadedReminders, Box<dyn std::error::Error>> {
+        let file = File::open(&self.path)?;
+        let (loaded, extra) = parse_document(BufReader::new(file))?;
+        self.version = self.version.max(loaded.version);
+        self.extra = extra;
+        Ok(loaded)
+    }
+
+    /// The current reminders, parsing the file only when it changed since the last look.
+    fn reminders(&mut self) -> Result<&[Reminder], Box<dyn std::error::Error>> {
+        let stamp = file_stamp(&self.path);
+        if self.cache.as_ref().is_none_or(|(cached, _)| *cached != stamp) {
+            let reminders = match stamp {
+                Some(_) => self.read()?.reminders,
+                None => Vec::new(),
+            };
+            self.cache = Some((stamp, reminders));
+        }
+        Ok(self.cache.as_ref().map(|(_, reminders)| reminders.as_slice()).unwrap_or_default())
+    }
+
+    /// Applies `change` to the current reminders and saves the result.
+    fn modify<F>(&mut self, change: F) -> Result<(), Box<dyn std::error::Error>>
+    where
+        F: FnOnce(&mut Vec<Reminder>) -> Result<(), Box<dyn std::error::Error>>,
+    {
+        let mut reminders = self.reminders()?.to_vec();
+        change(&mut reminders)?;
+        self.replace_all(&reminders)
+    }
+}
+
+impl ReminderStore for XmlStore {
+    fn load(&mut self) -> Result<LoadedReminders, Box<dyn std::error::Error>> {
+        let stamp = file_stamp(&self.path);
+        // No file yet just means no reminders yet
+        let loaded = match stamp {
+            Some(_) => self.read()?,
+            None => LoadedReminders { reminders: Vec::new(), errors: Vec::new(), needs_save: false, version: CURRENT_VERSION },
+        };
+        self.cache = Some((stamp, loaded.reminders.clone()));
+        Ok(loaded)
+    }
+
+    fn insert(&mut self, reminder: &Reminder) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|reminders| {
+            if reminders.iter().any(|r| r.id == reminder.id) {
+                return Err(format!("a reminder with id {} already exists", reminder.id).into());
             }
-            XmlEvent::Characters(data) => {
-                if inside_reminder && !data.trim().is_empty() {
-                    match current_element.as_str() {
-                        "name" => current_reminder.name = data.trim().to_string(),
-                        "time" => current_reminder.time = data.trim().to_string(),
-                        _ => {}
-                    }
+            reminders.push(reminder.clone());
+            Ok(())
+        })
+    }
+
+    // A single save, so an import doesn't push every older backup out
+    fn insert_all(&mut self, new: &[Reminder]) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|reminders| {
+            for reminder in new {
+                if reminders.iter().any(|r| r.id == reminder.id) {
+                    return Err(format!("a reminder with id {} already exists", reminder.id).into());
                 }
+                reminders.push(reminder.clone());
             }
-            XmlEvent::EndElement { name } => {
-                if name.local_name == "reminder" {
-                    reminders.push(current_reminder.clone());
-                    inside_reminder = false;
+            Ok(())
+        })
+    }
+
+    fn update(&mut self, reminder: &Reminder) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|reminders| {
+            let existing = reminders
+                .iter_mut()
+                .find(|r| r.id == reminder.id)
+                .ok_or_else(|| format!("no reminder with id {}", reminder.id))?;
+            *existing = reminder.clone();
+            Ok(())
+        })
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.modify(|reminders| {
+            let index = reminders
+                .iter()
+                .position(|r| r.id == id)
+                .ok_or_else(|| format!("no reminder with id {}", id))?;
+            reminders.remove(index);
+            Ok(())
+        })
+    }
+
+    fn replace_all(&mut self, reminders: &[Reminder]) -> Result<(), Box<dyn std::error::Error>> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Forget the cache first, in case only part of the save went through
+        self.cache = None;
+        save_reminders_to(&self.path, reminders, self.version, &self.extra)?;
+        self.cache = Some((file_stamp(&self.path), reminders.to_vec()));
+        Ok(())
+    }
+
+    fn due_between(
+        &mut self,
+        from: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> Result<Vec<Reminder>, Box<dyn std::error::Error>> {
+        Ok(self
+            .reminders()?
+            .iter()
+            .filter(|reminder| reminder.next_alert(from).is_some_and(|due| due <= to))
+            .cloned()
+            .collect())
+    }
+
+    fn location(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn set_aside_unreadable(&mut self) -> std::io::Result<PathBuf> {
+        self.cache = None;
+        let mut aside = self.path.clone().into_os_string();
+        aside.push(format!(".unreadable-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        fs::rename(&self.path, &aside)?;
+        Ok(PathBuf::from(aside))
+    }
+
+    fn has_backups(&self) -> bool {
+        !list_backups_in(&backup_dir(&self.path)).is_empty()
+    }
+
+    fn restore_latest_backup(&mut self) -> Result<(PathBuf, LoadedReminders), Box<dyn std::error::Error>> {
+        self.cache = None;
+        let (backup, (loaded, extra)) = restore_latest_backup_to(&self.path)?;
+        self.version = self.version.max(loaded.version);
+        self.extra = extra;
+        Ok((backup, loaded))
+    }
+}
+
+// The reminders read from a file, and the elements beside them that are not reminders
+type Document = (LoadedReminders, Vec<Element>);
+
+fn parse_document<R: Read>(source: R) -> Result<Document, Box<dyn std::error::Error>> {
+    let mut root = xml_tree::parse(source)?;
+    let version = migrations::migrate(&mut root)?;
+    if version > CURRENT_VERSION {
+        println!("Reminders were saved by a newer version of Rewind (format {}), unknown fields will be kept", version);
+    }
+
+    let mut loaded = LoadedReminders {
+        reminders: Vec::new(),
+        errors: Vec::new(),
+        needs_save: version < CURRENT_VERSION,
+        version,
+    };
+    let mut seen_ids = HashSet::new();
+    for (position, entry) in root.elements().filter(|e| e.name == "reminder").enumerate() {
+        match reminder_from_element(entry) {
+            Ok(mut reminder) => {
+                // Hand-edited files may leave out an id or copy one
+                if reminder.id.is_empty() || seen_ids.contains(&reminder.id) {
+                    reminder.id = reminder::derived_id(position, &reminder.name, &reminder::format_time(&reminder.time));
+                    loaded.needs_save = true;
                 }
+                seen_ids.insert(reminder.id.clone());
+                loaded.reminders.push(reminder);
+            }
+            Err(reason) => loaded.errors.push(LoadError {
+                position,
+                name: entry.child("name").map(|name| name.text()).unwrap_or_default(),
+                reason,
+            }),
+        }
+    }
+    // Added by newer versions, carried along unchanged like unknown reminder fields
+    let extra = root.elements().filter(|e| e.name != "reminder").cloned().collect();
+
+    Ok((loaded, extra))
+}
+
+/// Turns one `<reminder>` element into a `Reminder`. Also used for the
+/// entries of other backends, which store each reminder as an element.
+pub fn reminder_from_element(entry: &Element) -> Result<Reminder, String> {
+    let mut id = String::new();
+    let mut name = String::new();
+    let mut time = None;
+    let mut recurrence = None;
+    let mut exdates = Vec::new();
+    let mut tags = Vec::new();
+    let mut email = false;
+    let mut snoozed_until = None;
+    let mut snooze_count = 0;
+    let mut acknowledged_at = None;
+    let mut completed_at = None;
+    let mut hook = None;
+    let mut alarm = None;
+    let mut extra = Vec::new();
+
+    for child in entry.elements() {
+        match child.name.as_str() {
+            // Names are kept exactly as typed, everything else is machine-written
+            "name" => name = child.text(),
+            "id" => id = child.text().trim().to_string(),
+            "time" => time = Some(reminder::parse_time(child.text().trim())?),
+            "recurrence" => recurrence = Some(Recurrence::parse(child.text().trim())?),
+            "exdate" => exdates.push(reminder::parse_time(child.text().trim())?),
+            "tag" => tags.push(child.text()),
+            "email" => email = true,
+            "snoozed_until" => snoozed_until = Some(reminder::parse_time(child.text().trim())?),
+            "snooze_count" => {
+                snooze_count = child
+                    .text()
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("'{}' is not a snooze count", child.text().trim()))?
+            }
+            "acknowledged_at" => acknowledged_at = Some(reminder::parse_time(child.text().trim())?),
+            "completed_at" => completed_at = Some(reminder::parse_time(child.text().trim())?),
+            "hook" => hook = Some(hook_from_element(child)?),
+            "alarm" => alarm = Some(alarm_from_element(child)?),
+            // Fields added by newer versions are carried along unchanged
+            _ => extra.push(child.clone()),
+        }
+    }
+
+    let time = time.ok_or("missing <time> element")?;
+    let recurrence = recurrence.map(|mut rule: Recurrence| {
+        rule.exceptions = exdates;
+        rule
+    });
+    Ok(Reminder {
+        id,
+        name,
+        time,
+        recurrence,
+        tags,
+        email,
+        snoozed_until,
+        snooze_count,
+        acknowledged_at,
+        completed_at,
+        hook,
+        alarm,
+        extra,
+    })
+}
+
+pub fn reminder_to_element(reminder: &Reminder) -> Element {
+    let mut entry = Element::new("reminder");
+    entry.push(Element::with_text("id", &reminder.id));
+    entry.push(Element::with_text("name", &reminder.name));
+    entry.push(Element::with_text("time", &reminder::format_time(&reminder.time)));
+    if let Some(rule) = &reminder.recurrence {
+        entry.push(Element::with_text("recurrence", &rule.to_rrule()));
+        for exdate in &rule.exceptions {
+            entry.push(Element::with_text("exdate", &reminder::format_time(exdate)));
+        }
+    }
+    for tag in &reminder.tags {
+        entry.push(Element::with_text("tag", tag));
+    }
+    if reminder.email {
+        entry.push(Element::new("email"));
+    }
+    if let Some(until) = &reminder.snoozed_until {
+        entry.push(Element::with_text("snoozed_until", &reminder::format_time(until)));
+        entry.push(Element::with_text("snooze_count", &reminder.snooze_count.to_string()));
+    }
+    if let Some(acknowledged) = &reminder.acknowledged_at {
+        entry.push(Element::with_text("acknowledged_at", &reminder::format_time(acknowledged)));
+    }
+    if let Some(completed) = &reminder.completed_at {
+        entry.push(Element::with_text("completed_at", &reminder::format_time(completed)));
+    }
+    if let Some(hook) = &reminder.hook {
+        entry.push(hook_to_element(hook));
+    }
+    if let Some(alarm) = &reminder.alarm {
+        entry.push(alarm_to_element(alarm));
+    }
+    for extra in &reminder.extra {
+        entry.push(extra.clone());
+    }
+    entry
+}
+
+/// Reads a `<hook>`: `<program>`, then an `<arg>` per argument, an optional
+/// `<cwd>` and an `<env name="...">` per variable. The timeout is in seconds.
+fn hook_from_element(element: &Element) -> Result<Hook, String> {
+    let program = element.child("program").map(|program| program.text()).unwrap_or_default();
+    if program.is_empty() {
+        return Err("<hook> without a <program>".to_string());
+    }
+    let mut hook = Hook::new(program);
+    for child in element.elements() {
+        match child.name.as_str() {
+            "arg" => hook.args.push(child.text()),
+            "cwd" => hook.cwd = Some(PathBuf::from(child.text())),
+            "env" => {
+                let name = child.attribute("name").ok_or("<env> without a name")?;
+                hook.env.push((name.to_string(), child.text()));
             }
             _ => {}
         }
     }
-    
-    Ok(reminders)
+    if let Some(timeout) = element.attribute("timeout") {
+        let seconds = timeout.trim().parse().map_err(|_| format!("'{}' is not a hook timeout", timeout))?;
+        hook.timeout = Duration::from_secs(seconds);
+    }
+    Ok(hook)
+}
+
+fn hook_to_element(hook: &Hook) -> Element {
+    let mut element = Element::new("hook");
+    element.set_attribute("timeout", &hook.timeout.as_secs().to_string());
+    element.push(Element::with_text("program", &hook.program));
+    for arg in &hook.args {
+        element.push(Element::with_text("arg", arg));
+    }
+    if let Some(cwd) = &hook.cwd {
+        element.push(Element::with_text("cwd", &cwd.to_string_lossy()));
+    }
+    for (name, value) in &hook.env {
+        let mut env = Element::with_text("env", value);
+        env.set_attribute("name", name);
+        element.push(env);
+    }
+    element
+}
+
+/// Reads an `<alarm>`, with the minutes between repeats and how many there
+/// are at most as attributes, and an optional `<sound>` file. Numbers out of
+/// the dialog's range are brought back into it.
+fn alarm_from_element(element: &Element) -> Result<Alarm, String> {
+    let number = |name: &str, default: u32| match element.attribute(name) {
+        Some(text) => text.trim().parse().map_err(|_| format!("'{}' is not a number of alarm {}", text, name)),
+        None => Ok(default),
+    };
+    Ok(Alarm {
+        sound: element.child("sound").map(|sound| PathBuf::from(sound.text())),
+        every_minutes: number("every", alarm::DEFAULT_EVERY_MINUTES)?.clamp(1, alarm::MAX_EVERY_MINUTES),
+        repeats: number("repeats", alarm::DEFAULT_REPEATS)?.min(alarm::MAX_REPEATS),
+    })
+}
+
+fn alarm_to_element(alarm: &Alarm) -> Element {
+    let mut element = Element::new("alarm");
+    element.set_attribute("every", &alarm.every_minutes.to_string());
+    element.set_attribute("repeats", &alarm.repeats.to_string());
+    if let Some(sound) = &alarm.sound {
+        element.push(Element::with_text("sound", &sound.to_string_lossy()));
+    }
+    element
+}
+
+/// Set once at startup from `--data-dir` or `REWIND_DATA_DIR`.
+static DATA_DIR_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+pub fn set_data_dir(dir: PathBuf) {
+    if DATA_DIR_OVERRIDE.set(dir).is_err() {
+        println!("Data directory was already set, ignoring the new one");
+    }
+}
+
+/// Where reminders live: the override if one was given, otherwise
+/// `$XDG_DATA_HOME/rewind`, falling back to `~/.local/share/rewind`.
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = DATA_DIR_OVERRIDE.get() {
+        return dir.clone();
+    }
+    default_data_dir()
+}
+
+fn default_data_dir() -> PathBuf {
+    data_dir_in(env::var_os("XDG_DATA_HOME"), &home_dir())
+}
+
+/// The data directory for the given `$XDG_DATA_HOME` and home directory.
+fn data_dir_in(data_home: Option<OsString>, home: &Path) -> PathBuf {
+    // The spec says relative values must be ignored
+    match data_home.map(PathBuf::from) {
+        Some(dir) if dir.is_absolute() => dir.join("rewind"),
+        _ => home.join(".local/share/rewind"),
+    }
+}
+
+pub fn home_dir() -> PathBuf {
+    env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."))
+}
+
+/// Moves reminders saved by older versions from `~/.cache`, which cleanup
+/// tools like to empty, into the data directory. Returns what was moved.
+/// Nothing happens when a custom data directory is used or the new file already exists.
+pub fn migrate_legacy_file() -> Result<Option<String>, Box<dyn std::error::Error>> {
+    if DATA_DIR_OVERRIDE.get().is_some() {
+        return Ok(None);
+    }
+    move_legacy_file(&home_dir(), &data_dir())
+}
+
+/// Moves the reminders an older version kept under `home` into `data_dir`.
+fn move_legacy_file(home: &Path, data_dir: &Path) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    let cache_dir = home.join(".cache");
+    let legacy_file = cache_dir.join("Rewinders.xml");
+    let new_file = data_dir.join("reminders.xml");
+    if !legacy_file.exists() {
+        return Ok(None);
+    }
+    if new_file.exists() {
+        println!("Leaving {} alone, {} already exists", legacy_file.display(), new_file.display());
+        return Ok(None);
+    }
+
+    fs::create_dir_all(data_dir)?;
+    move_file(&legacy_file, &new_file)?;
+    let mut report = format!("Moved your reminders from {} to {}", legacy_file.display(), new_file.display());
+
+    let legacy_backups = cache_dir.join("Rewinders-backups");
+    if legacy_backups.is_dir() {
+        let backups = list_backups_in(&legacy_backups);
+        fs::create_dir_all(backup_dir(&new_file))?;
+        for backup in &backups {
+            if let Some(name) = backup.file_name() {
+                move_file(backup, &backup_dir(&new_file).join(name))?;
+            }
+        }
+        let _ = fs::remove_dir(&legacy_backups);
+        report.push_str(&format!(", along with {} backups", backups.len()));
+    }
+
+    println!("{}", report);
+    Ok(Some(report))
 }
 
-fn get_file_path() -> String {
-    let home = env::var("HOME").unwrap_or_else(|_| ".".to_string());
-    format!("{}/.cache/Rewinders.xml", home)
+/// Renames when possible and copies otherwise, since `~/.cache` may be on another file system.
+fn move_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    File::open(to)?.sync_all()?;
+    fs::remove_file(from)
 }
 
-pub fn does_file_exist() {
-    let file_path = get_file_path();
-    if !Path::new(&file_path).exists() {
-        println!("File doesn't exist at {}, will be created on first save", file_path);
+pub fn does_file_exist(file_path: &Path) {
+    if !file_path.exists() {
+        println!("File doesn't exist at {}, will be created on first save", file_path.display());
     } else {
-        println!("Found existing file at {}", file_path);
+        println!("Found existing file at {}", file_path.display());
+    }
+}
+
+/// Saves crash-safely: the document goes to a temporary file that is synced
+/// and then renamed over `path`, so readers only ever see a complete file.
+/// Every saved version is also kept as a timestamped backup.
+fn save_reminders_to(path: &Path, reminders: &[Reminder], version: u32, extra: &[Element]) -> Result<(), Box<dyn std::error::Error>> {
+    replace_file(path, |file| write_document(file, reminders, version, extra))?;
+
+    if let Err(e) = keep_backup(path) {
+        println!("Could not back up {}: {}", path.display(), e);
+    }
+    Ok(())
+}
+
+fn replace_file<F>(path: &Path, fill: F) -> Result<(), Box<dyn std::error::Error>>
+where
+    F: FnOnce(&mut BufWriter<&File>) -> Result<(), Box<dyn std::error::Error>>,
+{
+    let mut temp_name = path.as_os_str().to_owned();
+    temp_name.push(".tmp");
+    let temp_path = PathBuf::from(temp_name);
+
+    let result = (|| {
+        let file = File::create(&temp_path)?;
+        let mut writer = BufWriter::new(&file);
+        fill(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        // Make the rename itself durable
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    result
+}
+
+fn backup_dir(path: &Path) -> PathBuf {
+    path.with_file_name("backups")
+}
+
+fn keep_backup(path: &Path) -> std::io::Result<()> {
+    let dir = backup_dir(path);
+    fs::create_dir_all(&dir)?;
+    let stamp = chrono::Local::now().format("%Y%m%d-%H%M%S-%6f");
+    fs::copy(path, dir.join(format!("reminders-{}.xml", stamp)))?;
+
+    for old in list_backups_in(&dir).into_iter().skip(MAX_BACKUPS) {
+        fs::remove_file(old)?;
+    }
+    Ok(())
+}
+
+/// Backups next to the reminders file, newest first.
+fn list_backups_in(dir: &Path) -> Vec<PathBuf> {
+    let mut backups: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| p.extension().is_some_and(|ext| ext == "xml"))
+                .collect()
+        })
+        .unwrap_or_default();
+    // The timestamp in the name sorts chronologically
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+fn restore_latest_backup_to(path: &Path) -> Result<(PathBuf, Document), Box<dyn std::error::Error>> {
+    for backup in list_backups_in(&backup_dir(path)) {
+        let Ok(contents) = fs::read(&backup) else {
+            continue;
+        };
+        match parse_document(contents.as_slice()) {
+            Ok(parsed) => {
+                replace_file(path, |file| Ok(file.write_all(&contents)?))?;
+                return Ok((backup, parsed));
+            }
+            Err(e) => println!("Skipping unreadable backup {}: {}", backup.display(), e),
+        }
     }
+    Err("no readable backup was found".into())
 }
 
-pub fn write_reminders(reminders: &Vec<Reminder>) -> Result<(), Box<dyn std::error::Error>> {
-    let mut file = File::create(get_file_path())?;
-    
-    writeln!(file, "<reminders>")?;
-    
+fn write_document<W: Write>(sink: W, reminders: &[Reminder], version: u32, extra: &[Element]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut root = Element::new("reminders");
+    root.set_attribute("version", &version.to_string());
     for reminder in reminders {
-        writeln!(file, "  <reminder>")?;
-        writeln!(file, "    <name>{}</name>", reminder.name)?;
-        writeln!(file, "    <time>{}</time>", reminder.time)?;
-        writeln!(file, "  </reminder>")?;
-    }
-    
-    writeln!(file, "</reminders>")?;
-    
+        root.push(reminder_to_element(reminder));
+    }
+    for element in extra {
+        root.push(element.clone());
+    }
+    xml_tree::write(sink, &root)?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recurrence::Frequency;
+    use crate::test_support::ScratchDir;
+    use chrono::DateTime;
+
+    fn parse_reminders<R: Read>(source: R) -> Result<LoadedReminders, Box<dyn std::error::Error>> {
+        Ok(parse_document(source)?.0)
+    }
+
+    fn round_trip(reminders: &[Reminder]) -> LoadedReminders {
+        let mut buffer = Vec::new();
+        write_document(&mut buffer, reminders, CURRENT_VERSION, &[]).expect("writing should succeed");
+        parse_reminders(buffer.as_slice()).expect("written document should parse")
+    }
+
+    fn sample(name: &str) -> Reminder {
+        let time = DateTime::parse_from_rfc3339("2025-06-24T21:30:00-05:00").unwrap();
+        Reminder::new(name.to_string(), time)
+    }
+
+    #[test]
+    fn names_round_trip_exactly() {
+        let names = [
+            "Buy milk & eggs",
+            "<draft> review",
+            "</name></reminder></reminders>",
+            "a ]]> b <![CDATA[ c",
+            "&amp; is not an entity here",
+            "quotes \" and ' apostrophes",
+            "  leading and trailing spaces  ",
+            " ",
+            "",
+            "tab\tseparated\nand multi\nline",
+            "windows\r\nline endings and a lone \r",
+            "ünïcødé, 日本語, עברית, emoji 🎉👍🏽",
+            "\u{200B}zero width\u{FEFF}",
+        ];
+        let reminders: Vec<Reminder> = names.iter().map(|name| sample(name)).collect();
+
+        let loaded = round_trip(&reminders);
+
+        assert!(loaded.errors.is_empty(), "unexpected errors: {:?}", loaded.errors);
+        assert!(!loaded.needs_save);
+        let read_names: Vec<&str> = loaded.reminders.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(read_names, names);
+    }
+
+    #[test]
+    fn ids_times_and_recurrence_round_trip() {
+        let mut weekly = sample("Standup");
+        let mut rule = Recurrence::new(Frequency::Weekly);
+        rule.interval = 2;
+        rule.weekdays = vec![chrono::Weekday::Mon, chrono::Weekday::Thu];
+        rule.count = Some(12);
+        rule.exceptions.push(DateTime::parse_from_rfc3339("2025-07-07T21:30:00-05:00").unwrap());
+        weekly.recurrence = Some(rule);
+        weekly.tags = vec!["work".to_string(), "team sync".to_string()];
+        weekly.email = true;
+        let reminders = vec![sample("Once"), weekly];
+
+        let loaded = round_trip(&reminders);
+
+        assert_eq!(loaded.reminders.len(), 2);
+        for (written, read) in reminders.iter().zip(&loaded.reminders) {
+            assert_eq!(written.id, read.id);
+            assert_eq!(written.time, read.time);
+            assert_eq!(written.time.offset(), read.time.offset());
+            assert_eq!(written.recurrence, read.recurrence);
+            assert_eq!(written.tags, read.tags);
+            assert_eq!(written.email, read.email);
+        }
+    }
+
+    #[test]
+    fn hooks_and_alarms_round_trip() {
+        let mut hook = Hook::new("xdg-open".to_string());
+        hook.args = vec!["https://example.com/?a=1&b=<2>".to_string(), String::new()];
+        hook.cwd = Some(PathBuf::from("/home/me/my projects"));
+        hook.env = vec![("LANG".to_string(), "C".to_string()), ("EMPTY".to_string(), String::new())];
+        hook.timeout = Duration::from_secs(5);
+        let mut with_hook = sample("Open the dashboard");
+        with_hook.hook = Some(hook);
+        with_hook.alarm = Some(Alarm { sound: Some(PathBuf::from("/usr/share/sounds/bell.oga")), every_minutes: 2, repeats: 10 });
+        with_hook.acknowledged_at = Some(with_hook.time);
+
+        // Edited by hand far past what the dialog allows
+        let mut endless = sample("Endless");
+        endless.alarm = Some(Alarm { sound: None, every_minutes: u32::MAX, repeats: u32::MAX });
+        let mut constant = sample("Constant");
+        constant.alarm = Some(Alarm { sound: None, every_minutes: 0, repeats: 3 });
+
+        let loaded = round_trip(&[with_hook.clone(), sample("Plain"), endless, constant]);
+
+        assert!(loaded.errors.is_empty(), "unexpected errors: {:?}", loaded.errors);
+        assert_eq!(loaded.reminders[0].hook, with_hook.hook);
+        assert_eq!(loaded.reminders[0].alarm, with_hook.alarm);
+        assert_eq!(loaded.reminders[0].acknowledged_at, with_hook.acknowledged_at);
+        assert_eq!(loaded.reminders[1].hook, None);
+        let endless = loaded.reminders[2].alarm.as_ref().unwrap();
+        assert_eq!((endless.every_minutes, endless.repeats), (alarm::MAX_EVERY_MINUTES, alarm::MAX_REPEATS));
+        assert_eq!(loaded.reminders[3].alarm.as_ref().unwrap().every_minutes, 1);
+    }
+
+    #[test]
+    fn characters_xml_cannot_hold_are_dropped() {
+        let loaded = round_trip(&[sample("bell\u{7} and nul\u{0}")]);
+
+        assert!(loaded.errors.is_empty());
+        assert_eq!(loaded.reminders[0].name, "bell and nul");
+    }
+
+    #[test]
+    fn saving_replaces_the_file_and_keeps_limited_backups() {
+        let dir = ScratchDir::new("backups");
+        let path = dir.join("reminders.xml");
+
+        for count in 0..MAX_BACKUPS + 3 {
+            let reminders: Vec<Reminder> = (0..count).map(|i| sample(&format!("#{}", i))).collect();
+            save_reminders_to(&path, &reminders, CURRENT_VERSION, &[]).unwrap();
+        }
+
+        let saved = parse_reminders(File::open(&path).unwrap()).unwrap();
+        assert_eq!(saved.reminders.len(), MAX_BACKUPS + 2);
+        assert!(!dir.join("reminders.xml.tmp").exists());
+        assert_eq!(list_backups_in(&backup_dir(&path)).len(), MAX_BACKUPS);
+    }
+
+    #[test]
+    fn restore_uses_the_newest_readable_backup() {
+        let dir = ScratchDir::new("restore");
+        let path = dir.join("reminders.xml");
+        save_reminders_to(&path, &[sample("older")], CURRENT_VERSION, &[]).unwrap();
+        save_reminders_to(&path, &[sample("newer"), sample("newest")], CURRENT_VERSION, &[]).unwrap();
+        let backups = list_backups_in(&backup_dir(&path));
+        fs::write(&backups[0], "<reminders><reminder>").unwrap();
+        fs::write(&path, "<reminders><remin").unwrap();
+
+        let (used, (loaded, _)) = restore_latest_backup_to(&path).unwrap();
+
+        assert_eq!(used, backups[1]);
+        assert_eq!(loaded.reminders[0].name, "older");
+        let restored = parse_reminders(File::open(&path).unwrap()).unwrap();
+        assert_eq!(restored.reminders.len(), 1);
+    }
+
+    #[test]
+    fn legacy_files_still_load() {
+        let legacy = "<reminders>\n  <reminder>\n    <name>Walk the dog</name>\n    <time>2025-06-24T21:30:00</time>\n  </reminder>\n</reminders>\n";
+
+        let loaded = parse_reminders(legacy.as_bytes()).unwrap();
+
+        assert!(loaded.errors.is_empty());
+        assert!(loaded.needs_save);
+        assert_eq!(loaded.version, 1);
+        assert_eq!(loaded.reminders[0].name, "Walk the dog");
+        assert!(!loaded.reminders[0].id.is_empty());
+    }
+
+    #[test]
+    fn legacy_ids_are_stable_until_saved() {
+        let legacy = "<reminders><reminder><name>Walk the dog</name><time>2025-06-24T21:30:00</time></reminder></reminders>";
+
+        let first = parse_reminders(legacy.as_bytes()).unwrap();
+        let second = parse_reminders(legacy.as_bytes()).unwrap();
+
+        assert_eq!(first.reminders[0].id, second.reminders[0].id);
+    }
+
+    #[test]
+    fn documents_are_written_with_the_current_version() {
+        let mut buffer = Vec::new();
+        write_document(&mut buffer, &[sample("Versioned")], CURRENT_VERSION, &[]).unwrap();
+
+        let root = xml_tree::parse(buffer.as_slice()).unwrap();
+        assert_eq!(migrations::document_version(&root), Ok(CURRENT_VERSION));
+    }
+
+    #[test]
+    fn unknown_fields_from_newer_versions_survive_a_save() {
+        let newer = "<reminders version=\"99\"><reminder>\
+            <id>a</id><name>Future</name><time>2025-06-24T21:30:00+02:00</time>\
+            <notes kind=\"markdown\">Bring <b>snacks</b> &amp; drinks</notes>\
+            <priority>high</priority>\
+            </reminder></reminders>";
+
+        let loaded = parse_reminders(newer.as_bytes()).unwrap();
+        assert_eq!(loaded.version, 99);
+        assert!(!loaded.needs_save);
+        let mut buffer = Vec::new();
+        write_document(&mut buffer, &loaded.reminders, loaded.version, &[]).unwrap();
+        let reloaded = parse_reminders(buffer.as_slice()).unwrap();
+
+        assert_eq!(reloaded.version, 99);
+        let extra = &reloaded.reminders[0].extra;
+        assert_eq!(extra.len(), 2);
+        assert_eq!(extra[0].name, "notes");
+        assert_eq!(extra[0].attribute("kind"), Some("markdown"));
+        assert_eq!(extra[0].child("b").map(|b| b.text()), Some("snacks".to_string()));
+        assert_eq!(extra[1], xml_tree::Element::with_text("priority", "high"));
+    }
+
+    #[test]
+    fn unknown_elements_beside_the_reminders_survive_a_save() {
+        let dir = ScratchDir::new("newer-elements");
+        let path = dir.join("reminders.xml");
+        fs::write(&path, "<reminders version=\"99\"><reminder><id>a</id><name>Future</name>\
+            <time>2025-06-24T21:30:00+02:00</time></reminder>\
+            <tags><tag color=\"red\">Home</tag></tags></reminders>").unwrap();
+
+        let mut store = XmlStore::new(path.clone());
+        store.load().unwrap();
+        store.insert(&sample("Added")).unwrap();
+
+        let (loaded, extra) = parse_document(File::open(&path).unwrap()).unwrap();
+        assert_eq!(loaded.reminders.len(), 2);
+        assert_eq!(extra.len(), 1);
+        assert_eq!(extra[0].name, "tags");
+        assert_eq!(extra[0].child("tag").and_then(|tag| tag.attribute("color")), Some("red"));
+        assert_eq!(extra[0].child("tag").map(|tag| tag.text()), Some("Home".to_string()));
+    }
+
+    #[test]
+    fn only_the_store_that_read_a_newer_file_keeps_its_version() {
+        let dir = ScratchDir::new("newer-version");
+        let newer = dir.join("newer.xml");
+        let other = dir.join("other.xml");
+        fs::write(&newer, "<reminders version=\"99\"><reminder><id>a</id><name>Future</name>\
+            <time>2025-06-24T21:30:00+02:00</time></reminder></reminders>").unwrap();
+
+        let mut newer_store = XmlStore::new(newer.clone());
+        newer_store.load().unwrap();
+        newer_store.insert(&sample("Added")).unwrap();
+        XmlStore::new(other.clone()).insert(&sample("Elsewhere")).unwrap();
+
+        let version = |path: &Path| parse_reminders(File::open(path).unwrap()).unwrap().version;
+        assert_eq!(version(&newer), 99);
+        assert_eq!(version(&other), CURRENT_VERSION);
+    }
+
+    #[test]
+    fn data_lives_under_xdg_data_home_or_else_in_the_home_directory() {
+        let home = Path::new("/home/me");
+        assert_eq!(data_dir_in(Some("/data".into()), home), Path::new("/data/rewind"));
+        assert_eq!(data_dir_in(None, home), Path::new("/home/me/.local/share/rewind"));
+        assert_eq!(data_dir_in(Some("data".into()), home), Path::new("/home/me/.local/share/rewind"), "relative ones are ignored");
+        assert_eq!(data_dir_in(Some("".into()), home), Path::new("/home/me/.local/share/rewind"));
+    }
+
+    #[test]
+    fn legacy_files_move_into_the_data_directory_once() {
+        let home = ScratchDir::new("legacy-home");
+        let data = home.join(".local/share/rewind");
+        assert!(move_legacy_file(home.path(), &data).unwrap().is_none(), "nothing to move");
+        assert!(!data.exists());
+
+        let legacy = home.join(".cache/Rewinders.xml");
+        let legacy_backups = home.join(".cache/Rewinders-backups");
+        fs::create_dir_all(&legacy_backups).unwrap();
+        save_reminders_to(&legacy, &[sample("From the cache")], CURRENT_VERSION, &[]).unwrap();
+        for stamp in ["20240101-000000-000000", "20240102-000000-000000"] {
+            fs::write(legacy_backups.join(format!("Rewinders-{}.xml", stamp)), "<reminders/>").unwrap();
+        }
+
+        let report = move_legacy_file(home.path(), &data).unwrap().unwrap();
+        let moved = data.join("reminders.xml");
+        assert_eq!(
+            report,
+            format!("Moved your reminders from {} to {}, along with 2 backups", legacy.display(), moved.display())
+        );
+        assert!(!legacy.exists() && !legacy_backups.exists());
+        assert_eq!(XmlStore::new(moved.clone()).load().unwrap().reminders[0].name, "From the cache");
+        assert_eq!(list_backups_in(&backup_dir(&moved)).len(), 2);
+
+        // A file left in the cache again never replaces the one in use
+        fs::write(&legacy, "<reminders/>").unwrap();
+        assert!(move_legacy_file(home.path(), &data).unwrap().is_none());
+        assert!(legacy.exists());
+        assert_eq!(XmlStore::new(moved).load().unwrap().reminders[0].name, "From the cache");
+    }
+
+    #[test]
+    fn malformed_entries_are_reported_not_fatal() {
+        let document = "<reminders><reminder><name>Bad</name><time>tomorrow-ish</time></reminder>\
+            <reminder><name>Good</name><time>2025-06-24T21:30:00+02:00</time></reminder></reminders>";
+
+        let loaded = parse_reminders(document.as_bytes()).unwrap();
+
+        assert_eq!(loaded.reminders.len(), 1);
+        assert_eq!(loaded.reminders[0].name, "Good");
+        assert_eq!(loaded.errors.len(), 1);
+        assert_eq!(loaded.errors[0].name, "Bad");
+    }
+}
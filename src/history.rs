@@ -0,0 +1,68 @@
+//! Reminders that were marked done for good. They stay in the store with
+//! their completion time until they are purged.
+
+use std::error::Error;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::reminder::Reminder;
+use crate::store::ReminderStore;
+
+/// Completed reminders whose name contains `query`, ignoring case, most
+/// recently completed first. An empty query matches all of them.
+pub fn search<'a>(reminders: &'a [Reminder], query: &str) -> Vec<&'a Reminder> {
+    let query = query.trim().to_lowercase();
+    let mut found: Vec<&Reminder> = reminders
+        .iter()
+        .filter(|reminder| reminder.completed_at.is_some())
+        .filter(|reminder| reminder.name.to_lowercase().contains(&query))
+        .collect();
+    found.sort_by_key(|reminder| std::cmp::Reverse(reminder.completed_at));
+    found
+}
+
+/// Deletes the reminders completed before `before` and returns their ids.
+pub fn purge(store: &mut dyn ReminderStore, before: DateTime<FixedOffset>) -> Result<Vec<String>, Box<dyn Error>> {
+    let old: Vec<String> = store
+        .load()?
+        .reminders
+        .into_iter()
+        .filter(|reminder| reminder.completed_at.is_some_and(|completed| completed < before))
+        .map(|reminder| reminder.id)
+        .collect();
+    // One at a time, so entries that could not be read are left alone
+    for id in &old {
+        store.delete(id)?;
+    }
+    Ok(old)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_actions::XmlStore;
+    use crate::reminder;
+    use crate::test_support::scratch_store;
+
+    #[test]
+    fn purging_keeps_recent_and_open_reminders() {
+        let (_dir, mut store) = scratch_store("history", "reminders.xml", XmlStore::new);
+        let time = |text: &str| reminder::parse_time(text).unwrap();
+
+        let mut old = Reminder::new("Renew passport".to_string(), time("2026-01-10T09:00:00+01:00"));
+        old.complete(time("2026-01-10T09:05:00+01:00"));
+        let mut recent = Reminder::new("Water plants".to_string(), time("2026-10-10T09:00:00+02:00"));
+        recent.complete(time("2026-10-12T18:00:00+02:00"));
+        let open = Reminder::new("Call the plumber".to_string(), time("2026-11-02T10:00:00+01:00"));
+        store.insert_all(&[old.clone(), recent.clone(), open.clone()]).unwrap();
+
+        let loaded = store.load().unwrap().reminders;
+        let names: Vec<&str> = search(&loaded, "").iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["Water plants", "Renew passport"]);
+        assert_eq!(search(&loaded, "PASS").len(), 1);
+
+        assert_eq!(purge(&mut store, time("2026-07-01T00:00:00+02:00")).unwrap(), [old.id]);
+        let left: Vec<String> = store.load().unwrap().reminders.into_iter().map(|r| r.id).collect();
+        assert_eq!(left, [recent.id, open.id]);
+    }
+}
@@ -0,0 +1,352 @@
+//! Commands a reminder runs when it fires, like opening a link or locking
+//! the screen. They are started directly, never through a shell, and each
+//! run is written to the reminder's own log.
+
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, FixedOffset, Local};
+
+use crate::file_actions;
+use crate::reminder::{self, Reminder};
+
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+const LOG_DIR: &str = "hooks";
+// A log bigger than this is moved aside before the next entry is added
+pub const LOG_LIMIT: u64 = 256 * 1024;
+// How much of each output stream ends up in the log
+const OUTPUT_LIMIT: usize = 16 * 1024;
+// How long output is still read after the command exits. Programs like
+// xdg-open leave the browser they start holding on to it.
+const OUTPUT_GRACE: Duration = Duration::from_secs(1);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hook {
+    pub program: String,
+    pub args: Vec<String>,
+    /// Where it runs, the daemon's own directory when `None`.
+    pub cwd: Option<PathBuf>,
+    /// Added to the daemon's environment.
+    pub env: Vec<(String, String)>,
+    /// It is killed when it takes longer.
+    pub timeout: Duration,
+}
+
+/// How a run ended.
+#[derive(Debug)]
+pub enum Outcome {
+    Exited(ExitStatus),
+    TimedOut,
+    NotStarted(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Exited(status) => write!(f, "{}", status),
+            Outcome::TimedOut => write!(f, "killed after running too long"),
+            Outcome::NotStarted(e) => write!(f, "could not be started: {}", e),
+        }
+    }
+}
+
+/// One run of a hook, as it goes into the log.
+#[derive(Debug)]
+pub struct Run {
+    pub started: DateTime<FixedOffset>,
+    pub outcome: Outcome,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl Hook {
+    pub fn new(program: String) -> Self {
+        Hook { program, args: Vec::new(), cwd: None, env: Vec::new(), timeout: DEFAULT_TIMEOUT }
+    }
+
+    /// Runs the hook for `reminder`, which is due at `due`, and waits for it.
+    /// It also gets `REWIND_ID`, `REWIND_NAME` and `REWIND_DUE` in its environment.
+    pub fn run(&self, reminder: &Reminder, due: DateTime<FixedOffset>) -> Run {
+        let started = Local::now().fixed_offset();
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .envs(self.env.iter().map(|(name, value)| (name, value)))
+            .env("REWIND_ID", &reminder.id)
+            .env("REWIND_NAME", &reminder.name)
+            .env("REWIND_DUE", reminder::format_time(&due))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // Stopping the daemon with Ctrl+C should not take the hook with it
+            .process_group(0);
+        if let Some(dir) = &self.cwd {
+            command.current_dir(dir);
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                let outcome = Outcome::NotStarted(e.to_string());
+                return Run { started, outcome, stdout: String::new(), stderr: String::new() };
+            }
+        };
+        let stdout = collect(child.stdout.take());
+        let stderr = collect(child.stderr.take());
+        let outcome = wait(&mut child, self.timeout);
+
+        let deadline = Instant::now() + OUTPUT_GRACE;
+        while Instant::now() < deadline && !(stdout.1.is_finished() && stderr.1.is_finished()) {
+            thread::sleep(POLL_INTERVAL);
+        }
+        Run { started, outcome, stdout: collected(&stdout.0), stderr: collected(&stderr.0) }
+    }
+}
+
+/// Runs `reminder`'s hook, if it has one, on its own thread and logs how it went.
+pub fn start(reminder: &Reminder, due: DateTime<FixedOffset>) {
+    let Some(hook) = reminder.hook.clone() else {
+        return;
+    };
+    let reminder = reminder.clone();
+    thread::spawn(move || {
+        let run = hook.run(&reminder, due);
+        println!("Command for '{}' {}", reminder.name, run.outcome);
+        if let Err(e) = run.append_to(&log_path(&reminder.id)) {
+            println!("Failed to log the command for '{}': {}", reminder.name, e);
+        }
+    });
+}
+
+/// Waits for `child` to exit, killing it along with everything it started
+/// once `timeout` has passed.
+fn wait(child: &mut Child, timeout: Duration) -> Outcome {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Outcome::Exited(status),
+            Ok(None) if Instant::now() >= deadline => {
+                // The hook leads a process group of its own, see `run`
+                // SAFETY: kill only sends a signal, the group is still there
+                // because its leader has not been reaped yet
+                unsafe { libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL) };
+                let _ = child.wait();
+                return Outcome::TimedOut;
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Outcome::NotStarted(e.to_string()),
+        }
+    }
+}
+
+type Collector = (Arc<Mutex<Vec<u8>>>, thread::JoinHandle<()>);
+
+/// Reads `stream` on another thread, keeping the first `OUTPUT_LIMIT` bytes.
+fn collect(stream: Option<impl Read + Send + 'static>) -> Collector {
+    let buffer = Arc::new(Mutex::new(Vec::new()));
+    let kept = buffer.clone();
+    let reader = thread::spawn(move || {
+        let Some(mut stream) = stream else {
+            return;
+        };
+        let mut chunk = [0; 4096];
+        // Reading on after the limit keeps the command from blocking on a full pipe
+        while let Ok(read) = stream.read(&mut chunk) {
+            if read == 0 {
+                break;
+            }
+            let mut kept = kept.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            let room = OUTPUT_LIMIT.saturating_sub(kept.len());
+            kept.extend_from_slice(&chunk[..read.min(room)]);
+        }
+    });
+    (buffer, reader)
+}
+
+fn collected(buffer: &Mutex<Vec<u8>>) -> String {
+    let bytes = buffer.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// The log of reminder `id`'s hook.
+pub fn log_path(id: &str) -> PathBuf {
+    file_actions::data_dir().join(LOG_DIR).join(log_name(id))
+}
+
+/// Ids imported from calendars can be any text, so everything but a plain
+/// file name character is replaced to keep the log inside the hooks folder.
+fn log_name(id: &str) -> String {
+    let name: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' })
+        .collect();
+    format!("{}.log", name)
+}
+
+impl Run {
+    /// Adds this run to the log at `path`.
+    pub fn append_to(&self, path: &Path) -> std::io::Result<()> {
+        let mut log = open_log(path)?;
+        writeln!(log, "== {}: {}", reminder::format_time(&self.started), self.outcome)?;
+        for (label, output) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            if !output.is_empty() {
+                writeln!(log, "-- {}", label)?;
+                writeln!(log, "{}", output.trim_end())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Opens the log at `path` for adding to it. Once it grows past `LOG_LIMIT`
+/// it is moved to `.log.old`, replacing the one before, and starts over.
+pub fn open_log(path: &Path) -> std::io::Result<File> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() > LOG_LIMIT) {
+        fs::rename(path, path.with_extension("log.old"))?;
+    }
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Splits `text` into words at whitespace. Quotes keep words together:
+/// nothing is special inside '', and a backslash escapes the next character
+/// anywhere else. Nothing is expanded, this only spares typing one word per line.
+pub fn split_words(text: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err("a ' is never closed".to_string()),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => word.push(chars.next().ok_or("a \" is never closed")?),
+                        Some(c) => word.push(c),
+                        None => return Err("a \" is never closed".to_string()),
+                    }
+                }
+            }
+            '\\' => word.get_or_insert_with(String::new).push(chars.next().ok_or("nothing follows the last \\")?),
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+/// Joins `words` so that `split_words` gives them back.
+pub fn join_words<S: AsRef<str>>(words: &[S]) -> String {
+    let quoted: Vec<String> = words
+        .iter()
+        .map(|word| {
+            let word = word.as_ref();
+            if !word.is_empty() && !word.contains(|c: char| c.is_whitespace() || "'\"\\".contains(c)) {
+                word.to_string()
+            } else {
+                format!("'{}'", word.replace('\'', "'\\''"))
+            }
+        })
+        .collect();
+    quoted.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn words_split_and_join_without_a_shell() {
+        let words = split_words(r#"--title "Stand up" 'it''s $HOME' a\ b "" ; rm"#).unwrap();
+        assert_eq!(words, ["--title", "Stand up", "its $HOME", "a b", "", ";", "rm"]);
+        assert_eq!(split_words(&join_words(&words)).unwrap(), words);
+        assert_eq!(split_words(&join_words(&["it's"])).unwrap(), ["it's"]);
+        assert!(split_words("'open").is_err());
+    }
+
+    #[test]
+    fn log_names_stay_inside_the_hooks_folder() {
+        assert_eq!(log_name("0b6f-41c2"), "0b6f-41c2.log");
+        for id in ["../../.bashrc", "/etc/passwd", "..", "a\\b", "event@example.com/1"] {
+            let path = log_path(id);
+            assert_eq!(path.parent(), Some(file_actions::data_dir().join(LOG_DIR).as_path()), "{}", id);
+            assert!(!path.file_name().unwrap().to_str().unwrap().contains(['/', '\\']), "{}", id);
+        }
+        assert_eq!(log_name("../x"), ".._x.log");
+    }
+
+    #[test]
+    fn runs_are_logged_with_their_output_and_status() {
+        let dir = ScratchDir::new("hook");
+        let reminder = Reminder::new("Deploy; rm -rf ~".to_string(), Local::now().fixed_offset());
+
+        let mut hook = Hook::new("sh".to_string());
+        hook.args = split_words(r#"-c 'echo "$REWIND_NAME" in "$PWD" with $GREETING; echo oops >&2; exit 3'"#).unwrap();
+        hook.cwd = Some(dir.path().to_path_buf());
+        hook.env.push(("GREETING".to_string(), "hello".to_string()));
+        let run = hook.run(&reminder, reminder.time);
+        assert!(matches!(run.outcome, Outcome::Exited(status) if status.code() == Some(3)));
+        assert_eq!(run.stdout, format!("Deploy; rm -rf ~ in {} with hello\n", dir.path().display()));
+        assert_eq!(run.stderr, "oops\n");
+
+        let log = dir.join("hook.log");
+        run.append_to(&log).unwrap();
+        let mut slow = Hook::new("sleep".to_string());
+        slow.args.push("10".to_string());
+        slow.timeout = Duration::from_millis(200);
+        let run = slow.run(&reminder, reminder.time);
+        assert!(matches!(run.outcome, Outcome::TimedOut));
+        run.append_to(&log).unwrap();
+        Hook::new(dir.join("missing").display().to_string()).run(&reminder, reminder.time).append_to(&log).unwrap();
+
+        let logged = fs::read_to_string(&log).unwrap();
+        let headers: Vec<&str> = logged.lines().filter(|line| line.starts_with("== ")).collect();
+        assert_eq!(headers.len(), 3);
+        assert!(headers[0].ends_with("exit status: 3"));
+        assert!(headers[1].ends_with("killed after running too long"));
+        assert!(headers[2].contains("could not be started"));
+        assert!(logged.contains("-- stderr\noops\n"));
+    }
+
+    #[test]
+    fn timing_out_kills_what_the_hook_started() {
+        let dir = ScratchDir::new("hook-group");
+        let reminder = Reminder::new("Backup".to_string(), Local::now().fixed_offset());
+        let mut hook = Hook::new("sh".to_string());
+        hook.args = split_words("-c 'sleep 30 & echo $! > sleeper; wait'").unwrap();
+        hook.cwd = Some(dir.path().to_path_buf());
+        hook.timeout = Duration::from_millis(300);
+
+        let run = hook.run(&reminder, reminder.time);
+        assert!(matches!(run.outcome, Outcome::TimedOut));
+        let sleeper = fs::read_to_string(dir.join("sleeper")).unwrap();
+        // Killed processes linger as zombies until whoever adopted them reaps them
+        let stat = fs::read_to_string(format!("/proc/{}/stat", sleeper.trim())).unwrap_or_default();
+        let state = stat.rsplit(") ").next().unwrap_or_default();
+        assert!(stat.is_empty() || state.starts_with('Z'), "the sleep is still running: {}", stat);
+    }
+}
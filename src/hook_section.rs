@@ -0,0 +1,99 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use gtk::prelude::{BoxExt, EditableExt, EntryExt, WidgetExt};
+use relm4::gtk;
+
+use crate::hook::{self, Hook};
+use crate::widgets::labelled_row;
+
+/// The "Run a command" part of the reminder dialog.
+pub struct HookSection {
+    pub root: gtk::Expander,
+    program: gtk::Entry,
+    args: gtk::Entry,
+    cwd: gtk::Entry,
+    env: gtk::Entry,
+    timeout: gtk::SpinButton,
+    log: gtk::Label,
+}
+
+impl HookSection {
+    pub fn new() -> Self {
+        let root = gtk::Expander::new(Some("Run a command when it fires"));
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(5)
+            .build();
+
+        let program = gtk::Entry::new();
+        program.set_placeholder_text(Some("Program, like xdg-open or /home/me/bin/build"));
+        let args = gtk::Entry::new();
+        args.set_placeholder_text(Some("Arguments, quote the ones with spaces"));
+        let cwd = gtk::Entry::new();
+        cwd.set_placeholder_text(Some("Run in folder (full path, optional)"));
+        let env = gtk::Entry::new();
+        env.set_placeholder_text(Some("Environment, like NAME=value OTHER=value (optional)"));
+        let timeout = gtk::SpinButton::with_range(1.0, 86400.0, 1.0);
+        timeout.set_value(hook::DEFAULT_TIMEOUT.as_secs() as f64);
+        let log = gtk::Label::new(None);
+        log.set_wrap(true);
+        log.set_selectable(true);
+        log.set_visible(false);
+
+        content.append(&program);
+        content.append(&args);
+        content.append(&cwd);
+        content.append(&env);
+        content.append(&labelled_row("Stop it after", &timeout, "seconds"));
+        content.append(&log);
+        root.set_child(Some(&content));
+
+        HookSection { root, program, args, cwd, env, timeout, log }
+    }
+
+    /// Shows an existing hook in the widgets, with where its runs are logged.
+    pub fn fill(&self, hook: &Hook, log: &Path) {
+        self.program.set_text(&hook.program);
+        self.args.set_text(&hook::join_words(&hook.args));
+        self.cwd.set_text(&hook.cwd.as_ref().map(|dir| dir.display().to_string()).unwrap_or_default());
+        let env: Vec<String> = hook.env.iter().map(|(name, value)| format!("{}={}", name, value)).collect();
+        self.env.set_text(&hook::join_words(&env));
+        self.timeout.set_value(hook.timeout.as_secs() as f64);
+        self.log.set_text(&format!("Runs are logged in {}", log.display()));
+        self.log.set_visible(true);
+        self.root.set_expanded(true);
+    }
+
+    /// Builds the hook described by the widgets, `None` when no program is given.
+    pub fn read(&self) -> Result<Option<Hook>, String> {
+        let program = self.program.text().trim().to_string();
+        let args = hook::split_words(&self.args.text()).map_err(|e| format!("Arguments: {}", e))?;
+        if program.is_empty() {
+            if !args.is_empty() {
+                return Err("Arguments were given without a program to run".to_string());
+            }
+            return Ok(None);
+        }
+
+        let mut hook = Hook::new(program);
+        hook.args = args;
+        let cwd = self.cwd.text().trim().to_string();
+        if !cwd.is_empty() {
+            let cwd = PathBuf::from(cwd);
+            // The background service runs from wherever it was started
+            if !cwd.is_absolute() {
+                return Err(format!("'{}' is not a full path", cwd.display()));
+            }
+            hook.cwd = Some(cwd);
+        }
+        for variable in hook::split_words(&self.env.text()).map_err(|e| format!("Environment: {}", e))? {
+            match variable.split_once('=') {
+                Some((name, value)) if !name.is_empty() => hook.env.push((name.to_string(), value.to_string())),
+                _ => return Err(format!("'{}' is not like NAME=value", variable)),
+            }
+        }
+        hook.timeout = Duration::from_secs(self.timeout.value_as_int().max(1) as u64);
+        Ok(Some(hook))
+    }
+}
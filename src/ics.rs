@@ -0,0 +1,837 @@
+//! Moving reminders to and from other calendar tools as iCalendar (RFC 5545) files.
+//!
+//! Reminders are exported as events starting at the reminder's time, each
+//! with an alarm at its start, and keep their id as the UID. Times are written
+//! in the local time zone, which is described by a VTIMEZONE, so repeating
+//! reminders keep their wall-clock time across daylight saving changes.
+//!
+//! Imports accept events and to-dos. A reminder goes off when the entry's
+//! earliest alarm does, or at the start of an event or the due time of a
+//! to-do when there is no alarm.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, TimeZone, Utc, Weekday};
+
+use crate::recurrence::{self, Recurrence};
+use crate::reminder::Reminder;
+use crate::store::{LoadError, ReminderStore};
+
+const PRODID: &str = "-//Rewind//Rewind Reminders//EN";
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+const DATE_FORMAT: &str = "%Y%m%d";
+// Lines longer than this many bytes are folded
+const MAX_LINE_LENGTH: usize = 75;
+// How far ahead and back from now time zone changes are written out at most.
+// Reminders that repeat forever or for centuries would otherwise take ages.
+const ZONE_YEARS_AHEAD: i32 = 10;
+const ZONE_YEARS_BEHIND: i32 = 30;
+
+/// The outcome of an import.
+pub struct ImportReport {
+    pub added: usize,
+    /// Entries skipped because a reminder with their UID already exists.
+    pub already_present: usize,
+    pub errors: Vec<LoadError>,
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Imported {} reminders", self.added)?;
+        if self.already_present > 0 {
+            write!(f, ", {} were already in Rewind", self.already_present)?;
+        }
+        if !self.errors.is_empty() {
+            write!(f, ", {} could not be imported", self.errors.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Adds the reminders of an iCalendar file to `store`, leaving out entries
+/// whose UID matches a reminder that is already there.
+pub fn import_calendar(store: &mut dyn ReminderStore, text: &str) -> Result<ImportReport, Box<dyn Error>> {
+    let parsed = parse_calendar(text)?;
+    let existing: HashSet<String> = store.load()?.reminders.into_iter().map(|r| r.id).collect();
+
+    let (present, new): (Vec<Reminder>, Vec<Reminder>) =
+        parsed.reminders.into_iter().partition(|reminder| existing.contains(&reminder.id));
+    store.insert_all(&new)?;
+    Ok(ImportReport { added: new.len(), already_present: present.len(), errors: parsed.errors })
+}
+
+/// Writes every reminder in `store` as an iCalendar file and returns how many there were.
+/// Completed reminders stay behind, they would only go off again elsewhere.
+pub fn export_calendar<W: Write>(store: &mut dyn ReminderStore, mut sink: W) -> Result<usize, Box<dyn Error>> {
+    let mut reminders = store.load()?.reminders;
+    reminders.retain(|reminder| reminder.completed_at.is_none());
+    sink.write_all(write_calendar(&reminders, Utc::now()).as_bytes())?;
+    sink.flush()?;
+    Ok(reminders.len())
+}
+
+/// Reminders found in an iCalendar file, plus the entries that could not be used.
+pub struct ParsedCalendar {
+    pub reminders: Vec<Reminder>,
+    pub errors: Vec<LoadError>,
+}
+
+pub fn parse_calendar(text: &str) -> Result<ParsedCalendar, String> {
+    let root = parse_components(text)?;
+    let calendars: Vec<&Component> = root.children.iter().filter(|c| c.name == "VCALENDAR").collect();
+    if calendars.is_empty() {
+        return Err("the file has no VCALENDAR in it".to_string());
+    }
+
+    let mut parsed = ParsedCalendar { reminders: Vec::new(), errors: Vec::new() };
+    let mut seen_uids = HashSet::new();
+    let mut position = 0;
+    for calendar in calendars {
+        let zones = Zones::from_calendar(calendar);
+        for entry in calendar.children.iter().filter(|c| c.name == "VEVENT" || c.name == "VTODO") {
+            let name = entry.property("SUMMARY").map(|p| unescape_text(&p.value)).unwrap_or_default();
+            let result = reminder_from_component(entry, &zones).and_then(|reminder| {
+                if seen_uids.insert(reminder.id.clone()) {
+                    Ok(reminder)
+                } else {
+                    Err(format!("UID {} appears more than once", reminder.id))
+                }
+            });
+            match result {
+                Ok(reminder) => parsed.reminders.push(reminder),
+                Err(reason) => parsed.errors.push(LoadError { position, name, reason }),
+            }
+            position += 1;
+        }
+    }
+    Ok(parsed)
+}
+
+fn reminder_from_component(entry: &Component, zones: &Zones) -> Result<Reminder, String> {
+    if entry.property("RECURRENCE-ID").is_some() {
+        return Err("changes to single occurrences of a series are not supported".to_string());
+    }
+    match entry.property("STATUS").map(|p| p.value.trim().to_ascii_uppercase()).as_deref() {
+        Some("COMPLETED") => return Err("it is already completed".to_string()),
+        Some("CANCELLED") => return Err("it was cancelled".to_string()),
+        _ => {}
+    }
+
+    let is_todo = entry.name == "VTODO";
+    let start = entry.property("DTSTART").map(|p| zones.time(p)).transpose()?;
+    let end = entry.property(if is_todo { "DUE" } else { "DTEND" }).map(|p| zones.time(p)).transpose()?;
+    let base = if is_todo { end.or(start) } else { start };
+    let base = base.ok_or(if is_todo { "missing DUE and DTSTART" } else { "missing DTSTART" })?;
+    let time = earliest_alarm(entry, start.unwrap_or(base), end.unwrap_or(base), zones)?.unwrap_or(base);
+
+    let name = entry
+        .property("SUMMARY")
+        .map(|p| unescape_text(&p.value))
+        .filter(|name| !name.trim().is_empty())
+        .unwrap_or_else(|| "Untitled reminder".to_string());
+    let mut reminder = Reminder::new(name, time);
+    if let Some(uid) = entry.property("UID").map(|p| p.value.trim()).filter(|uid| !uid.is_empty()) {
+        reminder.id = uid.to_string();
+    }
+
+    if let Some(rrule) = entry.property("RRULE") {
+        let start = start.ok_or("repeating entries need a DTSTART")?;
+        let mut rule = Recurrence::parse(&rrule.value)?;
+        // The alarm keeps its distance from every occurrence
+        let shift = time - start;
+        for exdate in entry.properties("EXDATE") {
+            for value in exdate.value.split(',').filter(|v| !v.trim().is_empty()) {
+                let skipped = zones.parse(value.trim(), exdate.param("TZID"), exdate.param("VALUE"))?;
+                rule.exceptions.push(skipped + shift);
+            }
+        }
+        reminder.recurrence = Some(rule);
+    }
+    Ok(reminder)
+}
+
+/// When the earliest alarm of `entry` goes off, if it has any.
+fn earliest_alarm(
+    entry: &Component,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    zones: &Zones,
+) -> Result<Option<DateTime<FixedOffset>>, String> {
+    let mut earliest = None;
+    for trigger in entry.children.iter().filter(|c| c.name == "VALARM").filter_map(|alarm| alarm.property("TRIGGER")) {
+        let time = if trigger.param("VALUE").is_some_and(|v| v.eq_ignore_ascii_case("DATE-TIME")) {
+            zones.time(trigger)?
+        } else {
+            let related = if trigger.param("RELATED").is_some_and(|r| r.eq_ignore_ascii_case("END")) { end } else { start };
+            let value = trigger.value.trim();
+            related
+                .checked_add_signed(parse_duration(value)?)
+                .ok_or_else(|| format!("invalid duration '{}': the alarm is too far off", value))?
+        };
+        earliest = Some(earliest.map_or(time, |e: DateTime<FixedOffset>| e.min(time)));
+    }
+    Ok(earliest)
+}
+
+/// Parses a DURATION value such as `-PT15M` or `P1DT2H`.
+fn parse_duration(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration '{}'", value);
+    let (negative, rest) = match value.as_bytes().first() {
+        Some(b'-') => (true, &value[1..]),
+        Some(b'+') => (false, &value[1..]),
+        _ => (false, value),
+    };
+    let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() => in_time = true,
+            _ => {
+                let n: i64 = number.parse().map_err(|_| invalid())?;
+                number.clear();
+                let part = match (c, in_time) {
+                    ('W', false) => Duration::try_weeks(n),
+                    ('D', false) => Duration::try_days(n),
+                    ('H', true) => Duration::try_hours(n),
+                    ('M', true) => Duration::try_minutes(n),
+                    ('S', true) => Duration::try_seconds(n),
+                    _ => return Err(invalid()),
+                };
+                total = part.and_then(|part| total.checked_add(&part)).ok_or_else(invalid)?;
+            }
+        }
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(if negative { -total } else { total })
+}
+
+/// The time zones a calendar refers to.
+struct Zones {
+    definitions: HashMap<String, ZoneDefinition>,
+    local_name: Option<String>,
+}
+
+impl Zones {
+    fn from_calendar(calendar: &Component) -> Self {
+        let definitions = calendar
+            .children
+            .iter()
+            .filter(|c| c.name == "VTIMEZONE")
+            .filter_map(|zone| Some((zone.property("TZID")?.value.trim().to_string(), ZoneDefinition::parse(zone))))
+            .collect();
+        Zones { definitions, local_name: local_zone_name() }
+    }
+
+    fn time(&self, property: &Property) -> Result<DateTime<FixedOffset>, String> {
+        self.parse(property.value.trim(), property.param("TZID"), property.param("VALUE"))
+    }
+
+    fn parse(&self, value: &str, tzid: Option<&str>, kind: Option<&str>) -> Result<DateTime<FixedOffset>, String> {
+        if kind.is_some_and(|k| k.eq_ignore_ascii_case("DATE")) || (value.len() == 8 && !value.contains('T')) {
+            // Entries for a whole day are due at its start
+            let date = NaiveDate::parse_from_str(value, DATE_FORMAT).map_err(|_| format!("invalid date '{}'", value))?;
+            return local_time(date.and_time(NaiveTime::MIN));
+        }
+        if let Some(utc) = value.strip_suffix('Z') {
+            let naive = parse_date_time(utc)?;
+            return Ok(Utc.from_utc_datetime(&naive).fixed_offset());
+        }
+
+        let naive = parse_date_time(value)?;
+        match tzid {
+            None => local_time(naive),
+            Some(tzid) if Some(tzid) == self.local_name.as_deref() => local_time(naive),
+            Some(tzid) => match self.definitions.get(tzid) {
+                Some(zone) => Ok(zone.resolve(naive)),
+                None if matches!(tzid, "UTC" | "Etc/UTC" | "GMT" | "Z") => Ok(Utc.from_utc_datetime(&naive).fixed_offset()),
+                None => Err(format!("unknown time zone '{}'", tzid)),
+            },
+        }
+    }
+}
+
+fn parse_date_time(value: &str) -> Result<NaiveDateTime, String> {
+    NaiveDateTime::parse_from_str(value, DATE_TIME_FORMAT).map_err(|_| format!("invalid time '{}'", value))
+}
+
+fn local_time(naive: NaiveDateTime) -> Result<DateTime<FixedOffset>, String> {
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.fixed_offset())
+        .ok_or_else(|| format!("{} does not exist in the local time zone", naive))
+}
+
+/// A VTIMEZONE, enough to find the UTC offset in effect at a local time.
+struct ZoneDefinition {
+    observances: Vec<Observance>,
+}
+
+/// One STANDARD or DAYLIGHT part of a VTIMEZONE.
+struct Observance {
+    start: NaiveDateTime,
+    offset_from: FixedOffset,
+    offset_to: FixedOffset,
+    rule: Option<YearlyRule>,
+    dates: Vec<NaiveDateTime>,
+}
+
+/// The yearly rules time zones use, like "the last Sunday in October".
+struct YearlyRule {
+    month: u32,
+    weekday: Option<(i32, Weekday)>,
+    month_days: Vec<u32>,
+    until: Option<DateTime<FixedOffset>>,
+}
+
+impl ZoneDefinition {
+    fn parse(zone: &Component) -> Self {
+        let observances = zone
+            .children
+            .iter()
+            .filter(|c| c.name == "STANDARD" || c.name == "DAYLIGHT")
+            .filter_map(|observance| {
+                let start = parse_date_time(observance.property("DTSTART")?.value.trim()).ok()?;
+                let offset_from = parse_offset(&observance.property("TZOFFSETFROM")?.value)?;
+                let offset_to = parse_offset(&observance.property("TZOFFSETTO")?.value)?;
+                let rule = observance.property("RRULE").and_then(|rrule| YearlyRule::parse(&rrule.value));
+                let dates = observance
+                    .properties("RDATE")
+                    .flat_map(|rdate| rdate.value.split(','))
+                    .filter_map(|value| parse_date_time(value.trim()).ok())
+                    .collect();
+                Some(Observance { start, offset_from, offset_to, rule, dates })
+            })
+            .collect();
+        ZoneDefinition { observances }
+    }
+
+    /// The time at `naive` in this zone. The offset is the one of the change
+    /// that happened last before it.
+    fn resolve(&self, naive: NaiveDateTime) -> DateTime<FixedOffset> {
+        let latest = self
+            .observances
+            .iter()
+            .filter_map(|observance| observance.last_onset(naive).map(|onset| (onset, observance.offset_to)))
+            .max_by_key(|(onset, _)| *onset);
+        let offset = match latest {
+            Some((_, offset)) => offset,
+            None => self
+                .observances
+                .iter()
+                .min_by_key(|observance| observance.start)
+                .map(|observance| observance.offset_from)
+                .unwrap_or_else(|| Utc.fix()),
+        };
+        offset.from_local_datetime(&naive).single().unwrap_or_else(|| Utc.from_utc_datetime(&naive).fixed_offset())
+    }
+}
+
+impl Observance {
+    /// The last time this observance took effect at or before `naive`.
+    fn last_onset(&self, naive: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut onsets = vec![self.start];
+        onsets.extend(self.dates.iter().copied());
+        if let Some(rule) = &self.rule {
+            for year in [naive.year() - 1, naive.year()] {
+                let Some(date) = rule.date_in(year) else {
+                    continue;
+                };
+                let onset = date.and_time(self.start.time());
+                let before_until = rule.until.is_none_or(|until| onset - offset_duration(self.offset_from) <= until.naive_utc());
+                if onset >= self.start && before_until {
+                    onsets.push(onset);
+                }
+            }
+        }
+        onsets.into_iter().filter(|onset| *onset <= naive).max()
+    }
+}
+
+fn offset_duration(offset: FixedOffset) -> Duration {
+    Duration::seconds(offset.local_minus_utc() as i64)
+}
+
+impl YearlyRule {
+    fn parse(rrule: &str) -> Option<Self> {
+        let mut rule = YearlyRule { month: 0, weekday: None, month_days: Vec::new(), until: None };
+        for (key, value) in rrule.split(';').filter_map(|part| part.split_once('=')) {
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" if !value.eq_ignore_ascii_case("YEARLY") => return None,
+                "BYMONTH" => rule.month = value.parse().ok()?,
+                "BYDAY" => {
+                    let (n, weekday) = recurrence::parse_byday(value).ok()?;
+                    rule.weekday = Some((n.unwrap_or(0), weekday));
+                }
+                "BYMONTHDAY" => rule.month_days = value.split(',').filter_map(|d| d.parse().ok()).collect(),
+                "UNTIL" => rule.until = recurrence::parse_until(value).ok(),
+                _ => {}
+            }
+        }
+        (1..=12).contains(&rule.month).then_some(rule)
+    }
+
+    fn date_in(&self, year: i32) -> Option<NaiveDate> {
+        let days: Vec<NaiveDate> = (1..=recurrence::days_in_month(year, self.month))
+            .filter(|day| self.month_days.is_empty() || self.month_days.contains(day))
+            .filter_map(|day| NaiveDate::from_ymd_opt(year, self.month, day))
+            .filter(|date| self.weekday.is_none_or(|(_, weekday)| date.weekday() == weekday))
+            .collect();
+        match self.weekday.map_or(0, |(n, _)| n) {
+            0 => days.first().copied(),
+            n if n > 0 => days.get(n as usize - 1).copied(),
+            n => days.len().checked_sub(n.unsigned_abs() as usize).and_then(|i| days.get(i)).copied(),
+        }
+    }
+}
+
+/// Parses a UTC offset like `+0100` or `-053000`.
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    let sign = match value.as_bytes().first()? {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let digits = &value[1..];
+    if !(digits.len() == 4 || digits.len() == 6) || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let part = |range: std::ops::Range<usize>| digits.get(range).and_then(|p| p.parse::<i32>().ok()).unwrap_or(0);
+    FixedOffset::east_opt(sign * (part(0..2) * 3600 + part(2..4) * 60 + part(4..6)))
+}
+
+fn format_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    let mut text = format!("{}{:02}{:02}", sign, seconds / 3600, seconds / 60 % 60);
+    if seconds % 60 != 0 {
+        text.push_str(&format!("{:02}", seconds % 60));
+    }
+    text
+}
+
+/// The IANA name of the local time zone, e.g. `Europe/Berlin`.
+fn local_zone_name() -> Option<String> {
+    // `TZ` overrides the system setting, the same way it does for `Local`
+    if let Ok(tz) = env::var("TZ") {
+        let tz = tz.trim_start_matches(':');
+        let tz = tz.strip_prefix("/usr/share/zoneinfo/").unwrap_or(tz);
+        if !tz.is_empty() && !tz.starts_with('/') {
+            return Some(tz.to_string());
+        }
+    }
+    iana_time_zone::get_timezone().ok()
+}
+
+/// Formats `reminders` as an iCalendar file stamped with `now`.
+pub fn write_calendar(reminders: &[Reminder], now: DateTime<Utc>) -> String {
+    let mut text = String::new();
+    push_line(&mut text, "BEGIN:VCALENDAR");
+    push_line(&mut text, "VERSION:2.0");
+    push_line(&mut text, &format!("PRODID:{}", PRODID));
+    push_line(&mut text, "CALSCALE:GREGORIAN");
+
+    let zone = local_zone_name().filter(|_| !reminders.is_empty());
+    if let Some(tzid) = &zone {
+        let (first_year, last_year) = zone_years(reminders, now);
+        for line in local_zone_lines(tzid, first_year, last_year) {
+            push_line(&mut text, &line);
+        }
+    }
+
+    let stamp = now.format(DATE_TIME_FORMAT);
+    for reminder in reminders {
+        push_line(&mut text, "BEGIN:VEVENT");
+        push_line(&mut text, &format!("UID:{}", escape_text(&reminder.id)));
+        push_line(&mut text, &format!("DTSTAMP:{}Z", stamp));
+        push_line(&mut text, &format!("SUMMARY:{}", escape_text(&reminder.name)));
+        push_line(&mut text, &time_line("DTSTART", &reminder.time, zone.as_deref()));
+        if let Some(rule) = &reminder.recurrence {
+            push_line(&mut text, &format!("RRULE:{}", rule.to_rrule()));
+            for exdate in &rule.exceptions {
+                push_line(&mut text, &time_line("EXDATE", exdate, zone.as_deref()));
+            }
+        }
+        push_line(&mut text, "BEGIN:VALARM");
+        push_line(&mut text, "ACTION:DISPLAY");
+        push_line(&mut text, &format!("DESCRIPTION:{}", escape_text(&reminder.name)));
+        push_line(&mut text, "TRIGGER:PT0S");
+        push_line(&mut text, "END:VALARM");
+        push_line(&mut text, "END:VEVENT");
+    }
+    push_line(&mut text, "END:VCALENDAR");
+    text
+}
+
+/// The years the VTIMEZONE of `reminders` covers: from the first due time
+/// through the last occurrence, but no further from `now` than the limits.
+fn zone_years(reminders: &[Reminder], now: DateTime<Utc>) -> (i32, i32) {
+    let (earliest, latest) = (now.year() - ZONE_YEARS_BEHIND, now.year() + ZONE_YEARS_AHEAD);
+    let first_year = reminders.iter().map(|r| r.local_time().year()).min().unwrap_or(now.year());
+    let last_year = reminders
+        .iter()
+        .map(|r| r.last_occurrence().map_or(latest, |last| last.with_timezone(&Local).year()))
+        .max()
+        .unwrap_or(now.year());
+    // Starting a year early also covers the first hours of January
+    let first_year = (first_year - 1).clamp(earliest, latest);
+    (first_year, last_year.clamp(first_year, latest))
+}
+
+fn time_line(name: &str, time: &DateTime<FixedOffset>, zone: Option<&str>) -> String {
+    match zone {
+        Some(tzid) => format!("{};TZID={}:{}", name, tzid, time.with_timezone(&Local).format(DATE_TIME_FORMAT)),
+        None => format!("{}:{}Z", name, time.with_timezone(&Utc).format(DATE_TIME_FORMAT)),
+    }
+}
+
+/// Describes the UTC offsets of the local time zone from `first_year` through
+/// `last_year` as a VTIMEZONE, with one part for every change.
+fn local_zone_lines(tzid: &str, first_year: i32, last_year: i32) -> Vec<String> {
+    let offset_at = |time: DateTime<Utc>| Local.offset_from_utc_datetime(&time.naive_utc());
+    let observance = |kind: &str, onset: NaiveDateTime, from: FixedOffset, to: FixedOffset| {
+        vec![
+            format!("BEGIN:{}", kind),
+            format!("DTSTART:{}", onset.format(DATE_TIME_FORMAT)),
+            format!("TZOFFSETFROM:{}", format_offset(from)),
+            format!("TZOFFSETTO:{}", format_offset(to)),
+            format!("END:{}", kind),
+        ]
+    };
+
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", tzid)];
+    let (Some(mut day), Some(end)) = (
+        Utc.with_ymd_and_hms(first_year, 1, 1, 0, 0, 0).single(),
+        Utc.with_ymd_and_hms(last_year + 1, 1, 1, 0, 0, 0).single(),
+    ) else {
+        return Vec::new();
+    };
+    let mut offset = offset_at(day);
+    lines.extend(observance("STANDARD", day.naive_utc() + offset_duration(offset), offset, offset));
+
+    while day < end {
+        let next = day + Duration::days(1);
+        let next_offset = offset_at(next);
+        if next_offset != offset {
+            // Narrow the change down to the second
+            let (mut before, mut after) = (day, next);
+            while after - before > Duration::seconds(1) {
+                let middle = before + (after - before) / 2;
+                if offset_at(middle) == offset {
+                    before = middle;
+                } else {
+                    after = middle;
+                }
+            }
+            let kind = if next_offset.local_minus_utc() > offset.local_minus_utc() { "DAYLIGHT" } else { "STANDARD" };
+            lines.extend(observance(kind, after.naive_utc() + offset_duration(offset), offset, next_offset));
+            offset = next_offset;
+        }
+        day = next;
+    }
+    lines.push("END:VTIMEZONE".to_string());
+    lines
+}
+
+/// Appends a content line, folded so no line is longer than 75 bytes.
+fn push_line(text: &mut String, line: &str) {
+    let mut rest = line;
+    let mut limit = MAX_LINE_LENGTH;
+    while rest.len() > limit {
+        let mut split = limit;
+        while !rest.is_char_boundary(split) {
+            split -= 1;
+        }
+        text.push_str(&rest[..split]);
+        text.push_str("\r\n ");
+        rest = &rest[split..];
+        // The leading space of a continuation counts too
+        limit = MAX_LINE_LENGTH - 1;
+    }
+    text.push_str(rest);
+    text.push_str("\r\n");
+}
+
+fn escape_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => text.push('\\'),
+        }
+    }
+    text
+}
+
+/// A content line such as `DTSTART;TZID=Europe/Berlin:20250624T213000`.
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+/// A `BEGIN:`/`END:` block with its properties and nested blocks.
+struct Component {
+    name: String,
+    properties: Vec<Property>,
+    children: Vec<Component>,
+}
+
+impl Component {
+    fn new(name: &str) -> Self {
+        Component { name: name.to_ascii_uppercase(), properties: Vec::new(), children: Vec::new() }
+    }
+
+    fn property(&self, name: &str) -> Option<&Property> {
+        self.properties.iter().find(|p| p.name == name)
+    }
+
+    fn properties<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Property> {
+        self.properties.iter().filter(move |p| p.name == name)
+    }
+}
+
+fn parse_components(text: &str) -> Result<Component, String> {
+    let mut stack = vec![Component::new("")];
+    for line in unfold(text) {
+        // Lines that aren't properties at all are skipped, like other tools do
+        let Some(property) = parse_line(&line) else {
+            continue;
+        };
+        match property.name.as_str() {
+            "BEGIN" => stack.push(Component::new(property.value.trim())),
+            "END" => {
+                if stack.len() < 2 {
+                    return Err(format!("END:{} without a matching BEGIN", property.value.trim()));
+                }
+                let component = stack.pop().expect("stack has a component");
+                if !component.name.eq_ignore_ascii_case(property.value.trim()) {
+                    return Err(format!("END:{} closes BEGIN:{}", property.value.trim(), component.name));
+                }
+                stack.last_mut().expect("stack has a root").children.push(component);
+            }
+            _ => stack.last_mut().expect("stack has a root").properties.push(property),
+        }
+    }
+    if stack.len() != 1 {
+        return Err("the file ends in the middle of an entry".to_string());
+    }
+    Ok(stack.pop().expect("stack has a root"))
+}
+
+/// Joins folded lines back together.
+fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(previous)) => previous.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+fn parse_line(line: &str) -> Option<Property> {
+    let name_end = line.find([';', ':'])?;
+    let name = line[..name_end].trim().to_ascii_uppercase();
+    let mut params = Vec::new();
+    let mut rest = &line[name_end..];
+    while let Some(param) = rest.strip_prefix(';') {
+        let (key, value) = param.split_once('=')?;
+        let (value, after) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, after) = quoted.split_once('"')?;
+                (value, after)
+            }
+            None => value.split_at(value.find([';', ':'])?),
+        };
+        params.push((key.trim().to_ascii_uppercase(), value.to_string()));
+        rest = after;
+    }
+    let value = rest.strip_prefix(':')?;
+    (!name.is_empty()).then(|| Property { name, params, value: value.to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    #[test]
+    fn exported_reminders_import_unchanged() {
+        let mut weekly = Reminder::new("Plan; review, \\ and\nretro".to_string(), at("2025-03-24T09:30:00+01:00"));
+        let mut rule = Recurrence::parse("FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10").unwrap();
+        rule.exceptions.push(at("2025-03-26T09:30:00+01:00"));
+        weekly.recurrence = Some(rule);
+        let once = Reminder::new("A name long enough that its summary line has to be folded twice over".repeat(2), at("2025-06-24T21:30:00+02:00"));
+
+        let text = write_calendar(&[weekly.clone(), once.clone()], at("2025-06-01T00:00:00Z").with_timezone(&Utc));
+        assert!(text.lines().all(|line| line.len() <= MAX_LINE_LENGTH + 1), "lines must be folded");
+        let parsed = parse_calendar(&text).unwrap();
+        assert!(parsed.errors.is_empty(), "{:?}", parsed.errors);
+        assert_eq!(parsed.reminders.len(), 2);
+
+        let (first, second) = (&parsed.reminders[0], &parsed.reminders[1]);
+        assert_eq!((first.id.as_str(), first.name.as_str(), first.time), (weekly.id.as_str(), weekly.name.as_str(), weekly.time));
+        let rule = first.recurrence.as_ref().unwrap();
+        assert_eq!(rule.to_rrule(), "FREQ=WEEKLY;BYDAY=MO,WE;COUNT=10");
+        assert_eq!(rule.exceptions, [at("2025-03-26T09:30:00+01:00")]);
+        assert_eq!((second.id.as_str(), second.name.as_str(), second.time), (once.id.as_str(), once.name.as_str(), once.time));
+    }
+
+    #[test]
+    fn alarms_time_zones_and_to_dos_are_understood() {
+        let text = "BEGIN:VCALENDAR\r\n\
+            VERSION:2.0\r\n\
+            BEGIN:VTIMEZONE\r\n\
+            TZID:Test/Zone\r\n\
+            BEGIN:STANDARD\r\n\
+            DTSTART:19701025T030000\r\n\
+            TZOFFSETFROM:+0200\r\n\
+            TZOFFSETTO:+0100\r\n\
+            RRULE:FREQ=YEARLY;BYMONTH=10;BYDAY=-1SU\r\n\
+            END:STANDARD\r\n\
+            BEGIN:DAYLIGHT\r\n\
+            DTSTART:19700329T020000\r\n\
+            TZOFFSETFROM:+0100\r\n\
+            TZOFFSETTO:+0200\r\n\
+            RRULE:FREQ=YEARLY;BYMONTH=3;BYDAY=-1SU\r\n\
+            END:DAYLIGHT\r\n\
+            END:VTIMEZONE\r\n\
+            BEGIN:VEVENT\r\n\
+            UID:summer-meeting\r\n\
+            SUMMARY:Meet\r\n\
+            \x20ing\r\n\
+            DTSTART;TZID=Test/Zone:20250624T100000\r\n\
+            BEGIN:VALARM\r\n\
+            TRIGGER:-PT15M\r\n\
+            END:VALARM\r\n\
+            BEGIN:VALARM\r\n\
+            TRIGGER;RELATED=START:-PT5M\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n\
+            BEGIN:VTODO\r\n\
+            UID:winter-task\r\n\
+            SUMMARY:File taxes\r\n\
+            DUE;TZID=Test/Zone:20251202T170000\r\n\
+            END:VTODO\r\n\
+            BEGIN:VTODO\r\n\
+            UID:done-task\r\n\
+            SUMMARY:Done already\r\n\
+            STATUS:COMPLETED\r\n\
+            DUE:20250101T000000Z\r\n\
+            END:VTODO\r\n\
+            END:VCALENDAR\r\n";
+
+        let parsed = parse_calendar(text).unwrap();
+        let found: Vec<(&str, &str, DateTime<FixedOffset>)> =
+            parsed.reminders.iter().map(|r| (r.id.as_str(), r.name.as_str(), r.time)).collect();
+        assert_eq!(found, [
+            ("summer-meeting", "Meeting", at("2025-06-24T09:45:00+02:00")),
+            ("winter-task", "File taxes", at("2025-12-02T17:00:00+01:00")),
+        ]);
+        assert_eq!(parsed.errors.len(), 1);
+        assert_eq!(parsed.errors[0].name, "Done already");
+    }
+
+    #[test]
+    fn alarms_out_of_range_fail_only_their_entry() {
+        let entry = |uid: &str, trigger: &str| {
+            let alarm = format!("BEGIN:VALARM\r\nTRIGGER:{}\r\nEND:VALARM\r\n", trigger);
+            format!("BEGIN:VEVENT\r\nUID:{0}\r\nSUMMARY:{0}\r\nDTSTART:20250624T100000Z\r\n{1}END:VEVENT\r\n", uid, alarm)
+        };
+        let text = format!(
+            "BEGIN:VCALENDAR\r\n{}{}{}END:VCALENDAR\r\n",
+            entry("huge", "-P99999999999999W"),
+            entry("far", "-P1000000000W"),
+            entry("fine", "-PT15M")
+        );
+
+        let parsed = parse_calendar(&text).unwrap();
+        assert_eq!(parsed.reminders.len(), 1);
+        assert_eq!(parsed.reminders[0].time, at("2025-06-24T09:45:00Z"));
+        let names: Vec<&str> = parsed.errors.iter().map(|error| error.name.as_str()).collect();
+        assert_eq!(names, ["huge", "far"]);
+        assert!(parsed.errors.iter().all(|error| error.reason.contains("invalid duration")), "{:?}", parsed.errors);
+    }
+
+    #[test]
+    fn durations_and_offsets_parse() {
+        assert_eq!(parse_duration("-PT15M"), Ok(Duration::minutes(-15)));
+        assert_eq!(parse_duration("P1DT2H3S"), Ok(Duration::days(1) + Duration::hours(2) + Duration::seconds(3)));
+        assert_eq!(parse_duration("P2W"), Ok(Duration::weeks(2)));
+        assert!(parse_duration("PT").is_ok() && parse_duration("15M").is_err());
+        assert!(parse_duration("-P99999999999999W").is_err());
+        assert!(parse_duration("P99999999999999999999D").is_err());
+        assert!(parse_duration("P15250284452W15250284452W").is_err());
+        assert_eq!(parse_offset("-0530"), FixedOffset::west_opt(5 * 3600 + 30 * 60));
+        assert_eq!(format_offset(FixedOffset::east_opt(3600).unwrap()), "+0100");
+        assert_eq!(parse_offset("0100"), None);
+    }
+
+    #[test]
+    fn time_zones_are_described_for_a_limited_span() {
+        let now = at("2025-06-01T00:00:00Z").with_timezone(&Utc);
+        let once = Reminder::new("Dentist".to_string(), at("2026-03-02T09:00:00+01:00"));
+        assert_eq!(zone_years(std::slice::from_ref(&once), now), (2025, 2026));
+
+        let mut forever = Reminder::new("Birthday".to_string(), at("1901-04-20T09:00:00+01:00"));
+        forever.recurrence = Some(Recurrence::parse("FREQ=YEARLY").unwrap());
+        assert_eq!(zone_years(&[once.clone(), forever.clone()], now), (1995, 2035));
+
+        let mut far = Reminder::new("Time capsule".to_string(), at("9000-01-01T12:00:00Z"));
+        assert_eq!(zone_years(&[far.clone()], now), (2035, 2035));
+        far.recurrence = Some(Recurrence::parse("FREQ=DAILY;UNTIL=99991231T000000Z").unwrap());
+        far.time = at("2024-12-31T12:00:00Z");
+        assert_eq!(zone_years(&[far], now), (2023, 2035));
+    }
+
+    #[test]
+    fn calendars_without_a_time_zone_name_use_utc() {
+        let reminder = Reminder::new("Call".to_string(), at("2025-06-24T21:30:00+02:00"));
+        assert_eq!(time_line("DTSTART", &reminder.time, None), "DTSTART:20250624T193000Z");
+    }
+}
@@ -1,27 +1,72 @@
 use gtk::glib::clone;
 use gtk::{ MessageType, DialogFlags, ButtonsType, ResponseType};
-use gtk::prelude::{BoxExt, ButtonExt, GtkWindowExt, PopoverExt, EntryExt, EditableExt, FrameExt, WidgetExt, DialogExt};
+use gtk::prelude::{BoxExt, ButtonExt, CheckButtonExt, GtkWindowExt, PopoverExt, EntryExt, EditableExt, FrameExt, WidgetExt, DialogExt, FileChooserExt, FileExt, NativeDialogExt};
 use relm4::{gtk, ComponentParts, ComponentSender, RelmApp, SimpleComponent};
-use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike};
 use notify_rust::{Notification,Timeout,Hint};
-use std::collections::HashSet;
 use std::cell::RefCell;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
 use gtk::CssProvider;
 use gtk::gdk::Display;
 
+mod alarm;
+mod alarm_section;
+mod cli;
+mod commands;
+mod config;
+mod daemon;
+mod email;
 mod file_actions;
+mod history;
+mod hook;
+mod hook_section;
+mod ics;
+mod migrations;
+mod missed;
+mod natural_time;
+mod notifier;
+mod recurrence;
+mod recurrence_section;
+mod reminder;
+mod scheduler;
+mod service;
+mod sqlite_store;
+mod store;
+#[cfg(test)]
+mod test_support;
+mod webhook;
+mod widgets;
+mod xml_tree;
 
+use alarm_section::AlarmSection;
+use clap::Parser;
+use cli::Cli;
+use daemon::DaemonClient;
+use missed::Missed;
+use hook_section::HookSection;
+use recurrence_section::RecurrenceSection;
+use reminder::Reminder;
+use store::{LoadError, ReminderStore};
+
+// One store is shared by the window and the notification loop
+type SharedStore = std::rc::Rc<RefCell<Box<dyn ReminderStore>>>;
 
 
 struct AppModel {
     main_window: gtk::Window, 
+    store: SharedStore,
+    daemon: Option<DaemonClient>,
     reminders: Vec<Reminder>,
-}
-
-#[derive(Debug, Clone)]
-struct Reminder {
-    name: String,
-    time: String,
+    load_errors: Vec<LoadError>,
+    load_failure: Option<String>,
+    startup_notices: Vec<String>,
+    // Kept alive while the user picks a file
+    file_chooser: Option<gtk::FileChooserNative>,
+    history_window: gtk::Window,
+    history_query: String,
+    // Reminders that went off while the computer was off or asleep
+    missed: Vec<Missed>,
 }
 
 fn apply_css() {
@@ -42,23 +87,437 @@ fn apply_css() {
     );
 }
 
+/// The History window, hidden until it is asked for. Returns it with the box
+/// its entries go into.
+fn build_history_window(parent: &gtk::Window, sender: &ComponentSender<AppModel>) -> (gtk::Window, gtk::Box) {
+    let history_window = gtk::Window::builder()
+        .title("History")
+        .transient_for(parent)
+        .default_width(500)
+        .default_height(600)
+        .hide_on_close(true)
+        .build();
+
+    let search = gtk::SearchEntry::builder().placeholder_text("Search done reminders").build();
+    search.connect_search_changed(clone!(
+        #[strong] sender,
+        move |entry| {
+            sender.input(AppMsg::SearchHistory(entry.text().to_string()));
+        }
+    ));
+
+    // Completed more than this many days ago gets deleted
+    let purge_days = gtk::SpinButton::with_range(1.0, 3650.0, 1.0);
+    purge_days.set_value(30.0);
+    let purge_button = gtk::Button::with_label("Purge");
+    purge_button.set_widget_name("delete");
+    purge_button.connect_clicked(clone!(
+        #[strong] sender,
+        #[strong] purge_days,
+        move |_| {
+            sender.input(AppMsg::PurgeHistory(purge_days.value_as_int() as u32));
+        }
+    ));
+    let purge_row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+    purge_row.append(&gtk::Label::new(Some("Delete entries older than")));
+    purge_row.append(&purge_days);
+    purge_row.append(&gtk::Label::new(Some("days")));
+    purge_row.append(&purge_button);
+
+    let history_container = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(5)
+        .build();
+    let scrolled_window = gtk::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .vscrollbar_policy(gtk::PolicyType::Automatic)
+        .vexpand(true)
+        .child(&history_container)
+        .build();
+
+    let content = gtk::Box::builder()
+        .orientation(gtk::Orientation::Vertical)
+        .spacing(10)
+        .margin_top(10)
+        .margin_bottom(10)
+        .margin_start(10)
+        .margin_end(10)
+        .build();
+    content.append(&search);
+    content.append(&purge_row);
+    content.append(&scrolled_window);
+    history_window.set_child(Some(&content));
+    (history_window, history_container)
+}
+
+/// The list of reminders that went off while the computer was off or asleep,
+/// shown above the others until they are dismissed.
+fn overdue_section(missed: &[Missed], sender: &ComponentSender<AppModel>) -> gtk::Frame {
+    let (title, _) = missed::describe(missed);
+    let overdue_frame = gtk::Frame::new(Some(&title));
+    overdue_frame.set_widget_name("overdue");
+    overdue_frame.set_label_align(0.5);
+
+    let container = gtk::Box::new(gtk::Orientation::Vertical, 5);
+    for entry in missed {
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+        let due = entry.due.with_timezone(&Local).format("%A, %B %e at %H:%M");
+        let label = gtk::Label::new(Some(&format!("{}, due {}", entry.name, due)));
+        label.set_hexpand(true);
+        label.set_xalign(0.0);
+        let dismiss = gtk::Button::with_label("Dismiss");
+        let id = entry.id.clone();
+        dismiss.connect_clicked(clone!(
+            #[strong] sender,
+            move |_| {
+                sender.input(AppMsg::DismissMissed(id.clone()));
+            }
+        ));
+        row.append(&label);
+        row.append(&dismiss);
+        container.append(&row);
+    }
+    if missed.len() > 1 {
+        let dismiss_all = gtk::Button::with_label("Dismiss All");
+        dismiss_all.connect_clicked(clone!(
+            #[strong] sender,
+            move |_| {
+                sender.input(AppMsg::DismissMissed(String::new()));
+            }
+        ));
+        container.append(&dismiss_all);
+    }
+    overdue_frame.set_child(Some(&container));
+    overdue_frame
+}
+
+/// Turns a quick-add line into a new reminder, refusing ones that are already over.
+fn quick_add_reminder(text: &str) -> Result<Reminder, String> {
+    let now = Local::now();
+    let (name, when) = natural_time::split_quick_add(text, now)?;
+    let mut reminder = Reminder::new(name, when.time);
+    reminder.recurrence = when.recurrence;
+    if reminder.next_occurrence(now.fixed_offset()).is_none() {
+        return Err("The Reminder must be in the Future!".to_string());
+    }
+    Ok(reminder)
+}
+
+/// What the quick-add entry is about to create, shown next to it while typing.
+fn quick_add_preview(text: &str) -> String {
+    if text.trim().is_empty() {
+        return String::new();
+    }
+    match quick_add_reminder(text) {
+        Ok(reminder) => {
+            let due = reminder.local_time().format("%a %e %b, %H:%M");
+            match &reminder.recurrence {
+                Some(rule) => format!("“{}” {} · {}", reminder.name, due, rule),
+                None => format!("“{}” {}", reminder.name, due),
+            }
+        }
+        Err(e) => e,
+    }
+}
+
 #[derive(Debug)]
 enum AppMsg {
     NewReminder,
-    FinalizeReminder(String, String),
+    FinalizeReminder(Reminder),
+    EditReminder(String),
+    UpdateReminder(Reminder),
     LoadInitialData, 
+    RestoreBackup,
     About,
-    DeleteReminder(usize)
+    ChooseImportFile,
+    ChooseExportFile,
+    ImportFrom(PathBuf),
+    ExportTo(PathBuf),
+    DeleteReminder(String),
+    Unsnooze(String),
+    CompleteReminder(String),
+    ShowHistory,
+    SearchHistory(String),
+    PurgeHistory(u32),
+    RefreshMissed,
+    DismissMissed(String),
+}
+
+struct AppInit {
+    store: SharedStore,
+    daemon: Option<DaemonClient>,
+    startup_notices: Vec<String>,
 }
 
 struct AppWidgets {
     reminder_container: gtk::Box,
+    history_container: gtk::Box,
+}
+
+impl AppModel {
+    /// Asks for an .ics file to import from or export to.
+    fn choose_calendar_file(&mut self, sender: ComponentSender<Self>, action: gtk::FileChooserAction) {
+        let opening = action == gtk::FileChooserAction::Open;
+        let chooser = gtk::FileChooserNative::new(
+            Some(if opening { "Import from Calendar" } else { "Export to Calendar" }),
+            Some(&self.main_window),
+            action,
+            Some(if opening { "Import" } else { "Export" }),
+            Some("Cancel"),
+        );
+        let filter = gtk::FileFilter::new();
+        filter.set_name(Some("iCalendar files"));
+        filter.add_pattern("*.ics");
+        chooser.add_filter(&filter);
+        if !opening {
+            chooser.set_current_name("reminders.ics");
+        }
+        chooser.connect_response(move |chooser, response| {
+            let path = chooser.file().and_then(|file| file.path());
+            match (response, path) {
+                (ResponseType::Accept, Some(path)) if opening => sender.input(AppMsg::ImportFrom(path)),
+                (ResponseType::Accept, Some(path)) => sender.input(AppMsg::ExportTo(path)),
+                _ => {}
+            }
+        });
+        chooser.show();
+        self.file_chooser = Some(chooser);
+    }
+
+    fn show_message(&self, message_type: MessageType, text: &str, details: &str) {
+        let message_dialog = gtk::MessageDialog::new(
+            Some(&self.main_window),
+            DialogFlags::MODAL,
+            message_type,
+            ButtonsType::Ok,
+            text
+        );
+        if !details.is_empty() {
+            message_dialog.set_secondary_text(Some(details));
+        }
+        message_dialog.connect_response(|dialog, _| dialog.close());
+        message_dialog.present();
+    }
+
+    /// Lets the daemon pick up a change right away instead of on its next check.
+    fn reminders_changed(&self) {
+        if let Some(daemon) = &self.daemon {
+            daemon.reminders_changed();
+        }
+    }
+
+    /// Shows the reminder dialog, pre-filled from `existing` when editing.
+    fn open_reminder_dialog(&self, sender: ComponentSender<Self>, existing: Option<&Reminder>) {
+        let reminder_window = gtk::Dialog::builder()
+            .title(if existing.is_some() { "Edit Reminder" } else { "Add new Reminder" })
+            .default_width(600)
+            .default_height(750)
+            .build();
+        
+        let reminderbox = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(5)
+            .margin_start(45)
+            .margin_end(45)
+            .build();
+
+        let hour_adjustment = gtk::Adjustment::new(
+            12.0,  
+            0.0,   
+            23.0,  
+            1.0,   
+            1.0,   
+            0.0    
+        );
+        let reminder_hour = gtk::SpinButton::new(Some(&hour_adjustment), 1.0, 0);
+
+        let minute_adjustment = gtk::Adjustment::new(
+            0.0,   
+            0.0,   
+            59.0,  
+            1.0,   
+            5.0,   
+            0.0    
+        );
+        let reminder_minute = gtk::SpinButton::new(Some(&minute_adjustment), 1.0, 0);
+
+        
+        let calendar = gtk::Calendar::new();
+        let reminder_name = gtk::Entry::new();
+        reminder_name.set_placeholder_text(Some("What is your reminder called?"));
+        reminder_name.set_max_length(100);
+        reminder_name.add_css_class("remindername");
+        let reminder_tags = gtk::Entry::new();
+        reminder_tags.set_placeholder_text(Some("Tags, comma separated (optional)"));
+        let reminder_email = gtk::CheckButton::with_label("Also send an email when it fires");
+        let recurrence_section = RecurrenceSection::new();
+        let alarm_section = AlarmSection::new();
+        let hook_section = HookSection::new();
+        let finalize = gtk::Button::new();
+        finalize.set_icon_name("checkmark");
+
+        if let Some(reminder) = existing {
+            if let Some(rule) = &reminder.recurrence {
+                recurrence_section.fill(rule);
+            }
+            if let Some(alarm) = &reminder.alarm {
+                alarm_section.fill(alarm);
+            }
+            if let Some(hook) = &reminder.hook {
+                hook_section.fill(hook, &hook::log_path(&reminder.id));
+            }
+            let local_time = reminder.local_time();
+            reminder_name.set_text(&reminder.name);
+            reminder_tags.set_text(&reminder.tags.join(", "));
+            reminder_email.set_active(reminder.email);
+            reminder_hour.set_value(local_time.hour() as f64);
+            reminder_minute.set_value(local_time.minute() as f64);
+            if let Ok(date) = gtk::glib::DateTime::from_local(
+                local_time.year(),
+                local_time.month() as i32,
+                local_time.day() as i32,
+                0,
+                0,
+                0.0,
+            ) {
+                calendar.select_day(&date);
+            }
+        }
+        let editing = existing.cloned();
+
+        reminderbox.append(&reminder_name);
+        reminderbox.append(&reminder_tags);
+        reminderbox.append(&reminder_hour);
+        reminderbox.append(&reminder_minute);
+        reminderbox.append(&calendar);
+        reminderbox.append(&recurrence_section.root);
+        reminderbox.append(&reminder_email);
+        reminderbox.append(&alarm_section.root);
+        reminderbox.append(&hook_section.root);
+        reminderbox.append(&finalize);
+        reminder_window.set_child(Some(&reminderbox));
+        reminder_window.set_transient_for(Some(&self.main_window));
+        reminder_window.set_modal(true);
+        reminder_window.present();
+        
+        finalize.connect_clicked(clone!(
+            #[strong] sender,
+            #[strong] reminder_name,
+            #[strong] reminder_tags,
+            #[strong] reminder_email,
+            #[strong] calendar,
+            #[strong] reminder_window,
+            #[strong] reminder_hour,
+            #[strong] reminder_minute,
+            move |_| {
+                let text = reminder_name.text().to_string();
+                
+                let gtk_date = calendar.date();
+                let year = gtk_date.year();
+                let month = gtk_date.month() as u32;
+                let day = gtk_date.day_of_month() as u32;
+                let minute = reminder_minute.value_as_int() as u32;
+                let hour = reminder_hour.value_as_int() as u32;
+
+                let naive_date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+                let naive_time = NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
+                let naive_datetime = NaiveDateTime::new(naive_date, naive_time);
+                // Times skipped by a DST change have no local equivalent
+                let Some(local_datetime) = Local.from_local_datetime(&naive_datetime).earliest() else {
+                    println!("Reminder time does not exist in the local time zone!");
+                    widgets::show_error(&reminder_window, "That time does not exist in your time zone!", "");
+                    return;
+                };
+
+                let recurrence = match recurrence_section.read(naive_datetime) {
+                    Ok(recurrence) => recurrence,
+                    Err(e) => {
+                        println!("Invalid repeat settings: {}", e);
+                        widgets::show_error(&reminder_window, "The repeat settings are not valid", &e);
+                        return;
+                    }
+                };
+
+                let alarm = match alarm_section.read() {
+                    Ok(alarm) => alarm,
+                    Err(e) => {
+                        println!("Invalid alarm settings: {}", e);
+                        widgets::show_error(&reminder_window, "The alarm settings are not valid", &e);
+                        return;
+                    }
+                };
+
+                let hook = match hook_section.read() {
+                    Ok(hook) => hook,
+                    Err(e) => {
+                        println!("Invalid command settings: {}", e);
+                        widgets::show_error(&reminder_window, "The command to run is not valid", &e);
+                        return;
+                    }
+                };
+
+                // Editing keeps the reminder's id, everything else comes from the dialog
+                let mut reminder = match &editing {
+                    Some(existing) => existing.clone(),
+                    None => Reminder::new(text.clone(), local_datetime.fixed_offset()),
+                };
+                reminder.name = text;
+                reminder.time = local_datetime.fixed_offset();
+                reminder.recurrence = recurrence;
+                reminder.tags = Vec::new();
+                for tag in reminder_tags.text().split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+                    if !reminder.tags.iter().any(|known| known == tag) {
+                        reminder.tags.push(tag.to_string());
+                    }
+                }
+                reminder.email = reminder_email.is_active();
+                reminder.alarm = alarm;
+                reminder.hook = hook;
+                // A snooze was for the old schedule
+                reminder.unsnooze();
+
+                // Validate that the reminder (or one of its repeats) is in the future
+                let current_local = Local::now();
+                if reminder.next_occurrence(current_local.fixed_offset()).is_none() {
+                    println!("Reminder time must be in the future!");
+                    let reminder_ood = gtk::MessageDialog::new(
+                        Some(&reminder_window), 
+                        DialogFlags::MODAL, 
+                        MessageType::Error,
+                        ButtonsType::Ok, 
+                        "The Reminder must be in the Future!"
+                    );
+                    reminder_ood.connect_response(move |dialog, response| {
+                        match response {
+                            ResponseType::Ok => {
+                                dialog.close();
+                            }, 
+                            _ => {
+                                println!("Box closed");
+                                dialog.close();
+                            }
+                        }
+                    });
+                    reminder_ood.present();
+                    return;
+                }
+
+                println!("{}", local_datetime.to_rfc3339());
+                if editing.is_some() {
+                    sender.input(AppMsg::UpdateReminder(reminder));
+                } else {
+                    sender.input(AppMsg::FinalizeReminder(reminder));
+                }
+                reminder_window.close(); 
+            }
+        ));
+    }
 }
 
 impl SimpleComponent for AppModel {
     type Input = AppMsg;
     type Output = ();
-    type Init = u8;
+    type Init = AppInit;
     type Root = gtk::Window;
     type Widgets = AppWidgets;
 
@@ -71,15 +530,46 @@ impl SimpleComponent for AppModel {
     }
 
     fn init(
-        _whydoineedthis: Self::Init,
+        AppInit { store, daemon, startup_notices }: Self::Init,
         window: Self::Root,
         sender: ComponentSender<Self>,
     ) -> relm4::ComponentParts<Self> {
-        let existing_reminders = file_actions::read_reminders().unwrap_or_else(|_| Vec::new());
+        let mut load_failure = None;
+        let loaded = store.borrow_mut().load();
+        let (existing_reminders, load_errors) = match loaded {
+            Ok(loaded) => (loaded.reminders, loaded.errors),
+            Err(e) => {
+                // Starting empty would overwrite the file on the next save, so keep it aside
+                println!("Failed to read reminders: {}", e);
+                load_failure = Some(match store.borrow_mut().set_aside_unreadable() {
+                    Ok(aside) => format!("{}\n\nThe file was kept as {}", e, aside.display()),
+                    Err(move_error) => format!("{}\n\nIt could not be moved aside either: {}", e, move_error),
+                });
+                (Vec::new(), Vec::new())
+            }
+        };
 
+        let (history_window, history_container) = build_history_window(&window, &sender);
+        let missed = match &daemon {
+            Some(daemon) => {
+                let input = sender.input_sender().clone();
+                daemon.watch_missed(move || input.emit(AppMsg::RefreshMissed));
+                daemon.missed()
+            }
+            None => Vec::new(),
+        };
         let model = AppModel { 
             main_window: window.clone(),
+            store,
+            daemon,
             reminders: existing_reminders, 
+            load_errors,
+            load_failure,
+            startup_notices,
+            file_chooser: None,
+            history_window,
+            history_query: String::new(),
+            missed,
         };
 
         let header = gtk::HeaderBar::new();
@@ -90,6 +580,18 @@ impl SimpleComponent for AppModel {
         header.set_widget_name("TopBarName");
         header.pack_end(&new_tracked);
         window.set_titlebar(Some(&header));
+
+        // The whole reminder in one line, e.g. "Water plants tomorrow 9am"
+        let quick_add = gtk::Entry::builder()
+            .placeholder_text("Quick add: Water plants tomorrow 9am")
+            .width_chars(34)
+            .build();
+        let quick_preview = gtk::Label::new(None);
+        quick_preview.set_widget_name("quick_preview");
+        let quick_box = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+        quick_box.append(&quick_add);
+        quick_box.append(&quick_preview);
+        header.set_title_widget(Some(&quick_box));
         
         menu_button.set_icon_name("open-menu");
         new_tracked.set_icon_name("list-add");
@@ -100,7 +602,13 @@ impl SimpleComponent for AppModel {
             .spacing(5)
             .build();
         
+        let import_button = gtk::Button::with_label("Import from Calendar…");
+        let export_button = gtk::Button::with_label("Export to Calendar…");
+        let history_button = gtk::Button::with_label("History");
         let about_button = gtk::Button::with_label("About");
+        popover_box.append(&history_button);
+        popover_box.append(&import_button);
+        popover_box.append(&export_button);
         popover_box.append(&about_button);
         menu_dropdown.set_child(Some(&popover_box));
         menu_button.set_popover(Some(&menu_dropdown));
@@ -122,6 +630,30 @@ impl SimpleComponent for AppModel {
         
         scrolled_window.set_child(Some(&reminder_container));
         window.set_child(Some(&scrolled_window));
+        import_button.connect_clicked(clone!(
+            #[strong] sender,
+            #[strong] menu_dropdown,
+            move |_| {
+                menu_dropdown.popdown();
+                sender.input(AppMsg::ChooseImportFile);
+            }
+        ));
+        export_button.connect_clicked(clone!(
+            #[strong] sender,
+            #[strong] menu_dropdown,
+            move |_| {
+                menu_dropdown.popdown();
+                sender.input(AppMsg::ChooseExportFile);
+            }
+        ));
+        history_button.connect_clicked(clone!(
+            #[strong] sender,
+            #[strong] menu_dropdown,
+            move |_| {
+                menu_dropdown.popdown();
+                sender.input(AppMsg::ShowHistory);
+            }
+        ));
         about_button.connect_clicked(clone!(
             #[strong] sender,
             move |_| {
@@ -131,6 +663,26 @@ impl SimpleComponent for AppModel {
         ));
         
         
+        quick_add.connect_changed(clone!(
+            #[strong] quick_preview,
+            move |entry| {
+                quick_preview.set_text(&quick_add_preview(&entry.text()));
+            }
+        ));
+        quick_add.connect_activate(clone!(
+            #[strong] sender,
+            #[strong] quick_preview,
+            move |entry| {
+                match quick_add_reminder(&entry.text()) {
+                    Ok(reminder) => {
+                        sender.input(AppMsg::FinalizeReminder(reminder));
+                        entry.set_text("");
+                    }
+                    Err(e) => quick_preview.set_text(&e),
+                }
+            }
+        ));
+
         new_tracked.connect_clicked(clone!(
             #[strong] sender,
             move |_| {
@@ -141,6 +693,7 @@ impl SimpleComponent for AppModel {
         let widgets = AppWidgets { 
 
             reminder_container: reminder_container.clone(),
+            history_container,
         };
 
         sender.input(AppMsg::LoadInitialData);
@@ -151,20 +704,69 @@ impl SimpleComponent for AppModel {
     fn update(&mut self, message: Self::Input, sender: ComponentSender<Self>) {
         match message {
             AppMsg::LoadInitialData => {
+                if !self.startup_notices.is_empty() {
+                    let notice_dialog = gtk::MessageDialog::new(
+                        Some(&self.main_window),
+                        DialogFlags::MODAL,
+                        MessageType::Info,
+                        ButtonsType::Ok,
+                        self.startup_notices.join("\n\n")
+                    );
+                    notice_dialog.connect_response(|dialog, _| dialog.close());
+                    notice_dialog.present();
+                }
+                if let Some(failure) = &self.load_failure {
+                    let can_restore = self.store.borrow().has_backups();
+                    let failure_dialog = gtk::MessageDialog::new(
+                        Some(&self.main_window),
+                        DialogFlags::MODAL,
+                        MessageType::Error,
+                        if can_restore { ButtonsType::None } else { ButtonsType::Ok },
+                        "Your reminders file could not be read"
+                    );
+                    failure_dialog.set_secondary_text(Some(failure));
+                    if can_restore {
+                        failure_dialog.add_button("Start Empty", ResponseType::Reject);
+                        failure_dialog.add_button("Restore Latest Backup", ResponseType::Accept);
+                    }
+                    failure_dialog.connect_response(clone!(
+                        #[strong] sender,
+                        move |dialog, response| {
+                            if response == ResponseType::Accept {
+                                sender.input(AppMsg::RestoreBackup);
+                            }
+                            dialog.close();
+                        }
+                    ));
+                    failure_dialog.present();
+                }
+                if !self.load_errors.is_empty() {
+                    let details: Vec<String> = self.load_errors.iter().map(|e| e.to_string()).collect();
+                    for detail in &details {
+                        println!("Skipped reminder while loading: {}", detail);
+                    }
+                    let load_dialog = gtk::MessageDialog::new(
+                        Some(&self.main_window),
+                        DialogFlags::MODAL,
+                        MessageType::Warning,
+                        ButtonsType::Ok,
+                        "Some reminders could not be loaded"
+                    );
+                    load_dialog.set_secondary_text(Some(&details.join("\n")));
+                    load_dialog.connect_response(|dialog, _| dialog.close());
+                    load_dialog.present();
+                }
             }
 
 
-            AppMsg::FinalizeReminder(text, iso_date) => {
-                self.reminders.push(Reminder {
-                    name: text,
-                    time: iso_date,
-                });
-                
-                if let Err(e) = file_actions::write_reminders(&self.reminders) {
-                    println!("Error writing to XML: {}", e);
+            AppMsg::FinalizeReminder(reminder) => {
+                if let Err(e) = self.store.borrow_mut().insert(&reminder) {
+                    println!("Error saving reminder: {}", e);
                 } else {
-                    println!("Successfully saved {} reminders to XML", self.reminders.len());
+                    println!("Successfully saved reminder '{}'", reminder.name);
+                    self.reminders_changed();
                 }
+                self.reminders.push(reminder);
             }
             AppMsg::About => {
                 let about_window = gtk::AboutDialog::new();
@@ -189,128 +791,166 @@ impl SimpleComponent for AppModel {
                 about_window.present();
             }
 
-            AppMsg::DeleteReminder(index) => {
-                if index < self.reminders.len() {
+            AppMsg::ChooseImportFile => {
+                self.choose_calendar_file(sender, gtk::FileChooserAction::Open);
+            }
+            AppMsg::ChooseExportFile => {
+                self.choose_calendar_file(sender, gtk::FileChooserAction::Save);
+            }
+            AppMsg::ImportFrom(path) => {
+                self.file_chooser = None;
+                let imported = std::fs::read_to_string(&path)
+                    .map_err(|e| e.into())
+                    .and_then(|text| ics::import_calendar(self.store.borrow_mut().as_mut(), &text));
+                match imported {
+                    Ok(report) => {
+                        println!("{} from {}", report, path.display());
+                        self.reminders_changed();
+                        let loaded = self.store.borrow_mut().load();
+                        if let Ok(loaded) = loaded {
+                            self.reminders = loaded.reminders;
+                        }
+                        let details: Vec<String> = report.errors.iter().map(|e| e.to_string()).collect();
+                        let message_type = if details.is_empty() { MessageType::Info } else { MessageType::Warning };
+                        self.show_message(message_type, &report.to_string(), &details.join("\n"));
+                    }
+                    Err(e) => {
+                        println!("Failed to import {}: {}", path.display(), e);
+                        self.show_message(MessageType::Error, "The calendar could not be imported", &e.to_string());
+                    }
+                }
+            }
+            AppMsg::ExportTo(path) => {
+                self.file_chooser = None;
+                let exported = std::fs::File::create(&path)
+                    .map_err(|e| e.into())
+                    .and_then(|file| ics::export_calendar(self.store.borrow_mut().as_mut(), std::io::BufWriter::new(file)));
+                match exported {
+                    Ok(count) => {
+                        let text = format!("Exported {} reminders to {}", count, path.display());
+                        println!("{}", text);
+                        self.show_message(MessageType::Info, &text, "");
+                    }
+                    Err(e) => {
+                        println!("Failed to export to {}: {}", path.display(), e);
+                        self.show_message(MessageType::Error, "The calendar could not be exported", &e.to_string());
+                    }
+                }
+            }
+
+            AppMsg::RestoreBackup => {
+                let restored = self.store.borrow_mut().restore_latest_backup();
+                let (message_type, text) = match restored {
+                    Ok((backup, loaded)) => {
+                        self.reminders = loaded.reminders;
+                        self.load_errors = loaded.errors;
+                        self.reminders_changed();
+                        (MessageType::Info, format!("Restored {} reminders from {}", self.reminders.len(), backup.display()))
+                    }
+                    Err(e) => (MessageType::Error, format!("Could not restore a backup: {}", e)),
+                };
+                println!("{}", text);
+                let restore_dialog = gtk::MessageDialog::new(
+                    Some(&self.main_window),
+                    DialogFlags::MODAL,
+                    message_type,
+                    ButtonsType::Ok,
+                    &text
+                );
+                restore_dialog.connect_response(|dialog, _| dialog.close());
+                restore_dialog.present();
+            }
+            AppMsg::UpdateReminder(updated) => {
+                if let Some(reminder) = self.reminders.iter_mut().find(|r| r.id == updated.id) {
+                    if let Err(e) = self.store.borrow_mut().update(&updated) {
+                        println!("Error saving reminder: {}", e);
+                    } else {
+                        println!("Successfully updated reminder '{}'", updated.name);
+                    }
+                    *reminder = updated;
+                    self.reminders_changed();
+                } else {
+                    println!("Unknown reminder id: {}", updated.id);
+                }
+            }
+            AppMsg::DeleteReminder(id) => {
+                if let Some(index) = self.reminders.iter().position(|r| r.id == id) {
                     self.reminders.remove(index);
-                    if let Err(e) = file_actions::write_reminders(&self.reminders) {
-                        println!("Error writing to XML: {}", e);
+                    if let Err(e) = self.store.borrow_mut().delete(&id) {
+                        println!("Error removing reminder: {}", e);
                     } else {
-                        println!("Successfully removed reminder and saved to XML");
+                        println!("Successfully removed reminder");
+                        self.reminders_changed();
                     }
                 } else {
-                    println!("Invalid reminder index: {}", index);
+                    println!("Unknown reminder id: {}", id);
                 }
             }
-            AppMsg::NewReminder => {
-
-                let reminder_window = gtk::Dialog::builder()
-                    .title("Add new Reminder")
-                    .default_width(600)
-                    .default_height(750)
-                    .build();
-                
-                let reminderbox = gtk::Box::builder()
-                    .orientation(gtk::Orientation::Vertical)
-                    .spacing(5)
-                    .margin_start(45)
-                    .margin_end(45)
-                    .build();
-
-                let hour_adjustment = gtk::Adjustment::new(
-                    12.0,  
-                    0.0,   
-                    23.0,  
-                    1.0,   
-                    1.0,   
-                    0.0    
-                );
-                let reminder_hour = gtk::SpinButton::new(Some(&hour_adjustment), 1.0, 0);
-
-                let minute_adjustment = gtk::Adjustment::new(
-                    0.0,   
-                    0.0,   
-                    59.0,  
-                    1.0,   
-                    5.0,   
-                    0.0    
-                );
-                let reminder_minute = gtk::SpinButton::new(Some(&minute_adjustment), 1.0, 0);
-
-                
-                let calendar = gtk::Calendar::new();
-                let reminder_name = gtk::Entry::new();
-                reminder_name.set_placeholder_text(Some("What is your reminder called?"));
-                reminder_name.set_max_length(100);
-                reminder_name.add_css_class("remindername");
-                let finalize = gtk::Button::new();
-                finalize.set_icon_name("checkmark");
-
-                reminderbox.append(&reminder_name);
-                reminderbox.append(&reminder_hour);
-                reminderbox.append(&reminder_minute);
-                reminderbox.append(&calendar);
-                reminderbox.append(&finalize);
-                reminder_window.set_child(Some(&reminderbox));
-                reminder_window.set_transient_for(Some(&self.main_window));
-                reminder_window.set_modal(true);
-                reminder_window.present();
-                
-                finalize.connect_clicked(clone!(
-                    #[strong] sender,
-                    #[strong] reminder_name,
-                    #[strong] calendar,
-                    #[strong] reminder_window,
-                    #[strong] reminder_hour,
-                    #[strong] reminder_minute,
-                    move |_| {
-                        let text = reminder_name.text().to_string();
-                        
-                        let gtk_date = calendar.date();
-                        let year = gtk_date.year();
-                        let month = gtk_date.month() as u32;
-                        let day = gtk_date.day_of_month() as u32;
-                        let minute = reminder_minute.value_as_int() as u32;
-                        let hour = reminder_hour.value_as_int() as u32;
-
-                        let naive_date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
-                        let naive_time = NaiveTime::from_hms_opt(hour, minute, 0).unwrap();
-                        let naive_datetime = NaiveDateTime::new(naive_date, naive_time);
-                        let local_datetime: DateTime<Local> = Local.from_local_datetime(&naive_datetime).unwrap();
-                        let iso_string = local_datetime.format("%Y-%m-%dT%H:%M:%S").to_string();
-
-                        // Validate that the reminder time is in the future
-                        let current_local = Local::now();
-                        if local_datetime <= current_local {
-                            println!("Reminder time must be in the future!");
-                            let reminder_ood = gtk::MessageDialog::new(
-                                Some(&reminder_window), 
-                                DialogFlags::MODAL, 
-                                MessageType::Error,
-                                ButtonsType::Ok, 
-                                "The Reminder must be in the Future!"
-                            );
-                            reminder_ood.connect_response(move |dialog, response| {
-                                match response {
-                                    ResponseType::Ok => {
-                                        dialog.close();
-                                    }, 
-                                    _ => {
-                                        println!("Box closed");
-                                        dialog.close();
-                                    }
-                                }
-                            });
-                            reminder_ood.present();
-                            return;
-                        }
-
-                        println!("{}", iso_string);
-                        sender.input(AppMsg::FinalizeReminder(text, iso_string));
-                        reminder_window.close(); 
+            AppMsg::Unsnooze(id) => {
+                if let Some(reminder) = self.reminders.iter_mut().find(|r| r.id == id) {
+                    reminder.unsnooze();
+                    if let Err(e) = self.store.borrow_mut().update(reminder) {
+                        println!("Error updating reminder: {}", e);
+                    } else {
+                        self.reminders_changed();
                     }
-                ));
-                
-                
+                } else {
+                    println!("Unknown reminder id: {}", id);
+                }
+            }
+            AppMsg::CompleteReminder(id) => {
+                if let Some(reminder) = self.reminders.iter_mut().find(|r| r.id == id) {
+                    // Repeating reminders only move to the history after their last occurrence
+                    reminder.done(Local::now().fixed_offset());
+                    if let Err(e) = self.store.borrow_mut().update(reminder) {
+                        println!("Error saving reminder: {}", e);
+                    } else {
+                        println!("Marked '{}' done", reminder.name);
+                        self.reminders_changed();
+                    }
+                } else {
+                    println!("Unknown reminder id: {}", id);
+                }
+            }
+            AppMsg::RefreshMissed => {
+                if let Some(daemon) = &self.daemon {
+                    self.missed = daemon.missed();
+                }
+            }
+            AppMsg::DismissMissed(id) => {
+                self.missed.retain(|missed| !id.is_empty() && missed.id != id);
+                if let Some(daemon) = &self.daemon {
+                    daemon.dismiss_missed(&id);
+                }
+            }
+            AppMsg::ShowHistory => {
+                self.history_window.present();
+            }
+            AppMsg::SearchHistory(query) => {
+                self.history_query = query;
+            }
+            AppMsg::PurgeHistory(days) => {
+                let before = Local::now().fixed_offset() - chrono::Duration::days(days.into());
+                match history::purge(self.store.borrow_mut().as_mut(), before) {
+                    Ok(purged) => {
+                        println!("Purged {} completed reminders", purged.len());
+                        self.reminders.retain(|reminder| !purged.contains(&reminder.id));
+                    }
+                    Err(e) => {
+                        println!("Failed to purge the history: {}", e);
+                        self.show_message(MessageType::Error, "The history could not be purged", &e.to_string());
+                    }
+                }
+            }
+            AppMsg::NewReminder => {
+                self.open_reminder_dialog(sender, None);
+            }
+            AppMsg::EditReminder(id) => {
+                if let Some(reminder) = self.reminders.iter().find(|r| r.id == id) {
+                    self.open_reminder_dialog(sender, Some(reminder));
+                } else {
+                    println!("Unknown reminder id: {}", id);
+                }
             }
         }
     }
@@ -323,54 +963,222 @@ impl SimpleComponent for AppModel {
             child = next;
         }
         
-        for (index, reminder) in self.reminders.iter().enumerate() {
+        if !self.missed.is_empty() {
+            widgets.reminder_container.append(&overdue_section(&self.missed, &sender));
+        }
+
+        for reminder in self.reminders.iter().filter(|reminder| reminder.completed_at.is_none()) {
             let reminder_frame = gtk::Frame::new(Some(&reminder.name));
             let delete_reminder = gtk::Button::new();
             delete_reminder.set_label("Delete Reminder");
-            let reminder_index = index;
+            let reminder_id = reminder.id.clone();
             delete_reminder.connect_clicked(clone!(
             #[strong] sender,
+            #[strong] reminder_id,
             move |_| {
-                sender.input(AppMsg::DeleteReminder(reminder_index));
+                sender.input(AppMsg::DeleteReminder(reminder_id.clone()));
+            }
+        ));
+            let edit_reminder = gtk::Button::new();
+            edit_reminder.set_label("Edit Reminder");
+            edit_reminder.connect_clicked(clone!(
+            #[strong] sender,
+            move |_| {
+                sender.input(AppMsg::EditReminder(reminder_id.clone()));
             }
         ));
 
             delete_reminder.set_widget_name("delete");
+            edit_reminder.set_widget_name("edit");
             reminder_frame.set_widget_name("reminder_frame");
             // Center the frame label
             reminder_frame.set_label_align(0.5);
             
-            let naive_dt = NaiveDateTime::parse_from_str(&reminder.time, "%Y-%m-%dT%H:%M:%S")
-                .expect("Failed to parse datetime");     
-            let readable = naive_dt.format("%A, %B %e, %Y at %H:%M:%S").to_string();
-            let reminder_label = gtk::Label::new(Some(&format!("Due: {}", readable)));
+            // Repeating reminders show their next occurrence instead of the first one
+            let now = Local::now().fixed_offset();
+            let due = match &reminder.recurrence {
+                Some(_) => reminder.next_occurrence(now),
+                None => Some(reminder.time),
+            };
+            let reminder_label = match due {
+                Some(due) => {
+                    let readable = due.with_timezone(&Local).format("%A, %B %e, %Y at %H:%M:%S").to_string();
+                    gtk::Label::new(Some(&format!("Due: {}", readable)))
+                }
+                None => gtk::Label::new(Some("No more repeats")),
+            };
             
             // Create a container for both widgets
             let container = gtk::Box::new(gtk::Orientation::Vertical, 10);
             container.append(&reminder_label);
-            container.append(&delete_reminder);
+            if let Some(rule) = &reminder.recurrence {
+                container.append(&gtk::Label::new(Some(&rule.to_string())));
+            }
+            if let Some(until) = reminder.snoozed(now) {
+                let snoozed_label = gtk::Label::new(Some(&format!(
+                    "Snoozed until {} ({}×)",
+                    until.with_timezone(&Local).format("%H:%M"),
+                    reminder.snooze_count
+                )));
+                snoozed_label.set_widget_name("snoozed");
+                let unsnooze = gtk::Button::with_label("Un-snooze");
+                let reminder_id = reminder.id.clone();
+                unsnooze.connect_clicked(clone!(
+                    #[strong] sender,
+                    move |_| {
+                        sender.input(AppMsg::Unsnooze(reminder_id.clone()));
+                    }
+                ));
+                container.append(&snoozed_label);
+                container.append(&unsnooze);
+            }
+            let done_reminder = gtk::Button::with_label("Done");
+            done_reminder.set_widget_name("done");
+            let reminder_id = reminder.id.clone();
+            done_reminder.connect_clicked(clone!(
+                #[strong] sender,
+                move |_| {
+                    sender.input(AppMsg::CompleteReminder(reminder_id.clone()));
+                }
+            ));
+            container.append(&edit_reminder);
+            let finish_row = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+            finish_row.set_homogeneous(true);
+            finish_row.append(&done_reminder);
+            finish_row.append(&delete_reminder);
+            container.append(&finish_row);
             
             // Set the container as the frame's child
             reminder_frame.set_child(Some(&container));
             widgets.reminder_container.append(&reminder_frame);
         }
+
+        let mut child = widgets.history_container.first_child();
+        while let Some(widget) = child {
+            let next = widget.next_sibling();
+            widgets.history_container.remove(&widget);
+            child = next;
+        }
+        let completed = history::search(&self.reminders, &self.history_query);
+        if completed.is_empty() {
+            let empty = if self.history_query.trim().is_empty() { "Nothing done yet" } else { "No matches" };
+            widgets.history_container.append(&gtk::Label::new(Some(empty)));
+        }
+        for reminder in completed {
+            let entry_frame = gtk::Frame::new(Some(&reminder.name));
+            entry_frame.set_widget_name("reminder_frame");
+            entry_frame.set_label_align(0.5);
+            let completed_at = reminder
+                .completed_at
+                .map(|time| time.with_timezone(&Local).format("%A, %B %e, %Y at %H:%M").to_string())
+                .unwrap_or_default();
+            let delete_entry = gtk::Button::with_label("Delete");
+            delete_entry.set_widget_name("delete");
+            let reminder_id = reminder.id.clone();
+            delete_entry.connect_clicked(clone!(
+                #[strong] sender,
+                move |_| {
+                    sender.input(AppMsg::DeleteReminder(reminder_id.clone()));
+                }
+            ));
+            let container = gtk::Box::new(gtk::Orientation::Vertical, 10);
+            container.append(&gtk::Label::new(Some(&format!("Done: {}", completed_at))));
+            container.append(&delete_entry);
+            entry_frame.set_child(Some(&container));
+            widgets.history_container.append(&entry_frame);
+        }
+    }
+}
+
+/// The options a daemon started from here needs to use the same reminders and settings.
+fn daemon_args(data_dir: Option<&Path>, config: Option<&Path>) -> Vec<OsString> {
+    let mut args = Vec::new();
+    // Anything started at login runs from another directory
+    for (flag, path) in [("--data-dir", data_dir), ("--config", config)] {
+        if let Some(path) = path {
+            args.push(OsString::from(flag));
+            args.push(std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).into_os_string());
+        }
     }
+    args
 }
 
 fn main() {
-    // Initialize GTK first
+    let cli = Cli::parse();
+    let daemon_args = daemon_args(cli.data_dir.as_deref(), cli.config.as_deref());
+    if let Some(dir) = cli.data_dir {
+        file_actions::set_data_dir(dir);
+    }
+    let mut startup_notices = Vec::new();
+    let config_path = cli.config.unwrap_or_else(config::default_config_path);
+    let config = config::load(&config_path).unwrap_or_else(|e| {
+        println!("Using the default settings: {}", e);
+        startup_notices.push(format!("Your settings could not be used, the defaults apply instead.\n\n{}", e));
+        config::Config::default()
+    });
+
+    match file_actions::migrate_legacy_file() {
+        Ok(Some(report)) => startup_notices.push(report),
+        Ok(None) => {}
+        Err(e) => {
+            println!("Failed to move reminders out of ~/.cache: {}", e);
+            startup_notices.push(format!("Your reminders could not be moved out of ~/.cache: {}", e));
+        }
+    }
+    match store::copy_xml_into_new_database(config.storage.backend) {
+        Ok(Some(report)) => startup_notices.push(report),
+        Ok(None) => {}
+        Err(e) => {
+            println!("Failed to copy reminders into the database: {}", e);
+            startup_notices.push(format!("Your reminders could not be copied into the new database: {}", e));
+        }
+    }
+    let store: SharedStore = std::rc::Rc::new(RefCell::new(store::open_store(config.storage.backend)));
+
+    // Save upgraded files right away, unless that would drop unreadable entries
+    let loaded = store.borrow_mut().load();
+    if let Ok(loaded) = loaded
+        && loaded.needs_save
+        && loaded.errors.is_empty()
+    {
+        match store.borrow_mut().replace_all(&loaded.reminders) {
+            Ok(_) => println!("Upgraded reminders file from format {}", loaded.version),
+            Err(e) => println!("Error saving reminders: {}", e),
+        }
+    }
+
+    if cli.daemon {
+        if let Err(e) = daemon::run(&config) {
+            eprintln!("rewind: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Command-line operations don't need a window
+    if let Some(command) = cli.command {
+        match commands::run(command, store.borrow_mut().as_mut(), &daemon_args) {
+            // Without a session bus there is nobody to tell, a daemon elsewhere notices
+            // the store's file changed within a minute anyway
+            Ok(true) => {
+                let _ = daemon::reload_running();
+            }
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("rewind: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    file_actions::does_file_exist(&store.borrow().location());
+
+    // Initialize GTK
     gtk::init().expect("Failed to initialize GTK");
     
     // Apply CSS styling
     apply_css();
-    
-    file_actions::does_file_exist();
    
-    let notified_reminders: std::rc::Rc<RefCell<HashSet<String>>> = std::rc::Rc::new(RefCell::new(HashSet::new()));
-    
-    // Track past reminders to avoid notifying for them
-    let past_reminders: std::rc::Rc<RefCell<HashSet<String>>> = std::rc::Rc::new(RefCell::new(HashSet::new()));
-    
     // Try a second test notification after a short delay
     gtk::glib::timeout_add_seconds_local(2, || {
         println!("Sending secondary test notification");
@@ -386,110 +1194,25 @@ fn main() {
         }
         gtk::glib::ControlFlow::Break  // Run only once
     });
-    
-    // Mark all existing past reminders on startup
-    if let Ok(reminders) = file_actions::read_reminders() {
-        let current_naive = Local::now().naive_local();
-        let mut past = past_reminders.borrow_mut();
-        for reminder in reminders {
-            if let Ok(reminder_time) = NaiveDateTime::parse_from_str(&reminder.time, "%Y-%m-%dT%H:%M:%S") {
-                if reminder_time < current_naive {
-                    // This is a past reminder, mark it
-                    let reminder_id = format!("{}_{}", reminder.name, reminder.time);
-                    past.insert(reminder_id);
-                    println!("Marked past reminder: {}", reminder.name);
-                }
-            }
-        }
-    }
-    
-    
-    
-    gtk::glib::timeout_add_seconds_local(15, clone!(
-        #[strong] notified_reminders,
-        #[strong] past_reminders,
-        move || {
-            // println!("Checking reminders...");
-            
-            if let Ok(reminders) = file_actions::read_reminders() {
-                let current_local = Local::now();
-                let current_naive = current_local.naive_local();
-                
-                // println!("Current time: {}", current_naive);
-                // println!("Found {} reminders", reminders.len());
-                
-                for reminder in reminders {
-                    if let Ok(reminder_time) = NaiveDateTime::parse_from_str(&reminder.time, "%Y-%m-%dT%H:%M:%S") {
-                        let time_diff = reminder_time.signed_duration_since(current_naive);
-                        let reminder_id = format!("{}_{}", reminder.name, reminder.time);
-                        
-                        // println!("Reminder '{}' time: {}, diff: {} seconds", 
-                                //  reminder.name, reminder_time, time_diff.num_seconds());
-                        
-                        // Check if this is a past reminder we've already seen
-                        if past_reminders.borrow().contains(&reminder_id) {
-                            // println!("Skipping past reminder: {}", reminder.name);
-                            continue;
-                        }
-                        
-                        // Only notify for upcoming reminders or those due in the last 60 seconds
-                        if time_diff.num_seconds() >= -60 && time_diff.num_seconds() <= 300 {
-                            // Check if we've already notified for this reminder
-                            let mut notified = notified_reminders.borrow_mut();
-                            if !notified.contains(&reminder_id) {
-                                println!("Sending notification for: {}", reminder.name);
-                                
-                                // Try with more specific notification settings
-                                match Notification::new()
-                                    .summary(&format!("Reminder: {}", reminder.name))
-                                    .body(&format!("Your reminder '{}' is due now!", reminder.name))
-                                    .icon("appointment-soon")
-                                    .timeout(Timeout::Milliseconds(10000))
-                                    .hint(Hint::Urgency(notify_rust::Urgency::Critical))
-                                    .hint(Hint::Category("reminder".to_string()))
-                                    .show() {
-                                    Ok(_) => {
-                                        println!("Notification sent for: {}", reminder.name);
-                                        notified.insert(reminder_id.clone());
-                                        
-                                        // Also try native command as fallback
-                                        let cmd = format!(
-                                            "notify-send -u critical \"Reminder: {}\" \"Your reminder is due now!\"",
-                                            reminder.name
-                                        );
-                                        match std::process::Command::new("sh")
-                                            .arg("-c")
-                                            .arg(&cmd)
-                                            .status() {
-                                            Ok(_) => println!("Sent fallback notification via command"),
-                                            Err(e) => println!("Failed to send fallback: {}", e),
-                                        }
-                                    },
-                                    Err(e) => println!("Failed to send notification: {}", e),
-                                }
-                            } else {
-                                // println!("Already notified for: {}", reminder.name);
-                            }
-                        } else if time_diff.num_seconds() < 0 {
-                            // This reminder is in the past, mark it
-                            past_reminders.borrow_mut().insert(reminder_id);
-                            println!("Marked past reminder: {}", reminder.name);
-                        }
-                    } else {
-                        println!("Failed to parse reminder time for: {}", reminder.name);
-                    }
-                }                        /*println!("Failed to parse reminder time for: {}", reminder.name);*/
 
-            } else {
-                println!("Failed to read reminders");
+    // Notifications come from the daemon, so they keep coming after the window is closed
+    let daemon = match DaemonClient::connect(&daemon_args) {
+        Ok((client, started)) => {
+            if started {
+                println!("Started the background service");
             }
-            
-            gtk::glib::ControlFlow::Continue
+            Some(client)
         }
-    ));
+        Err(e) => {
+            println!("Could not reach the background service: {}", e);
+            startup_notices.push(format!("Reminders will not show notifications, the background service could not be started: {}", e));
+            None
+        }
+    };
 
-    let app = RelmApp::new("Rewind");
-    app.run::<AppModel>(0);
+    // Our own flags were handled above, GTK should not try to parse them
+    let app = RelmApp::new("Rewind").with_args(Vec::new());
+    app.run::<AppModel>(AppInit { store, daemon, startup_notices });
 }
 
 
@@ -0,0 +1,71 @@
+//! Upgrades reminder documents written by older versions of Rewind.
+//!
+//! Every change to the on-disk format that older documents have to be
+//! converted for bumps `CURRENT_VERSION` and adds one step to `MIGRATIONS`,
+//! which turns a document of the previous version into the next one.
+//! Documents are upgraded one step at a time, so a file from any older
+//! version ends up in the current format. New optional elements need no
+//! new version: older documents simply don't have them, and older versions
+//! of Rewind keep them unchanged.
+
+use std::collections::HashSet;
+
+use crate::reminder;
+use crate::xml_tree::{Element, Node};
+
+pub const CURRENT_VERSION: u32 = 2;
+
+/// `MIGRATIONS[n]` upgrades a version `n + 1` document to version `n + 2`.
+const MIGRATIONS: [fn(&mut Element); (CURRENT_VERSION - 1) as usize] = [v1_to_v2];
+
+/// The version of a document. Files written before versioning count as version 1.
+pub fn document_version(root: &Element) -> Result<u32, String> {
+    match root.attribute("version") {
+        None => Ok(1),
+        Some(text) => text
+            .trim()
+            .parse()
+            .ok()
+            .filter(|version| *version >= 1)
+            .ok_or_else(|| format!("unknown document version '{}'", text)),
+    }
+}
+
+/// Upgrades `root` in place to `CURRENT_VERSION` and returns the version it had.
+/// Documents from newer versions are left alone.
+pub fn migrate(root: &mut Element) -> Result<u32, String> {
+    if root.name != "reminders" {
+        return Err(format!("expected <reminders> but found <{}>", root.name));
+    }
+    let original = document_version(root)?;
+    for version in original..CURRENT_VERSION {
+        MIGRATIONS[(version - 1) as usize](root);
+        root.set_attribute("version", &(version + 1).to_string());
+    }
+    Ok(original)
+}
+
+/// Version 2 gives every reminder an id and stores times with their UTC offset.
+fn v1_to_v2(root: &mut Element) {
+    let mut seen_ids = HashSet::new();
+    for (position, entry) in root.elements_mut().filter(|e| e.name == "reminder").enumerate() {
+        let time_text = entry.child("time").map(|time| time.text().trim().to_string()).unwrap_or_default();
+
+        let id = entry.child("id").map(|id| id.text().trim().to_string()).unwrap_or_default();
+        if id.is_empty() || seen_ids.contains(&id) {
+            let name = entry.child("name").map(|name| name.text()).unwrap_or_default();
+            let derived = reminder::derived_id(position, &name, &time_text);
+            entry.set_child_text("id", &derived);
+            seen_ids.insert(derived);
+        } else {
+            seen_ids.insert(id);
+        }
+
+        // Times that can't be converted are left for the reader to report
+        for child in entry.elements_mut().filter(|e| e.name == "time" || e.name == "exdate") {
+            if let Ok(time) = reminder::parse_legacy_time(child.text().trim()) {
+                child.children = vec![Node::Text(reminder::format_time(&time))];
+            }
+        }
+    }
+}
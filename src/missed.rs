@@ -0,0 +1,194 @@
+//! Reminders that came up while nothing could tell about them, because the
+//! computer was off or suspended or the daemon wasn't running. The daemon
+//! remembers when it last checked, so it can catch up when it gets going again.
+
+use std::error::Error;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde::{Deserialize, Serialize};
+
+use crate::file_actions;
+use crate::reminder::{self, Reminder};
+
+const STATE_FILE: &str = "state.toml";
+
+/// A reminder that went off unnoticed, with the first time it was due then.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Missed {
+    pub id: String,
+    pub name: String,
+    pub due: DateTime<FixedOffset>,
+}
+
+/// What the daemon keeps between runs. Only the daemon writes it, the window
+/// asks the daemon over the bus instead.
+#[derive(Debug, Default)]
+pub struct RunState {
+    path: PathBuf,
+    /// The last time reminders were checked.
+    pub last_run: Option<DateTime<FixedOffset>>,
+    /// Missed reminders that were not dismissed yet.
+    pub missed: Vec<Missed>,
+}
+
+// How the state looks on disk, times are RFC 3339 like everywhere else
+#[derive(Serialize, Deserialize)]
+struct StateFile {
+    last_run: Option<String>,
+    #[serde(default)]
+    missed: Vec<MissedEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MissedEntry {
+    id: String,
+    name: String,
+    due: String,
+}
+
+pub fn state_path() -> PathBuf {
+    file_actions::data_dir().join(STATE_FILE)
+}
+
+impl RunState {
+    /// Reads the state kept at `path`. A missing or broken file gives an
+    /// empty state, the worst that does is miss out on one catch-up.
+    pub fn load(path: PathBuf) -> Self {
+        let mut state = RunState { path, ..RunState::default() };
+        let file: StateFile = match fs::read_to_string(&state.path) {
+            Ok(text) => match toml::from_str(&text) {
+                Ok(file) => file,
+                Err(e) => {
+                    println!("Ignoring {}: {}", state.path.display(), e);
+                    return state;
+                }
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => return state,
+            Err(e) => {
+                println!("Could not read {}: {}", state.path.display(), e);
+                return state;
+            }
+        };
+
+        state.last_run = file.last_run.and_then(|time| reminder::parse_time(&time).ok());
+        state.missed = file
+            .missed
+            .into_iter()
+            .filter_map(|entry| {
+                let due = reminder::parse_time(&entry.due).ok()?;
+                Some(Missed { id: entry.id, name: entry.name, due })
+            })
+            .collect();
+        state
+    }
+
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let file = StateFile {
+            last_run: self.last_run.as_ref().map(reminder::format_time),
+            missed: self
+                .missed
+                .iter()
+                .map(|missed| MissedEntry {
+                    id: missed.id.clone(),
+                    name: missed.name.clone(),
+                    due: reminder::format_time(&missed.due),
+                })
+                .collect(),
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        // Written aside and renamed over, so a power cut can't leave half a file
+        let partial = self.path.with_extension("toml.partial");
+        fs::write(&partial, toml::to_string(&file)?)?;
+        fs::rename(&partial, &self.path)?;
+        Ok(())
+    }
+
+    /// Adds newly missed reminders, keeping the earlier entry for ones already listed.
+    /// Returns whether anything was added.
+    pub fn add_missed(&mut self, missed: Vec<Missed>) -> bool {
+        let before = self.missed.len();
+        for entry in missed {
+            if !self.missed.iter().any(|known| known.id == entry.id) {
+                self.missed.push(entry);
+            }
+        }
+        self.missed.len() != before
+    }
+
+    /// Dismisses the entry for reminder `id`, or every entry when `id` is empty.
+    /// Returns whether anything was dismissed.
+    pub fn dismiss(&mut self, id: &str) -> bool {
+        let before = self.missed.len();
+        self.missed.retain(|missed| !id.is_empty() && missed.id != id);
+        self.missed.len() != before
+    }
+
+    /// Drops entries for reminders that were since deleted or completed.
+    /// Returns whether anything was dropped.
+    pub fn forget_finished(&mut self, reminders: &[Reminder]) -> bool {
+        let before = self.missed.len();
+        self.missed.retain(|missed| {
+            reminders.iter().any(|reminder| reminder.id == missed.id && reminder.completed_at.is_none())
+        });
+        self.missed.len() != before
+    }
+}
+
+/// The title and text of the notification that lists missed reminders.
+pub fn describe(missed: &[Missed]) -> (String, String) {
+    // Enough to recognise them, the window lists every one
+    const LISTED: usize = 5;
+
+    let title = match missed.len() {
+        1 => "You missed a reminder".to_string(),
+        count => format!("You missed {} reminders", count),
+    };
+    let mut lines: Vec<String> = missed
+        .iter()
+        .take(LISTED)
+        .map(|missed| format!("{} ({})", missed.name, missed.due.with_timezone(&Local).format("%a %H:%M")))
+        .collect();
+    if missed.len() > LISTED {
+        lines.push(format!("and {} more", missed.len() - LISTED));
+    }
+    (title, lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+
+    #[test]
+    fn missed_reminders_are_kept_until_dismissed() {
+        let time = |text: &str| reminder::parse_time(text).unwrap();
+        let stretch = Reminder::new("Stretch".to_string(), time("2026-10-18T08:00:00+02:00"));
+        let mut bank = Reminder::new("Call the bank".to_string(), time("2026-10-17T15:30:00+02:00"));
+        let missed: Vec<Missed> = [&stretch, &bank]
+            .iter()
+            .map(|reminder| Missed { id: reminder.id.clone(), name: reminder.name.clone(), due: reminder.time })
+            .collect();
+
+        let dir = ScratchDir::new("missed");
+        let mut state = RunState::load(dir.join(STATE_FILE));
+        assert!(state.last_run.is_none());
+        state.last_run = Some(time("2026-10-18T12:00:00+02:00"));
+        assert!(state.add_missed(missed.clone()));
+        assert!(!state.add_missed(missed.clone()), "already listed");
+        state.save().unwrap();
+
+        let mut state = RunState::load(dir.join(STATE_FILE));
+        assert_eq!(state.last_run, Some(time("2026-10-18T12:00:00+02:00")));
+        assert_eq!(state.missed, missed);
+        bank.complete(time("2026-10-18T12:30:00+02:00"));
+        assert!(state.forget_finished(&[stretch, bank]));
+        assert_eq!(state.missed.len(), 1);
+        assert!(state.dismiss(""));
+        assert!(state.missed.is_empty());
+    }
+}
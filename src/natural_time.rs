@@ -0,0 +1,474 @@
+//! Reads due times written the way people say them: "in 20 minutes",
+//! "tomorrow 9am", "next friday at 17:30", "every monday 10:00" or "on the 3rd".
+//!
+//! Everything is resolved in the local time zone relative to a given `now`,
+//! the same way the reminder dialog turns its spin buttons into a time.
+
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, Local, Month, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Timelike,
+    Weekday,
+};
+
+use crate::recurrence::{self, Frequency, Recurrence};
+
+// Used when a phrase names a day but no time
+const DEFAULT_HOUR: u32 = 9;
+
+/// When a reminder is due, and how it repeats for phrases with "every".
+#[derive(Debug, Clone, PartialEq)]
+pub struct When {
+    pub time: DateTime<FixedOffset>,
+    pub recurrence: Option<Recurrence>,
+}
+
+/// Parses a whole phrase such as "tomorrow 9am".
+pub fn parse(text: &str, now: DateTime<Local>) -> Result<When, String> {
+    let words: Vec<String> = text.to_lowercase().replace(',', " ").split_whitespace().map(str::to_string).collect();
+    if words.is_empty() {
+        return Err("Say when, e.g. \"tomorrow 9am\"".to_string());
+    }
+    Phrase { words, pos: 0 }.parse(now)
+}
+
+/// Splits quick-add text like "Water plants every monday 10:00" into the
+/// reminder's name and when it is due. The time is the longest run of words
+/// at the end that makes sense as one, everything before it is the name.
+pub fn split_quick_add(text: &str, now: DateTime<Local>) -> Result<(String, When), String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    for start in 1..words.len() {
+        let Ok(when) = parse(&words[start..].join(" "), now) else {
+            continue;
+        };
+        let name = words[..start].join(" ");
+        let name = name.trim_end_matches([',', ';', ':', '-']).trim();
+        if !name.is_empty() {
+            return Ok((name.to_string(), when));
+        }
+    }
+    Err("Write the name, then when, e.g. \"Water plants tomorrow 9am\"".to_string())
+}
+
+/// The day a phrase names, before it is pinned to a date.
+#[derive(Debug, Clone, Copy)]
+enum Day {
+    Today,
+    Tomorrow,
+    /// `next` skips today even when the time is still ahead.
+    Weekday { weekday: Weekday, next: bool },
+    OfMonth(u32),
+    Date { year: Option<i32>, month: u32, day: u32 },
+}
+
+struct Phrase {
+    words: Vec<String>,
+    pos: usize,
+}
+
+impl Phrase {
+    fn peek(&self) -> Option<&str> {
+        self.words.get(self.pos).map(String::as_str)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<&str> {
+        self.words.get(self.pos + offset).map(String::as_str)
+    }
+
+    fn eat(&mut self, word: &str) -> bool {
+        let found = self.peek() == Some(word);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn parse(mut self, now: DateTime<Local>) -> Result<When, String> {
+        if self.eat("in") {
+            return self.relative(now);
+        }
+
+        let mut day = None;
+        let mut time = None;
+        let mut every = None;
+        while let Some(word) = self.peek() {
+            let word = word.to_string();
+            // Filler words, as long as something follows them
+            if matches!(word.as_str(), "on" | "the") && self.peek_at(1).is_some() {
+                self.pos += 1;
+                continue;
+            }
+
+            if word == "every" {
+                self.pos += 1;
+                let rule = self.every()?;
+                set_once(&mut every, rule, "how often it repeats")?;
+            } else if word == "at" {
+                self.pos += 1;
+                let clock = self.time(true)?.ok_or("Expected a time after \"at\"")?;
+                set_once(&mut time, clock, "the time")?;
+            } else if let Some(clock) = self.time(false)? {
+                set_once(&mut time, clock, "the time")?;
+            } else if let Some(named) = self.day()? {
+                set_once(&mut day, named, "the day")?;
+            } else {
+                return Err(format!("\"{}\" is not a time I understand", word));
+            }
+        }
+        resolve(day, time, every, now)
+    }
+
+    /// "in 20 minutes", "in an hour", "in 1 hour 30 minutes", "in 2h".
+    fn relative(&mut self, now: DateTime<Local>) -> Result<When, String> {
+        let mut total = Duration::zero();
+        while let Some(word) = self.peek().map(str::to_string) {
+            self.pos += 1;
+            if word == "and" {
+                continue;
+            }
+            let digits: String = word.chars().take_while(char::is_ascii_digit).collect();
+            let (amount, unit) = match (word.as_str(), digits.is_empty()) {
+                ("a" | "an" | "one", _) => (1, None),
+                (_, false) => {
+                    let amount: i64 = digits.parse().map_err(|_| format!("{} is too large", word))?;
+                    let rest = &word[digits.len()..];
+                    (amount, (!rest.is_empty()).then(|| rest.to_string()))
+                }
+                (_, true) => return Err(format!("Expected a number instead of \"{}\"", word)),
+            };
+            let unit = match unit {
+                Some(unit) => unit,
+                None => {
+                    let unit = self.peek().ok_or("Expected minutes, hours, days or weeks")?.to_string();
+                    self.pos += 1;
+                    unit
+                }
+            };
+            let step = match unit.as_str() {
+                "minute" | "minutes" | "min" | "mins" | "m" => Duration::try_minutes(amount),
+                "hour" | "hours" | "hr" | "hrs" | "h" => Duration::try_hours(amount),
+                "day" | "days" | "d" => Duration::try_days(amount),
+                "week" | "weeks" | "w" => Duration::try_weeks(amount),
+                other => return Err(format!("\"{}\" is not minutes, hours, days or weeks", other)),
+            };
+            let too_far = || format!("{} {} is too far away", amount, unit);
+            total = step.and_then(|step| total.checked_add(&step)).ok_or_else(too_far)?;
+        }
+
+        if total <= Duration::zero() {
+            return Err("Say how long from now, e.g. \"in 20 minutes\"".to_string());
+        }
+        let time = now.checked_add_signed(total).ok_or("That is too far away")?;
+        let time = time.with_nanosecond(0).unwrap_or(time);
+        Ok(When { time: time.fixed_offset(), recurrence: None })
+    }
+
+    /// A clock time: "17:30", "9am", "9:15 pm", "noon". Bare hours like "9"
+    /// only count when `bare` is set, after "at".
+    fn time(&mut self, bare: bool) -> Result<Option<NaiveTime>, String> {
+        let Some(word) = self.peek().map(str::to_string) else {
+            return Ok(None);
+        };
+        match word.as_str() {
+            "noon" | "midday" => {
+                self.pos += 1;
+                return Ok(NaiveTime::from_hms_opt(12, 0, 0));
+            }
+            "midnight" => {
+                self.pos += 1;
+                return Ok(NaiveTime::from_hms_opt(0, 0, 0));
+            }
+            _ => {}
+        }
+
+        let (clock, mut meridiem) = match word.strip_suffix("am").or_else(|| word.strip_suffix("pm")) {
+            Some(clock) => (clock.to_string(), Some(word.ends_with("pm"))),
+            None => (word.clone(), None),
+        };
+        let (hour, minute) = match clock.split_once(':') {
+            Some((hour, minute)) => (hour, Some(minute)),
+            None => (clock.as_str(), None),
+        };
+        let is_number = |text: &str| !text.is_empty() && text.len() <= 2 && text.chars().all(|c| c.is_ascii_digit());
+        if !is_number(hour) || minute.is_some_and(|minute| minute.len() != 2 || !is_number(minute)) {
+            return Ok(None);
+        }
+        let separate = if meridiem.is_none() { self.peek_at(1).filter(|next| matches!(*next, "am" | "pm")) } else { None };
+        if let Some(next) = separate {
+            meridiem = Some(next == "pm");
+        }
+        if minute.is_none() && meridiem.is_none() && !bare {
+            return Ok(None);
+        }
+        self.pos += if separate.is_some() { 2 } else { 1 };
+
+        let mut hour: u32 = hour.parse().unwrap_or(99);
+        let minute: u32 = minute.map_or(Ok(0), str::parse).unwrap_or(99);
+        if let Some(pm) = meridiem {
+            if !(1..=12).contains(&hour) {
+                return Err(format!("{} is not a time", word));
+            }
+            hour = match (hour, pm) {
+                (12, false) => 0,
+                (12, true) => 12,
+                (hour, true) => hour + 12,
+                (hour, false) => hour,
+            };
+        }
+        NaiveTime::from_hms_opt(hour, minute, 0).map(Some).ok_or_else(|| format!("{} is not a time", word))
+    }
+
+    fn day(&mut self) -> Result<Option<Day>, String> {
+        let Some(word) = self.peek().map(str::to_string) else {
+            return Ok(None);
+        };
+        let day = match word.as_str() {
+            "today" => Day::Today,
+            "tomorrow" => Day::Tomorrow,
+            "next" => {
+                let weekday = self
+                    .peek_at(1)
+                    .and_then(|next| next.parse::<Weekday>().ok())
+                    .ok_or("Expected a weekday after \"next\"")?;
+                self.pos += 1;
+                Day::Weekday { weekday, next: true }
+            }
+            _ => {
+                if let Ok(weekday) = word.parse::<Weekday>() {
+                    Day::Weekday { weekday, next: false }
+                } else if let Ok(date) = NaiveDate::parse_from_str(&word, "%Y-%m-%d") {
+                    Day::Date { year: Some(date.year()), month: date.month(), day: date.day() }
+                } else if let Ok(month) = word.parse::<Month>() {
+                    // "october 20", "oct 20th 2027"
+                    let day = self.peek_at(1).and_then(day_number).ok_or_else(|| format!("Expected a day after \"{}\"", word))?;
+                    self.pos += 1;
+                    let year = self.year_after();
+                    Day::Date { year, month: month.number_from_month(), day }
+                } else if let Some(day) = day_number(&word) {
+                    // "20 october", or "the 3rd" of whichever month comes next
+                    match self.peek_at(1).and_then(|next| next.parse::<Month>().ok()) {
+                        Some(month) => {
+                            self.pos += 1;
+                            let year = self.year_after();
+                            Day::Date { year, month: month.number_from_month(), day }
+                        }
+                        None if !word.chars().all(|c| c.is_ascii_digit()) => Day::OfMonth(day),
+                        None => return Ok(None),
+                    }
+                } else {
+                    return Ok(None);
+                }
+            }
+        };
+        self.pos += 1;
+        Ok(Some(day))
+    }
+
+    /// A four-digit year following a date, e.g. the 2027 in "oct 20 2027".
+    fn year_after(&mut self) -> Option<i32> {
+        let year = self.peek_at(1).filter(|word| word.len() == 4).and_then(|word| word.parse().ok());
+        if year.is_some() {
+            self.pos += 1;
+        }
+        year
+    }
+
+    /// What follows "every": "day", "2 weeks", "other month", "weekday", "monday and thursday".
+    fn every(&mut self) -> Result<Recurrence, String> {
+        let mut interval = 1;
+        if self.eat("other") {
+            interval = 2;
+        } else if let Some(n) = self.peek().and_then(|word| word.parse::<u32>().ok()) {
+            interval = n.max(1);
+            self.pos += 1;
+        }
+
+        let word = self.peek().ok_or("Expected what to repeat after \"every\"")?.to_string();
+        self.pos += 1;
+        let frequency = match word.as_str() {
+            "day" | "days" => Some(Frequency::Daily),
+            "week" | "weeks" => Some(Frequency::Weekly),
+            "month" | "months" => Some(Frequency::Monthly),
+            "year" | "years" => Some(Frequency::Yearly),
+            _ => None,
+        };
+        if let Some(frequency) = frequency {
+            let mut rule = Recurrence::new(frequency);
+            rule.interval = interval;
+            return Ok(rule);
+        }
+
+        let mut rule = Recurrence::new(Frequency::Weekly);
+        rule.interval = interval;
+        rule.weekdays = match word.as_str() {
+            "weekday" | "weekdays" => vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri],
+            "weekend" | "weekends" => vec![Weekday::Sat, Weekday::Sun],
+            _ => {
+                let first = word.parse::<Weekday>().map_err(|_| format!("\"every {}\" is not something I can repeat", word))?;
+                let mut weekdays = vec![first];
+                // "monday and thursday", "mon wed fri"
+                loop {
+                    let offset = usize::from(self.peek() == Some("and"));
+                    match self.peek_at(offset).and_then(|next| next.parse::<Weekday>().ok()) {
+                        Some(weekday) => {
+                            self.pos += offset + 1;
+                            if !weekdays.contains(&weekday) {
+                                weekdays.push(weekday);
+                            }
+                        }
+                        None => break,
+                    }
+                }
+                weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+                weekdays
+            }
+        };
+        Ok(rule)
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, what: &str) -> Result<(), String> {
+    if slot.is_some() {
+        return Err(format!("Give {} only once", what));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+/// "3rd", "20th", and plain "20" for dates that name a month.
+fn day_number(word: &str) -> Option<u32> {
+    let digits = ["st", "nd", "rd", "th"].iter().find_map(|suffix| word.strip_suffix(suffix)).unwrap_or(word);
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+/// Pins the parsed parts to the first matching local time after `now`.
+fn resolve(day: Option<Day>, time: Option<NaiveTime>, every: Option<Recurrence>, now: DateTime<Local>) -> Result<When, String> {
+    let today = now.date_naive();
+    let clock = time.unwrap_or_else(|| NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0).unwrap());
+    let ahead = |date: NaiveDate| local(date, clock).is_ok_and(|time| time > now);
+    let weekdays = every.as_ref().map(|rule| rule.weekdays.clone()).unwrap_or_default();
+
+    let date = match day {
+        // A weekly rule starts on the first of its days
+        None if !weekdays.is_empty() => (0..14)
+            .map(|n| today + Duration::days(n))
+            .find(|date| weekdays.contains(&date.weekday()) && ahead(*date))
+            .ok_or("That never comes up")?,
+        None if ahead(today) => today,
+        None => today + Duration::days(1),
+        Some(Day::Today) => today,
+        Some(Day::Tomorrow) => today + Duration::days(1),
+        Some(Day::Weekday { weekday, next }) => {
+            let days = (weekday.num_days_from_monday() + 7 - today.weekday().num_days_from_monday()) % 7;
+            let date = today + Duration::days(days.into());
+            if date == today && (next || !ahead(date)) { date + Duration::days(7) } else { date }
+        }
+        Some(Day::OfMonth(day)) => (0..24)
+            .filter_map(|n| {
+                let months = today.year() * 12 + today.month0() as i32 + n;
+                let (year, month) = (months.div_euclid(12), months.rem_euclid(12) as u32 + 1);
+                (day <= recurrence::days_in_month(year, month)).then(|| NaiveDate::from_ymd_opt(year, month, day)).flatten()
+            })
+            .find(|date| ahead(*date))
+            .ok_or("That day never comes up")?,
+        Some(Day::Date { year: Some(year), month, day }) => {
+            NaiveDate::from_ymd_opt(year, month, day).ok_or_else(|| format!("{}-{:02}-{:02} is not a date", year, month, day))?
+        }
+        Some(Day::Date { year: None, month, day }) => (0..8)
+            .filter_map(|n| NaiveDate::from_ymd_opt(today.year() + n, month, day))
+            .find(|date| ahead(*date))
+            .ok_or("That date never comes up")?,
+    };
+
+    if !weekdays.is_empty() && !weekdays.contains(&date.weekday()) {
+        return Err(format!("{} is not one of the days it repeats on", date.format("%A %-d %B")));
+    }
+    Ok(When { time: local(date, clock)?, recurrence: every })
+}
+
+fn local(date: NaiveDate, time: NaiveTime) -> Result<DateTime<FixedOffset>, String> {
+    // Times skipped by a DST change have no local equivalent
+    Local
+        .from_local_datetime(&NaiveDateTime::new(date, time))
+        .earliest()
+        .map(|time| time.fixed_offset())
+        .ok_or_else(|| format!("{} does not exist on {} in your time zone", time.format("%H:%M"), date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Wednesday
+    fn now() -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 10, 14, 10, 0, 0).unwrap()
+    }
+
+    fn local_text(text: &str) -> String {
+        let when = parse(text, now()).unwrap_or_else(|e| panic!("{}: {}", text, e));
+        when.time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    #[test]
+    fn days_and_times_resolve_to_the_next_match() {
+        assert_eq!(local_text("in 20 minutes"), "2026-10-14 10:20");
+        assert_eq!(local_text("in an hour and 30 mins"), "2026-10-14 11:30");
+        assert_eq!(local_text("in 2d"), "2026-10-16 10:00");
+        assert_eq!(local_text("tomorrow 9am"), "2026-10-15 09:00");
+        assert_eq!(local_text("9 pm"), "2026-10-14 21:00");
+        assert_eq!(local_text("9:30"), "2026-10-15 09:30");
+        assert_eq!(local_text("at noon"), "2026-10-14 12:00");
+        assert_eq!(local_text("friday"), "2026-10-16 09:00");
+        assert_eq!(local_text("next friday at 17:30"), "2026-10-16 17:30");
+        assert_eq!(local_text("wednesday at 11"), "2026-10-14 11:00");
+        assert_eq!(local_text("next wednesday at 11"), "2026-10-21 11:00");
+        assert_eq!(local_text("wed 8am"), "2026-10-21 08:00");
+        assert_eq!(local_text("on the 3rd"), "2026-11-03 09:00");
+        assert_eq!(local_text("the 31st at 18:00"), "2026-10-31 18:00");
+        assert_eq!(local_text("oct 20th 2027 at 12pm"), "2027-10-20 12:00");
+        assert_eq!(local_text("14 february"), "2027-02-14 09:00");
+        assert_eq!(local_text("2026-12-24 18:00"), "2026-12-24 18:00");
+    }
+
+    #[test]
+    fn every_makes_repeating_reminders() {
+        let when = parse("every monday 10:00", now()).unwrap();
+        assert_eq!(when.time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string(), "2026-10-19 10:00");
+        assert_eq!(when.recurrence.unwrap().to_rrule(), "FREQ=WEEKLY;BYDAY=MO");
+
+        let when = parse("every other day at 7am", now()).unwrap();
+        assert_eq!(when.recurrence.unwrap().to_rrule(), "FREQ=DAILY;INTERVAL=2");
+        assert_eq!(local_text("every weekday at 9"), "2026-10-15 09:00");
+        assert_eq!(parse("every tue and thu", now()).unwrap().recurrence.unwrap().to_rrule(), "FREQ=WEEKLY;BYDAY=TU,TH");
+        assert_eq!(parse("every month on the 3rd", now()).unwrap().recurrence.unwrap().to_rrule(), "FREQ=MONTHLY");
+        assert!(parse("every monday tomorrow", now()).is_err(), "tomorrow is a Thursday");
+    }
+
+    #[test]
+    fn nonsense_is_rejected() {
+        for text in ["", "soon", "in", "in 0 minutes", "at", "25:00", "13pm", "9", "tomorrow tomorrow", "next week"] {
+            assert!(parse(text, now()).is_err(), "{:?} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn amounts_past_the_calendar_are_errors() {
+        for text in ["in 1000000000 days", "in 9999999999999999 weeks", "in 100000000 weeks and 100000000 weeks"] {
+            let error = parse(text, now()).unwrap_err();
+            assert!(error.contains("too far away"), "{:?}: {}", text, error);
+        }
+        assert!(split_quick_add("Tea in 1000000000 days", now()).is_err());
+    }
+
+    #[test]
+    fn quick_add_splits_the_name_from_the_time() {
+        let (name, when) = split_quick_add("Water plants every monday 10:00", now()).unwrap();
+        assert_eq!(name, "Water plants");
+        assert!(when.recurrence.is_some());
+
+        let (name, _) = split_quick_add("Pay rent, on the 3rd", now()).unwrap();
+        assert_eq!(name, "Pay rent");
+        let (name, _) = split_quick_add("Call the 2 dentists in 20 minutes", now()).unwrap();
+        assert_eq!(name, "Call the 2 dentists");
+        assert!(split_quick_add("tomorrow", now()).is_err(), "a reminder needs a name");
+        assert!(split_quick_add("Buy milk", now()).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+//! The ways an alert reaches the desktop. The daemon sends every alert
+//! through one `Notifier`, picked in the configuration.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use notify_rust::{Hint, Notification, Timeout, Urgency};
+
+use crate::config::NotifierBackend;
+
+/// Called with the key of the button that was clicked, from another thread.
+pub type OnAction = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// What a notification shows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alert {
+    pub title: String,
+    pub body: String,
+    /// A freedesktop icon name.
+    pub icon: String,
+    pub urgent: bool,
+    /// How long it stays up, or `None` to leave that to the desktop.
+    pub timeout: Option<Duration>,
+    /// Stays up until it is dismissed, whatever `timeout` says.
+    pub persistent: bool,
+    /// The buttons, as key and label.
+    pub actions: Vec<(String, String)>,
+    /// An alert with the same tag as one still on screen takes its place.
+    pub tag: Option<String>,
+}
+
+/// Shared with the threads that wait for clicks, so they can take alerts down.
+pub trait Notifier: Send + Sync {
+    /// Shows `alert`. `on_action` is called when one of its buttons is clicked.
+    fn notify(&self, alert: &Alert, on_action: OnAction) -> Result<(), Box<dyn Error>>;
+
+    /// Takes down the alert shown with `tag`, if it is still up.
+    fn close(&self, _tag: &str) {}
+}
+
+/// The notifier the configuration asks for.
+pub fn from_config(backend: NotifierBackend) -> Box<dyn Notifier> {
+    match backend {
+        NotifierBackend::Auto => {
+            Box::new(Fallback { first: Box::new(Freedesktop::default()), then: Box::new(NotifySend::default()) })
+        }
+        NotifierBackend::Freedesktop => Box::new(Freedesktop::default()),
+        NotifierBackend::NotifySend => Box::new(NotifySend::default()),
+        NotifierBackend::None => Box::new(Silent),
+    }
+}
+
+/// Talks to the desktop's notification server over D-Bus. The only one with
+/// buttons, and the only one that can take its notifications down again.
+#[derive(Default)]
+pub struct Freedesktop {
+    // The notification on screen for each tag, by the id the server gave it
+    shown: Arc<Mutex<HashMap<String, u32>>>,
+}
+
+impl Notifier for Freedesktop {
+    fn notify(&self, alert: &Alert, on_action: OnAction) -> Result<(), Box<dyn Error>> {
+        // Closed rather than replaced, so its thread stops waiting for a click
+        // that would otherwise be answered twice
+        if let Some(tag) = &alert.tag {
+            self.close(tag);
+        }
+        let mut notification = Notification::new();
+        for (key, label) in &alert.actions {
+            notification.action(key, label);
+        }
+        notification
+            .summary(&alert.title)
+            .body(&alert.body)
+            .icon(&alert.icon)
+            .appname("Rewind")
+            .hint(Hint::Category("reminder".to_string()));
+        if alert.urgent {
+            notification.hint(Hint::Urgency(Urgency::Critical));
+        }
+        if alert.persistent {
+            notification.timeout(Timeout::Never).hint(Hint::Resident(true));
+        } else if let Some(timeout) = alert.timeout {
+            notification.timeout(Timeout::Milliseconds(timeout.as_millis().try_into().unwrap_or(u32::MAX)));
+        }
+
+        let handle = notification.show()?;
+        let id = handle.id();
+        if let Some(tag) = &alert.tag {
+            self.shown.lock().unwrap().insert(tag.clone(), id);
+        }
+        // Waiting for a click blocks, so every notification gets its own thread.
+        // It ends with the first click or when the notification is closed.
+        let (shown, tag) = (self.shown.clone(), alert.tag.clone());
+        thread::spawn(move || {
+            handle.wait_for_action(|key| on_action(key));
+            if let Some(tag) = tag {
+                let mut shown = shown.lock().unwrap();
+                if shown.get(&tag) == Some(&id) {
+                    shown.remove(&tag);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    fn close(&self, tag: &str) {
+        let Some(id) = self.shown.lock().unwrap().remove(tag) else {
+            return;
+        };
+        let closed = zbus::blocking::Connection::session().and_then(|connection| {
+            connection.call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "CloseNotification",
+                &id,
+            )
+        });
+        if let Err(e) = closed {
+            println!("Failed to close notification {}: {}", id, e);
+        }
+    }
+}
+
+/// Runs `notify-send`, for desktops where the D-Bus call fails. Every value
+/// is its own argument, nothing goes through a shell. It has no buttons.
+pub struct NotifySend {
+    program: PathBuf,
+}
+
+impl Default for NotifySend {
+    fn default() -> Self {
+        NotifySend { program: PathBuf::from("notify-send") }
+    }
+}
+
+impl Notifier for NotifySend {
+    fn notify(&self, alert: &Alert, _on_action: OnAction) -> Result<(), Box<dyn Error>> {
+        let mut command = Command::new(&self.program);
+        command
+            .arg("--app-name=Rewind")
+            .arg(format!("--icon={}", alert.icon))
+            .arg(format!("--urgency={}", if alert.urgent { "critical" } else { "normal" }))
+            .arg("--category=reminder");
+        if alert.persistent {
+            command.arg("--expire-time=0");
+        } else if let Some(timeout) = alert.timeout {
+            command.arg(format!("--expire-time={}", timeout.as_millis()));
+        }
+        // A title starting with a dash is still a title
+        let status = command.arg("--").arg(&alert.title).arg(&alert.body).stdin(Stdio::null()).status()?;
+        if !status.success() {
+            return Err(format!("{} failed: {}", self.program.display(), status).into());
+        }
+        Ok(())
+    }
+}
+
+/// Shows nothing, when reminders should only reach webhooks.
+pub struct Silent;
+
+impl Notifier for Silent {
+    fn notify(&self, _alert: &Alert, _on_action: OnAction) -> Result<(), Box<dyn Error>> {
+        Ok(())
+    }
+}
+
+/// Uses `first`, and `then` only when `first` fails.
+pub struct Fallback {
+    pub first: Box<dyn Notifier>,
+    pub then: Box<dyn Notifier>,
+}
+
+impl Notifier for Fallback {
+    fn notify(&self, alert: &Alert, on_action: OnAction) -> Result<(), Box<dyn Error>> {
+        match self.first.notify(alert, on_action.clone()) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                println!("Failed to send notification, trying the fallback: {}", e);
+                self.then.notify(alert, on_action)
+            }
+        }
+    }
+
+    fn close(&self, tag: &str) {
+        self.first.close(tag);
+        self.then.close(tag);
+    }
+}
+
+/// Keeps what it was asked to show instead of showing it, for tests.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct Recording {
+    pub shown: Arc<Mutex<Vec<(Alert, OnAction)>>>,
+    /// The tags of alerts that were taken down.
+    pub closed: Arc<Mutex<Vec<String>>>,
+    /// Whether `notify` fails instead.
+    pub broken: bool,
+}
+
+#[cfg(test)]
+impl Recording {
+    pub fn alerts(&self) -> Vec<Alert> {
+        self.shown.lock().unwrap().iter().map(|(alert, _)| alert.clone()).collect()
+    }
+
+    /// Clicks button `key` on the alert shown `index`th.
+    pub fn click(&self, index: usize, key: &str) {
+        let on_action = self.shown.lock().unwrap()[index].1.clone();
+        on_action(key);
+    }
+}
+
+#[cfg(test)]
+impl Notifier for Recording {
+    fn notify(&self, alert: &Alert, on_action: OnAction) -> Result<(), Box<dyn Error>> {
+        if self.broken {
+            return Err("no notification server".into());
+        }
+        self.shown.lock().unwrap().push((alert.clone(), on_action));
+        Ok(())
+    }
+
+    fn close(&self, tag: &str) {
+        self.closed.lock().unwrap().push(tag.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn alert(title: &str) -> Alert {
+        Alert {
+            title: title.to_string(),
+            body: "Your reminder is due now!".to_string(),
+            icon: "appointment-soon".to_string(),
+            urgent: true,
+            timeout: Some(Duration::from_secs(10)),
+            persistent: false,
+            actions: vec![("done".to_string(), "Mark done".to_string())],
+            tag: None,
+        }
+    }
+
+    #[test]
+    fn notify_send_gets_the_title_as_one_argument() {
+        let dir = ScratchDir::new("notifier");
+        // Stands in for notify-send and writes down each argument on its own line
+        let program = dir.join("notify-send");
+        let arguments = dir.join("arguments");
+        fs::write(&program, format!("#!/bin/sh\nprintf '%s\\n' \"$@\" > '{}'\n", arguments.display())).unwrap();
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let title = "Reminder: \"; touch pwned; \" $(touch pwned)";
+        let notifier = NotifySend { program };
+        notifier.notify(&alert(title), Arc::new(|_| {})).unwrap();
+        let written = fs::read_to_string(&arguments).unwrap();
+        let written: Vec<&str> = written.lines().collect();
+        assert_eq!(written[written.len() - 3..], ["--", title, "Your reminder is due now!"]);
+        assert!(written.contains(&"--urgency=critical"));
+        assert!(!dir.join("pwned").exists());
+        assert!(!std::path::Path::new("pwned").exists());
+
+        let missing = NotifySend { program: dir.join("not-there") };
+        assert!(missing.notify(&alert(title), Arc::new(|_| {})).is_err());
+    }
+
+    #[test]
+    fn the_fallback_is_only_used_when_the_first_fails() {
+        let (first, then) = (Recording::default(), Recording::default());
+        let notifier = Fallback { first: Box::new(first.clone()), then: Box::new(then.clone()) };
+        notifier.notify(&alert("Tea"), Arc::new(|_| {})).unwrap();
+        assert_eq!(first.alerts(), [alert("Tea")]);
+        assert!(then.alerts().is_empty());
+
+        let broken = Recording { broken: true, ..Recording::default() };
+        let notifier = Fallback { first: Box::new(broken), then: Box::new(then.clone()) };
+        notifier.notify(&alert("Laundry"), Arc::new(|_| {})).unwrap();
+        assert_eq!(then.alerts(), [alert("Laundry")]);
+    }
+}
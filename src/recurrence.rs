@@ -0,0 +1,613 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use std::collections::VecDeque;
+use std::fmt;
+
+// Stops runaway expansion of rules that can never produce another occurrence
+const MAX_PERIODS: u32 = 100_000;
+
+const UNTIL_UTC_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+const UNTIL_LOCAL_FORMAT: &str = "%Y%m%dT%H%M%S";
+const UNTIL_DATE_FORMAT: &str = "%Y%m%d";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl Frequency {
+    pub const ALL: [Frequency; 4] = [Frequency::Daily, Frequency::Weekly, Frequency::Monthly, Frequency::Yearly];
+
+    fn as_rrule(self) -> &'static str {
+        match self {
+            Frequency::Daily => "DAILY",
+            Frequency::Weekly => "WEEKLY",
+            Frequency::Monthly => "MONTHLY",
+            Frequency::Yearly => "YEARLY",
+        }
+    }
+
+    fn unit(self, plural: bool) -> &'static str {
+        match (self, plural) {
+            (Frequency::Daily, false) => "day",
+            (Frequency::Daily, true) => "days",
+            (Frequency::Weekly, false) => "week",
+            (Frequency::Weekly, true) => "weeks",
+            (Frequency::Monthly, false) => "month",
+            (Frequency::Monthly, true) => "months",
+            (Frequency::Yearly, false) => "year",
+            (Frequency::Yearly, true) => "years",
+        }
+    }
+}
+
+/// A subset of an RFC 5545 recurrence rule, plus the EXDATE list that goes with it.
+///
+/// Occurrences are expanded in local wall-clock time starting from the
+/// reminder's own time (DTSTART), so a weekly 10:00 standup stays at 10:00
+/// across daylight saving changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// BYDAY without an ordinal, e.g. `MO,WE,FR`.
+    pub weekdays: Vec<Weekday>,
+    /// BYMONTHDAY, negative values count back from the end of the month.
+    pub month_days: Vec<i32>,
+    /// BYDAY with an ordinal for monthly rules, e.g. `2TU` or `-1FR`.
+    pub nth_weekday: Option<(i32, Weekday)>,
+    pub count: Option<u32>,
+    pub until: Option<DateTime<FixedOffset>>,
+    /// EXDATE values, occurrences at these times are skipped.
+    pub exceptions: Vec<DateTime<FixedOffset>>,
+}
+
+impl Recurrence {
+    pub fn new(frequency: Frequency) -> Self {
+        Recurrence {
+            frequency,
+            interval: 1,
+            weekdays: Vec::new(),
+            month_days: Vec::new(),
+            nth_weekday: None,
+            count: None,
+            until: None,
+            exceptions: Vec::new(),
+        }
+    }
+
+    /// Parses the value of an RRULE property, e.g. `FREQ=WEEKLY;BYDAY=MO,WE`.
+    pub fn parse(rrule: &str) -> Result<Self, String> {
+        let mut frequency = None;
+        let mut rule = Recurrence::new(Frequency::Daily);
+
+        for part in rrule.trim().trim_start_matches("RRULE:").split(';').filter(|p| !p.is_empty()) {
+            let (key, value) = part
+                .split_once('=')
+                .ok_or_else(|| format!("malformed rule part '{}'", part))?;
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    frequency = Some(match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        "YEARLY" => Frequency::Yearly,
+                        other => return Err(format!("unsupported frequency '{}'", other)),
+                    })
+                }
+                "INTERVAL" => {
+                    rule.interval = value
+                        .parse()
+                        .ok()
+                        .filter(|interval| *interval > 0)
+                        .ok_or_else(|| format!("invalid interval '{}'", value))?
+                }
+                "COUNT" => {
+                    rule.count = Some(value
+                        .parse()
+                        .ok()
+                        .filter(|count| *count > 0)
+                        .ok_or_else(|| format!("invalid count '{}'", value))?)
+                }
+                "UNTIL" => rule.until = Some(parse_until(value)?),
+                "BYDAY" => {
+                    for day in value.split(',') {
+                        let (ordinal, weekday) = parse_byday(day)?;
+                        match ordinal {
+                            Some(n) if rule.nth_weekday.is_none() => rule.nth_weekday = Some((n, weekday)),
+                            Some(_) => return Err("only one numbered BYDAY value is supported".to_string()),
+                            None => rule.weekdays.push(weekday),
+                        }
+                    }
+                }
+                "BYMONTHDAY" => {
+                    for day in value.split(',') {
+                        let day: i32 = day
+                            .parse()
+                            .ok()
+                            .filter(|d: &i32| *d != 0 && d.abs() <= 31)
+                            .ok_or_else(|| format!("invalid month day '{}'", day))?;
+                        rule.month_days.push(day);
+                    }
+                }
+                // Weeks always start on Monday here, which is also the RFC default
+                "WKST" if value.eq_ignore_ascii_case("MO") => {}
+                other => return Err(format!("unsupported rule part '{}'", other)),
+            }
+        }
+
+        rule.frequency = frequency.ok_or("missing FREQ")?;
+        if rule.count.is_some() && rule.until.is_some() {
+            return Err("COUNT and UNTIL cannot be combined".to_string());
+        }
+        if rule.nth_weekday.is_some() && (rule.frequency != Frequency::Monthly || !rule.weekdays.is_empty()) {
+            return Err("numbered BYDAY values are only supported on their own in monthly rules".to_string());
+        }
+        // The expander only filters daily rules by weekday and month day, picks weekdays in weekly
+        // rules and month days in monthly ones; anything else would be silently ignored
+        if !rule.weekdays.is_empty() && matches!(rule.frequency, Frequency::Monthly | Frequency::Yearly) {
+            return Err("BYDAY without a number is unsupported in monthly and yearly rules".to_string());
+        }
+        if !rule.month_days.is_empty() && (rule.nth_weekday.is_some() || matches!(rule.frequency, Frequency::Weekly | Frequency::Yearly)) {
+            return Err("BYMONTHDAY is unsupported in weekly and yearly rules and next to a numbered BYDAY".to_string());
+        }
+        Ok(rule)
+    }
+
+    /// Formats the rule as an RRULE value, without the EXDATE list.
+    pub fn to_rrule(&self) -> String {
+        let mut parts = vec![format!("FREQ={}", self.frequency.as_rrule())];
+        if self.interval != 1 {
+            parts.push(format!("INTERVAL={}", self.interval));
+        }
+        if let Some((n, weekday)) = self.nth_weekday {
+            parts.push(format!("BYDAY={}{}", n, weekday_code(weekday)));
+        } else if !self.weekdays.is_empty() {
+            let days: Vec<&str> = self.weekdays.iter().map(|d| weekday_code(*d)).collect();
+            parts.push(format!("BYDAY={}", days.join(",")));
+        }
+        if !self.month_days.is_empty() {
+            let days: Vec<String> = self.month_days.iter().map(|d| d.to_string()).collect();
+            parts.push(format!("BYMONTHDAY={}", days.join(",")));
+        }
+        if let Some(count) = self.count {
+            parts.push(format!("COUNT={}", count));
+        }
+        if let Some(until) = self.until {
+            parts.push(format!("UNTIL={}", until.with_timezone(&Utc).format(UNTIL_UTC_FORMAT)));
+        }
+        parts.join(";")
+    }
+
+    /// Every occurrence of the rule for a series starting at `start`, in order.
+    /// `start` itself is always the first occurrence unless it is an exception.
+    pub fn occurrences(&self, start: DateTime<FixedOffset>) -> Occurrences<'_> {
+        Occurrences {
+            rule: self,
+            start,
+            local_start: start.with_timezone(&Local).naive_local(),
+            period: 0,
+            pending: VecDeque::from([start]),
+            emitted: 0,
+            finished: false,
+        }
+    }
+
+    /// Candidate local dates for the `period`-th step of the rule, or `None`
+    /// once that step lies past the end of the calendar.
+    fn dates_in_period(&self, first: NaiveDate, period: u32) -> Option<Vec<NaiveDate>> {
+        let step = period.checked_mul(self.interval)?;
+        match self.frequency {
+            Frequency::Daily => {
+                let date = first.checked_add_days(chrono::Days::new(step as u64))?;
+                let weekday_ok = self.weekdays.is_empty() || self.weekdays.contains(&date.weekday());
+                let day_ok = self.month_days.is_empty()
+                    || self.month_days.iter().any(|d| resolve_month_day(date.year(), date.month(), *d) == Some(date.day()));
+                Some(if weekday_ok && day_ok { vec![date] } else { Vec::new() })
+            }
+            Frequency::Weekly => {
+                let monday = first - Duration::days(first.weekday().num_days_from_monday() as i64);
+                let week = monday.checked_add_days(chrono::Days::new(step as u64 * 7))?;
+                let weekdays = if self.weekdays.is_empty() { vec![first.weekday()] } else { self.weekdays.clone() };
+                weekdays
+                    .iter()
+                    .map(|d| week.checked_add_days(chrono::Days::new(d.num_days_from_monday() as u64)))
+                    .collect()
+            }
+            Frequency::Monthly => {
+                let months = (first.year() * 12 + first.month0() as i32).checked_add(i32::try_from(step).ok()?)?;
+                let (year, month) = (months.div_euclid(12), months.rem_euclid(12) as u32 + 1);
+                // Checks the year is on the calendar before looking inside the month
+                NaiveDate::from_ymd_opt(year, month, 1)?;
+                if let Some((n, weekday)) = self.nth_weekday {
+                    return Some(nth_weekday_of_month(year, month, n, weekday).into_iter().collect());
+                }
+                let days = if self.month_days.is_empty() { vec![first.day() as i32] } else { self.month_days.clone() };
+                Some(days.iter()
+                    .filter_map(|d| resolve_month_day(year, month, *d))
+                    .filter_map(|d| NaiveDate::from_ymd_opt(year, month, d))
+                    .collect())
+            }
+            Frequency::Yearly => {
+                let year = first.year().checked_add(i32::try_from(step).ok()?)?;
+                NaiveDate::from_ymd_opt(year, 1, 1)?;
+                Some(NaiveDate::from_ymd_opt(year, first.month(), first.day()).into_iter().collect())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.interval == 1 {
+            write!(f, "Every {}", self.frequency.unit(false))?;
+        } else {
+            write!(f, "Every {} {}", self.interval, self.frequency.unit(true))?;
+        }
+        if let Some((n, weekday)) = self.nth_weekday {
+            write!(f, " on the {} {}", ordinal_name(n), weekday_name(weekday))?;
+        } else if !self.weekdays.is_empty() {
+            let days: Vec<&str> = self.weekdays.iter().map(|d| weekday_name(*d)).collect();
+            write!(f, " on {}", days.join(", "))?;
+        }
+        if !self.month_days.is_empty() {
+            let days: Vec<String> = self.month_days.iter().map(|d| d.to_string()).collect();
+            write!(f, " on day {}", days.join(", "))?;
+        }
+        if let Some(count) = self.count {
+            write!(f, ", {} times", count)?;
+        }
+        if let Some(until) = self.until {
+            write!(f, ", until {}", until.with_timezone(&Local).format("%B %e, %Y"))?;
+        }
+        if !self.exceptions.is_empty() {
+            write!(f, " (skipping {})", self.exceptions.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// Iterator over the occurrences of a [`Recurrence`].
+pub struct Occurrences<'a> {
+    rule: &'a Recurrence,
+    start: DateTime<FixedOffset>,
+    local_start: NaiveDateTime,
+    period: u32,
+    pending: VecDeque<DateTime<FixedOffset>>,
+    // Excluded dates still count towards COUNT, as in RFC 5545
+    emitted: u32,
+    finished: bool,
+}
+
+impl Iterator for Occurrences<'_> {
+    type Item = DateTime<FixedOffset>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.finished {
+                return None;
+            }
+            if let Some(candidate) = self.pending.pop_front() {
+                if self.rule.until.is_some_and(|until| candidate > until)
+                    || self.rule.count.is_some_and(|count| self.emitted >= count)
+                {
+                    self.finished = true;
+                    return None;
+                }
+                self.emitted += 1;
+                if self.rule.exceptions.contains(&candidate) {
+                    continue;
+                }
+                return Some(candidate);
+            }
+
+            if self.period >= MAX_PERIODS {
+                self.finished = true;
+                return None;
+            }
+            let Some(dates) = self.rule.dates_in_period(self.local_start.date(), self.period) else {
+                self.finished = true;
+                return None;
+            };
+            let mut candidates: Vec<DateTime<FixedOffset>> = dates
+                .into_iter()
+                .filter_map(|date| resolve_local(date, self.local_start.time()))
+                .filter(|time| *time > self.start)
+                .collect();
+            candidates.sort();
+            candidates.dedup();
+            self.pending.extend(candidates);
+            self.period += 1;
+        }
+    }
+}
+
+/// Converts a local wall-clock time to an instant. Times inside a DST gap are
+/// moved forward by the length of a typical gap, which matches RFC 5545's rule
+/// of using the offset from before the gap.
+fn resolve_local(date: NaiveDate, time: NaiveTime) -> Option<DateTime<FixedOffset>> {
+    let naive = NaiveDateTime::new(date, time);
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .or_else(|| Local.from_local_datetime(&(naive + Duration::hours(1))).earliest())
+        .map(|local| local.fixed_offset())
+}
+
+pub fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|d| d.pred_opt())
+        .map(|d| d.day())
+        .unwrap_or(28)
+}
+
+/// Turns a BYMONTHDAY value into a day of the given month, if that month has it.
+fn resolve_month_day(year: i32, month: u32, day: i32) -> Option<u32> {
+    let length = days_in_month(year, month) as i32;
+    let resolved = if day < 0 { length + day + 1 } else { day };
+    (1..=length).contains(&resolved).then_some(resolved as u32)
+}
+
+/// The `n`-th given weekday of a month, counting from the end when `n` is negative.
+fn nth_weekday_of_month(year: i32, month: u32, n: i32, weekday: Weekday) -> Option<NaiveDate> {
+    if n > 0 {
+        NaiveDate::from_weekday_of_month_opt(year, month, weekday, n as u8)
+    } else if n < 0 {
+        let last = NaiveDate::from_ymd_opt(year, month, days_in_month(year, month))?;
+        let back = (last.weekday().num_days_from_monday() + 7 - weekday.num_days_from_monday()) % 7;
+        let date = last - Duration::days((back + 7 * (n.unsigned_abs() - 1)) as i64);
+        (date.month() == month).then_some(date)
+    } else {
+        None
+    }
+}
+
+pub fn parse_until(value: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(naive) = NaiveDateTime::parse_from_str(value, UNTIL_UTC_FORMAT) {
+        return Ok(Utc.from_utc_datetime(&naive).fixed_offset());
+    }
+    let naive = NaiveDateTime::parse_from_str(value, UNTIL_LOCAL_FORMAT)
+        .or_else(|_| {
+            // A plain date includes the whole day
+            NaiveDate::parse_from_str(value, UNTIL_DATE_FORMAT)
+                .map(|date| date.and_hms_opt(23, 59, 59).unwrap_or_default())
+        })
+        .map_err(|_| format!("invalid UNTIL '{}'", value))?;
+    resolve_local(naive.date(), naive.time()).ok_or_else(|| format!("invalid UNTIL '{}'", value))
+}
+
+pub fn parse_byday(value: &str) -> Result<(Option<i32>, Weekday), String> {
+    let value = value.trim();
+    if value.len() < 2 || !value.is_char_boundary(value.len() - 2) {
+        return Err(format!("invalid weekday '{}'", value));
+    }
+    let (ordinal, code) = value.split_at(value.len() - 2);
+    let weekday = parse_weekday_code(code).ok_or_else(|| format!("invalid weekday '{}'", value))?;
+    if ordinal.is_empty() {
+        return Ok((None, weekday));
+    }
+    let n: i32 = ordinal
+        .trim_start_matches('+')
+        .parse()
+        .ok()
+        .filter(|n: &i32| *n != 0 && n.abs() <= 5)
+        .ok_or_else(|| format!("invalid weekday '{}'", value))?;
+    Ok((Some(n), weekday))
+}
+
+fn parse_weekday_code(code: &str) -> Option<Weekday> {
+    match code.to_ascii_uppercase().as_str() {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn weekday_code(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    match weekday {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+fn ordinal_name(n: i32) -> String {
+    match n {
+        1 => "first".to_string(),
+        2 => "second".to_string(),
+        3 => "third".to_string(),
+        4 => "fourth".to_string(),
+        5 => "fifth".to_string(),
+        -1 => "last".to_string(),
+        -2 => "second to last".to_string(),
+        n => format!("{}th", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reminder::Reminder;
+
+    /// A local wall-clock time, the way the rules expand.
+    fn local(text: &str) -> DateTime<FixedOffset> {
+        let naive = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M").unwrap();
+        resolve_local(naive.date(), naive.time()).unwrap()
+    }
+
+    fn local_text(time: DateTime<FixedOffset>) -> String {
+        time.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    /// The first `n` occurrences of `rrule` for a series starting at `start`.
+    fn expand(rrule: &str, start: &str, n: usize) -> Vec<String> {
+        let rule = Recurrence::parse(rrule).unwrap_or_else(|e| panic!("{}: {}", rrule, e));
+        rule.occurrences(local(start)).take(n).map(local_text).collect()
+    }
+
+    #[test]
+    fn frequency_and_interval_step_through_local_time() {
+        assert_eq!(expand("FREQ=DAILY;INTERVAL=2", "2026-01-30 10:00", 3), ["2026-01-30 10:00", "2026-02-01 10:00", "2026-02-03 10:00"]);
+        assert_eq!(expand("FREQ=WEEKLY", "2026-10-14 09:30", 3), ["2026-10-14 09:30", "2026-10-21 09:30", "2026-10-28 09:30"]);
+        assert_eq!(expand("FREQ=MONTHLY;INTERVAL=3", "2026-11-15 08:00", 3), ["2026-11-15 08:00", "2027-02-15 08:00", "2027-05-15 08:00"]);
+        // Across the change back from summer time, if the local zone has one
+        assert_eq!(expand("FREQ=WEEKLY", "2026-10-22 10:00", 2), ["2026-10-22 10:00", "2026-10-29 10:00"]);
+    }
+
+    #[test]
+    fn byday_picks_weekdays_and_numbered_weekdays() {
+        // A Wednesday
+        assert_eq!(
+            expand("FREQ=WEEKLY;BYDAY=MO,WE,FR", "2026-10-14 10:00", 4),
+            ["2026-10-14 10:00", "2026-10-16 10:00", "2026-10-19 10:00", "2026-10-21 10:00"]
+        );
+        // The start always comes first, even on a day the rule leaves out
+        assert_eq!(expand("FREQ=WEEKLY;BYDAY=FR", "2026-10-13 10:00", 2), ["2026-10-13 10:00", "2026-10-16 10:00"]);
+        assert_eq!(expand("FREQ=DAILY;BYDAY=SA,SU", "2026-10-16 10:00", 3), ["2026-10-16 10:00", "2026-10-17 10:00", "2026-10-18 10:00"]);
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=-1FR", "2026-10-30 17:00", 3),
+            ["2026-10-30 17:00", "2026-11-27 17:00", "2026-12-25 17:00"]
+        );
+        assert_eq!(expand("FREQ=MONTHLY;BYDAY=2TU", "2026-10-13 12:00", 2), ["2026-10-13 12:00", "2026-11-10 12:00"]);
+        // Not every month has a fifth Friday
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYDAY=5FR", "2026-10-30 17:00", 3),
+            ["2026-10-30 17:00", "2027-01-29 17:00", "2027-04-30 17:00"]
+        );
+    }
+
+    #[test]
+    fn month_days_skip_months_that_do_not_have_them() {
+        let thirty_first = ["2026-01-31 09:00", "2026-03-31 09:00", "2026-05-31 09:00", "2026-07-31 09:00"];
+        assert_eq!(expand("FREQ=MONTHLY;BYMONTHDAY=31", "2026-01-31 09:00", 4), thirty_first);
+        assert_eq!(expand("FREQ=MONTHLY", "2026-01-31 09:00", 4), thirty_first);
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYMONTHDAY=-1", "2026-01-31 09:00", 4),
+            ["2026-01-31 09:00", "2026-02-28 09:00", "2026-03-31 09:00", "2026-04-30 09:00"]
+        );
+        assert_eq!(
+            expand("FREQ=MONTHLY;BYMONTHDAY=1,15", "2026-10-01 09:00", 4),
+            ["2026-10-01 09:00", "2026-10-15 09:00", "2026-11-01 09:00", "2026-11-15 09:00"]
+        );
+        assert_eq!(expand("FREQ=DAILY;BYMONTHDAY=-1", "2024-02-27 09:00", 3), ["2024-02-27 09:00", "2024-02-29 09:00", "2024-03-31 09:00"]);
+    }
+
+    #[test]
+    fn leap_days_only_come_in_leap_years() {
+        assert_eq!(expand("FREQ=YEARLY", "2024-02-29 08:00", 3), ["2024-02-29 08:00", "2028-02-29 08:00", "2032-02-29 08:00"]);
+        assert_eq!(expand("FREQ=MONTHLY;BYMONTHDAY=29", "2027-01-29 08:00", 3), ["2027-01-29 08:00", "2027-03-29 08:00", "2027-04-29 08:00"]);
+    }
+
+    #[test]
+    fn count_and_until_end_the_series() {
+        assert_eq!(expand("FREQ=DAILY;COUNT=3", "2026-10-14 10:00", 10).len(), 3);
+        // UNTIL is inclusive
+        assert_eq!(
+            expand("FREQ=WEEKLY;UNTIL=20261028T100000", "2026-10-14 10:00", 10),
+            ["2026-10-14 10:00", "2026-10-21 10:00", "2026-10-28 10:00"]
+        );
+        assert_eq!(expand("FREQ=WEEKLY;UNTIL=20261027", "2026-10-14 10:00", 10).len(), 2);
+        assert!(Recurrence::parse("FREQ=DAILY;COUNT=3;UNTIL=20261028").is_err());
+
+        // Rules made in code can have both, whichever ends first wins
+        let mut rule = Recurrence::parse("FREQ=DAILY;COUNT=2").unwrap();
+        rule.until = Some(local("2026-12-31 00:00"));
+        let mut reminder = Reminder::new("Both".to_string(), local("2026-10-14 10:00"));
+        reminder.recurrence = Some(rule.clone());
+        assert_eq!(rule.occurrences(reminder.time).count(), 2);
+        assert_eq!(reminder.last_occurrence(), Some(local("2026-10-15 10:00")));
+        rule.count = Some(1000);
+        reminder.recurrence = Some(rule);
+        assert_eq!(reminder.last_occurrence(), Some(local("2026-12-30 10:00")));
+    }
+
+    #[test]
+    fn exceptions_are_skipped_but_count_towards_count() {
+        let mut rule = Recurrence::parse("FREQ=DAILY;COUNT=4").unwrap();
+        rule.exceptions = vec![local("2026-10-15 10:00"), local("2026-10-14 10:00")];
+        let times: Vec<String> = rule.occurrences(local("2026-10-14 10:00")).map(local_text).collect();
+        assert_eq!(times, ["2026-10-16 10:00", "2026-10-17 10:00"]);
+    }
+
+    #[test]
+    fn last_occurrence_follows_the_end_of_the_rule() {
+        let mut reminder = Reminder::new("Stand-up".to_string(), local("2026-10-14 10:00"));
+        assert_eq!(reminder.last_occurrence(), Some(reminder.time));
+        reminder.recurrence = Some(Recurrence::parse("FREQ=WEEKLY").unwrap());
+        assert_eq!(reminder.last_occurrence(), None, "repeats forever");
+        reminder.recurrence = Some(Recurrence::parse("FREQ=WEEKLY;COUNT=3").unwrap());
+        assert_eq!(reminder.last_occurrence(), Some(local("2026-10-28 10:00")));
+        reminder.recurrence = Some(Recurrence::parse("FREQ=WEEKLY;UNTIL=20261020").unwrap());
+        assert_eq!(reminder.last_occurrence(), Some(parse_until("20261020").unwrap()));
+    }
+
+    #[test]
+    fn rules_that_never_match_again_stop() {
+        // Every February, but only on the 31st
+        let times = expand("FREQ=MONTHLY;INTERVAL=12;BYMONTHDAY=31", "2026-02-10 10:00", 5);
+        assert_eq!(times, ["2026-02-10 10:00"]);
+    }
+
+    #[test]
+    fn intervals_past_the_calendar_end_the_series() {
+        for rrule in ["FREQ=DAILY;INTERVAL=4294967295", "FREQ=WEEKLY;INTERVAL=4294967295", "FREQ=MONTHLY;INTERVAL=2147483647",
+            "FREQ=MONTHLY;INTERVAL=4294967295", "FREQ=YEARLY;INTERVAL=2147483647", "FREQ=YEARLY;INTERVAL=4294967295"]
+        {
+            assert_eq!(expand(rrule, "2026-03-01 09:00", 3), ["2026-03-01 09:00"], "{}", rrule);
+        }
+        assert_eq!(expand("FREQ=YEARLY;INTERVAL=1000", "2026-03-01 09:00", 3), ["2026-03-01 09:00", "3026-03-01 09:00", "4026-03-01 09:00"]);
+    }
+
+    #[test]
+    fn rules_parse_and_format_back() {
+        for rrule in ["FREQ=DAILY", "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR;COUNT=10", "FREQ=MONTHLY;BYDAY=-1FR", "FREQ=MONTHLY;BYMONTHDAY=1,-1"] {
+            assert_eq!(Recurrence::parse(rrule).unwrap().to_rrule(), rrule);
+        }
+        let rule = Recurrence::parse("RRULE:freq=yearly;until=20301231T235959Z;wkst=MO").unwrap();
+        assert_eq!(rule.to_rrule(), "FREQ=YEARLY;UNTIL=20301231T235959Z");
+        assert_eq!(Recurrence::parse("FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR").unwrap().to_string(), "Every 2 weeks on Monday, Friday");
+
+        for broken in ["", "BYDAY=MO", "FREQ=HOURLY", "FREQ=DAILY;INTERVAL=0", "FREQ=DAILY;COUNT=0", "FREQ=MONTHLY;BYMONTHDAY=32",
+            "FREQ=WEEKLY;BYDAY=2MO", "FREQ=MONTHLY;BYDAY=1MO,2TU", "FREQ=MONTHLY;BYDAY=6MO", "FREQ=DAILY;BYSETPOS=1", "FREQ"]
+        {
+            assert!(Recurrence::parse(broken).is_err(), "{}", broken);
+        }
+    }
+
+    #[test]
+    fn rule_parts_the_expander_ignores_are_rejected() {
+        for unsupported in ["FREQ=MONTHLY;BYDAY=MO", "FREQ=YEARLY;BYDAY=MO,TU", "FREQ=WEEKLY;BYMONTHDAY=3",
+            "FREQ=YEARLY;BYMONTHDAY=1", "FREQ=MONTHLY;BYDAY=1MO;BYMONTHDAY=1"]
+        {
+            let error = Recurrence::parse(unsupported).unwrap_err();
+            assert!(error.contains("unsupported"), "{}: {}", unsupported, error);
+        }
+        for supported in ["FREQ=DAILY;BYDAY=MO;BYMONTHDAY=13", "FREQ=WEEKLY;BYDAY=SA,SU", "FREQ=MONTHLY;BYMONTHDAY=31"] {
+            assert!(Recurrence::parse(supported).is_ok(), "{}", supported);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+use chrono::{Datelike, Local, NaiveDate, NaiveDateTime, TimeZone, Weekday};
+use gtk::prelude::{BoxExt, Cast, CheckButtonExt, EditableExt, EntryExt, WidgetExt};
+use relm4::gtk;
+
+use crate::recurrence::{self, Frequency, Recurrence};
+use crate::widgets::labelled_row;
+
+const WEEKDAYS: [Weekday; 7] = [
+    Weekday::Mon,
+    Weekday::Tue,
+    Weekday::Wed,
+    Weekday::Thu,
+    Weekday::Fri,
+    Weekday::Sat,
+    Weekday::Sun,
+];
+
+// Positions in the drop-downs below
+const REPEAT_NEVER: u32 = 0;
+const MONTHLY_BY_DAY: u32 = 0;
+const MONTHLY_BY_WEEKDAY: u32 = 1;
+const MONTHLY_BY_LAST_WEEKDAY: u32 = 2;
+const END_AFTER_COUNT: u32 = 1;
+const END_ON_DATE: u32 = 2;
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+/// The "Repeat" part of the reminder dialog.
+pub struct RecurrenceSection {
+    pub root: gtk::Box,
+    frequency: gtk::DropDown,
+    interval: gtk::SpinButton,
+    weekdays: Vec<gtk::CheckButton>,
+    monthly_mode: gtk::DropDown,
+    end_mode: gtk::DropDown,
+    count: gtk::SpinButton,
+    until: gtk::Entry,
+    exceptions: gtk::Entry,
+}
+
+impl RecurrenceSection {
+    pub fn new() -> Self {
+        let root = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(5)
+            .build();
+
+        let frequency = gtk::DropDown::from_strings(&["Does not repeat", "Daily", "Weekly", "Monthly", "Yearly"]);
+        let interval = gtk::SpinButton::with_range(1.0, 99.0, 1.0);
+        let interval_row = labelled_row("Every", &interval, "");
+
+        let weekday_row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+        let weekdays: Vec<gtk::CheckButton> = WEEKDAYS
+            .iter()
+            .map(|day| {
+                let check = gtk::CheckButton::with_label(&day.to_string());
+                weekday_row.append(&check);
+                check
+            })
+            .collect();
+
+        let monthly_mode = gtk::DropDown::from_strings(&[
+            "On the same day of the month",
+            "On the same weekday (e.g. 2nd Tuesday)",
+            "On the same weekday from the end (e.g. last Friday)",
+        ]);
+
+        let end_mode = gtk::DropDown::from_strings(&["Forever", "After a number of times", "Until a date"]);
+        let count = gtk::SpinButton::with_range(1.0, 999.0, 1.0);
+        count.set_value(10.0);
+        let until = gtk::Entry::new();
+        until.set_placeholder_text(Some("Last date (YYYY-MM-DD)"));
+        let exceptions = gtk::Entry::new();
+        exceptions.set_placeholder_text(Some("Skip on (YYYY-MM-DD, comma separated)"));
+
+        root.append(&frequency);
+        root.append(&interval_row);
+        root.append(&weekday_row);
+        root.append(&monthly_mode);
+        root.append(&end_mode);
+        root.append(&count);
+        root.append(&until);
+        root.append(&exceptions);
+
+        let section = RecurrenceSection {
+            root,
+            frequency,
+            interval,
+            weekdays,
+            monthly_mode,
+            end_mode,
+            count,
+            until,
+            exceptions,
+        };
+
+        // Only show the options that apply to the chosen frequency and end
+        let details: [gtk::Widget; 3] = [
+            interval_row.upcast(),
+            section.end_mode.clone().upcast(),
+            section.exceptions.clone().upcast(),
+        ];
+        let update_visibility = {
+            let frequency = section.frequency.clone();
+            let monthly_mode = section.monthly_mode.clone();
+            let end_mode = section.end_mode.clone();
+            let count = section.count.clone();
+            let until = section.until.clone();
+            move || {
+                let selected = frequency.selected();
+                let repeats = selected != REPEAT_NEVER;
+                for widget in &details {
+                    widget.set_visible(repeats);
+                }
+                weekday_row.set_visible(frequency_at(selected) == Some(Frequency::Weekly));
+                monthly_mode.set_visible(frequency_at(selected) == Some(Frequency::Monthly));
+                count.set_visible(repeats && end_mode.selected() == END_AFTER_COUNT);
+                until.set_visible(repeats && end_mode.selected() == END_ON_DATE);
+            }
+        };
+        update_visibility();
+        let update_visibility = std::rc::Rc::new(update_visibility);
+        section.frequency.connect_selected_notify({
+            let update_visibility = update_visibility.clone();
+            move |_| update_visibility()
+        });
+        section.end_mode.connect_selected_notify(move |_| update_visibility());
+
+        section
+    }
+
+    /// Shows an existing rule in the widgets.
+    pub fn fill(&self, rule: &Recurrence) {
+        let position = Frequency::ALL.iter().position(|f| *f == rule.frequency).unwrap_or(0);
+        self.frequency.set_selected(position as u32 + 1);
+        self.interval.set_value(rule.interval as f64);
+        for (check, day) in self.weekdays.iter().zip(WEEKDAYS) {
+            check.set_active(rule.weekdays.contains(&day));
+        }
+        self.monthly_mode.set_selected(match rule.nth_weekday {
+            Some((n, _)) if n < 0 => MONTHLY_BY_LAST_WEEKDAY,
+            Some(_) => MONTHLY_BY_WEEKDAY,
+            None => MONTHLY_BY_DAY,
+        });
+        if let Some(count) = rule.count {
+            self.end_mode.set_selected(END_AFTER_COUNT);
+            self.count.set_value(count as f64);
+        } else if let Some(until) = rule.until {
+            self.end_mode.set_selected(END_ON_DATE);
+            self.until.set_text(&until.with_timezone(&Local).format(DATE_FORMAT).to_string());
+        }
+        let skipped: Vec<String> = rule
+            .exceptions
+            .iter()
+            .map(|time| time.with_timezone(&Local).format(DATE_FORMAT).to_string())
+            .collect();
+        self.exceptions.set_text(&skipped.join(", "));
+    }
+
+    /// Builds the rule described by the widgets for a series starting at `start`.
+    pub fn read(&self, start: NaiveDateTime) -> Result<Option<Recurrence>, String> {
+        let Some(frequency) = frequency_at(self.frequency.selected()) else {
+            return Ok(None);
+        };
+
+        let mut rule = Recurrence::new(frequency);
+        rule.interval = self.interval.value_as_int().max(1) as u32;
+        match frequency {
+            Frequency::Weekly => {
+                rule.weekdays = self
+                    .weekdays
+                    .iter()
+                    .zip(WEEKDAYS)
+                    .filter(|(check, _)| check.is_active())
+                    .map(|(_, day)| day)
+                    .collect();
+            }
+            Frequency::Monthly => {
+                let date = start.date();
+                match self.monthly_mode.selected() {
+                    MONTHLY_BY_WEEKDAY => rule.nth_weekday = Some((date.day0() as i32 / 7 + 1, date.weekday())),
+                    MONTHLY_BY_LAST_WEEKDAY => {
+                        let days_left = recurrence::days_in_month(date.year(), date.month()) - date.day();
+                        rule.nth_weekday = Some((-(days_left as i32 / 7 + 1), date.weekday()));
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+
+        match self.end_mode.selected() {
+            END_AFTER_COUNT => rule.count = Some(self.count.value_as_int().max(1) as u32),
+            END_ON_DATE => {
+                let date = parse_date(&self.until.text())?;
+                rule.until = Local
+                    .from_local_datetime(&date.and_hms_opt(23, 59, 59).unwrap_or_default())
+                    .earliest()
+                    .map(|time| time.fixed_offset());
+            }
+            _ => {}
+        }
+
+        for text in self.exceptions.text().split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            let date = parse_date(text)?;
+            let skipped = Local
+                .from_local_datetime(&date.and_time(start.time()))
+                .earliest()
+                .ok_or_else(|| format!("{} has no {} in your time zone", text, start.time()))?;
+            rule.exceptions.push(skipped.fixed_offset());
+        }
+
+        Ok(Some(rule))
+    }
+}
+
+fn frequency_at(position: u32) -> Option<Frequency> {
+    position.checked_sub(1).and_then(|index| Frequency::ALL.get(index as usize).copied())
+}
+
+fn parse_date(text: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(text.trim(), DATE_FORMAT)
+        .map_err(|_| format!("'{}' is not a date like 2025-06-24", text.trim()))
+}
@@ -0,0 +1,333 @@
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, SecondsFormat, TimeZone};
+use uuid::Uuid;
+
+use crate::alarm::Alarm;
+use crate::hook::Hook;
+use crate::recurrence::Recurrence;
+use crate::xml_tree::Element;
+
+// Old files stored the local wall-clock time without any offset
+const LEGACY_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    /// Stable identity, persisted so it survives edits, reordering and restarts.
+    pub id: String,
+    pub name: String,
+    /// Due time, or the first occurrence for recurring reminders.
+    pub time: DateTime<FixedOffset>,
+    pub recurrence: Option<Recurrence>,
+    /// Free-form labels, passed on to webhooks.
+    pub tags: Vec<String>,
+    /// Whether it is also sent by email when it fires.
+    pub email: bool,
+    /// Set by snoozing, the reminder alerts again at this time instead of
+    /// on the occurrences it postponed.
+    pub snoozed_until: Option<DateTime<FixedOffset>>,
+    /// How many times in a row it has been snoozed.
+    pub snooze_count: u32,
+    /// When an alert was last answered by marking it done or snoozing,
+    /// which stops an alarm from repeating it.
+    pub acknowledged_at: Option<DateTime<FixedOffset>>,
+    /// When it was marked done for good. Completed reminders never come up
+    /// again and are only kept for the history.
+    pub completed_at: Option<DateTime<FixedOffset>>,
+    /// A command to run whenever it fires.
+    pub hook: Option<Hook>,
+    /// Makes it ring and repeat until it is answered.
+    pub alarm: Option<Alarm>,
+    /// Elements from newer versions of the file format, written back unchanged.
+    pub extra: Vec<Element>,
+}
+
+impl Reminder {
+    pub fn new(name: String, time: DateTime<FixedOffset>) -> Self {
+        Reminder {
+            id: new_id(),
+            name,
+            time,
+            recurrence: None,
+            tags: Vec::new(),
+            email: false,
+            snoozed_until: None,
+            snooze_count: 0,
+            acknowledged_at: None,
+            completed_at: None,
+            hook: None,
+            alarm: None,
+            extra: Vec::new(),
+        }
+    }
+
+    /// The first occurrence at or after `from`, if the reminder has one left.
+    pub fn next_occurrence(&self, from: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        if self.completed_at.is_some() {
+            return None;
+        }
+        match &self.recurrence {
+            Some(rule) => rule.occurrences(self.time).find(|time| *time >= from),
+            None => (self.time >= from).then_some(self.time),
+        }
+    }
+
+    /// The last occurrence, or `None` for reminders that repeat forever.
+    pub fn last_occurrence(&self) -> Option<DateTime<FixedOffset>> {
+        match &self.recurrence {
+            None => Some(self.time),
+            // COUNT can run out before UNTIL does
+            Some(rule) => match (rule.count, rule.until) {
+                (Some(_), _) => Some(rule.occurrences(self.time).last().unwrap_or(self.time)),
+                (None, Some(until)) => Some(until.max(self.time)),
+                (None, None) => None,
+            },
+        }
+    }
+
+    /// When the reminder next alerts at or after `from`. A pending snooze
+    /// comes first and swallows the occurrences it postponed. Alarms repeat
+    /// the last alert until it is answered.
+    pub fn next_alert(&self, from: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        let next = match self.snoozed_until {
+            Some(until) if until >= from => return Some(until),
+            _ => self.next_occurrence(from),
+        };
+        match (next, self.next_repeat(from)) {
+            (Some(next), Some(repeat)) => Some(next.min(repeat)),
+            (next, repeat) => next.or(repeat),
+        }
+    }
+
+    /// The last time it alerts, or `None` for reminders that repeat forever
+    /// or whose alarm would outlast the calendar.
+    pub fn last_alert(&self) -> Option<DateTime<FixedOffset>> {
+        let last = self.last_occurrence()?;
+        let last = self.snoozed_until.map_or(last, |until| until.max(last));
+        let repeating = self
+            .alarm
+            .as_ref()
+            .map_or(0, |alarm| i64::from(alarm.every_minutes).saturating_mul(alarm.repeats.into()));
+        last.checked_add_signed(chrono::Duration::try_minutes(repeating)?)
+    }
+
+    /// The alert that an unanswered alarm repeats at `time`, and which repeat
+    /// it is, counting from 1. `None` when `time` is a regular alert.
+    pub fn alarm_repeat(&self, time: DateTime<FixedOffset>) -> Option<(DateTime<FixedOffset>, u32)> {
+        let (alert, every, repeats) = self.unanswered_alarm(time)?;
+        let repeat = (time - alert).num_seconds() / every.num_seconds();
+        let exact = alert + every * repeat as i32 == time && (1..=i64::from(repeats)).contains(&repeat);
+        exact.then_some((alert, repeat as u32))
+    }
+
+    /// The first repeat of an unanswered alarm at or after `from`.
+    fn next_repeat(&self, from: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        let (alert, every, repeats) = self.unanswered_alarm(from)?;
+        let (since, every_nanos) = ((from - alert).num_nanoseconds()?, every.num_nanoseconds()?);
+        // Rounded up, so a repeat exactly at `from` still counts
+        let repeat = (since + every_nanos - 1) / every_nanos;
+        (repeat <= i64::from(repeats)).then(|| alert + every * repeat as i32)
+    }
+
+    /// The last alert before `time`, with the alarm's interval and number of
+    /// repeats, when the reminder is an alarm and that alert was not answered.
+    fn unanswered_alarm(&self, time: DateTime<FixedOffset>) -> Option<(DateTime<FixedOffset>, chrono::Duration, u32)> {
+        let alarm = self.alarm.as_ref().filter(|alarm| alarm.every_minutes > 0 && alarm.repeats > 0)?;
+        if self.completed_at.is_some() {
+            return None;
+        }
+        let occurrence = match &self.recurrence {
+            Some(rule) => rule.occurrences(self.time).take_while(|occurrence| *occurrence < time).last(),
+            None => Some(self.time).filter(|occurrence| *occurrence < time),
+        };
+        let snooze = self.snoozed_until.filter(|until| *until < time);
+        let alert = occurrence.max(snooze)?;
+        if self.acknowledged_at.is_some_and(|acknowledged| acknowledged >= alert) {
+            return None;
+        }
+        Some((alert, chrono::Duration::minutes(alarm.every_minutes.into()), alarm.repeats))
+    }
+
+    /// The time a pending snooze ends, if there is one.
+    pub fn snoozed(&self, now: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        self.snoozed_until.filter(|until| *until > now)
+    }
+
+    /// Has the reminder alert again `minutes` after `now`. The count starts
+    /// over when a regular occurrence came up since the last snooze.
+    pub fn snooze(&mut self, minutes: u32, now: DateTime<FixedOffset>) {
+        let again = self
+            .snoozed_until
+            .is_some_and(|until| self.next_occurrence(until + chrono::Duration::seconds(1)).is_none_or(|next| next > now));
+        self.snooze_count = if again { self.snooze_count + 1 } else { 1 };
+        self.snoozed_until = Some(now + chrono::Duration::minutes(minutes.into()));
+        self.acknowledged_at = Some(now);
+    }
+
+    pub fn unsnooze(&mut self) {
+        self.snoozed_until = None;
+        self.snooze_count = 0;
+    }
+
+    /// Marks the occurrence due at `due` done. Returns false when nothing is
+    /// left of the reminder afterwards, it is then completed. Occurrences
+    /// still ahead are skipped, ones already past need no change.
+    pub fn mark_done(&mut self, due: DateTime<FixedOffset>, now: DateTime<FixedOffset>) -> bool {
+        self.unsnooze();
+        self.acknowledged_at = Some(now);
+        let last = self.last_occurrence();
+        match &mut self.recurrence {
+            Some(_) if last == Some(due) => {}
+            Some(rule) => {
+                if due >= now {
+                    rule.exceptions.push(due);
+                }
+                return true;
+            }
+            None => {}
+        }
+        self.complete(now);
+        false
+    }
+
+    /// Marks whatever comes up next done, for when no notification says which
+    /// occurrence is meant: the one a snooze postponed, otherwise the next one.
+    /// Returns false when the reminder got completed.
+    pub fn done(&mut self, now: DateTime<FixedOffset>) -> bool {
+        if self.recurrence.is_some() && self.snoozed(now).is_some() {
+            self.unsnooze();
+            self.acknowledged_at = Some(now);
+            return true;
+        }
+        match self.next_occurrence(now) {
+            Some(next) => self.mark_done(next, now),
+            None => {
+                self.complete(now);
+                false
+            }
+        }
+    }
+
+    /// Ends the reminder for good, moving it to the history.
+    pub fn complete(&mut self, now: DateTime<FixedOffset>) {
+        self.unsnooze();
+        self.completed_at = Some(now);
+    }
+
+    /// The due time converted into the user's current time zone.
+    pub fn local_time(&self) -> DateTime<Local> {
+        self.time.with_timezone(&Local)
+    }
+}
+
+/// Parses a stored reminder time, which must be an RFC 3339 timestamp.
+pub fn parse_time(text: &str) -> Result<DateTime<FixedOffset>, String> {
+    DateTime::parse_from_rfc3339(text).map_err(|e| format!("'{}' is not a valid time: {}", text, e))
+}
+
+/// Like `parse_time`, but also accepts the offset-less format of old files,
+/// which is interpreted in the local time zone.
+pub fn parse_legacy_time(text: &str) -> Result<DateTime<FixedOffset>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time);
+    }
+
+    let naive = NaiveDateTime::parse_from_str(text, LEGACY_TIME_FORMAT)
+        .map_err(|e| format!("'{}' is not a valid time: {}", text, e))?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|time| time.fixed_offset())
+        .ok_or_else(|| format!("'{}' does not exist in the local time zone", text))
+}
+
+pub fn format_time(time: &DateTime<FixedOffset>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, false)
+}
+
+pub fn new_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// Id for an entry saved without one. It is derived from the entry itself so
+/// that re-reading the same file hands out the same id until it gets saved.
+pub fn derived_id(position: usize, name: &str, time: &str) -> String {
+    let seed = format!("{}\n{}\n{}", position, name, time);
+    Uuid::new_v5(&Uuid::NAMESPACE_OID, seed.as_bytes()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        parse_time(text).unwrap()
+    }
+
+    fn daily(time: &str) -> Reminder {
+        let mut reminder = Reminder::new("Stretch".to_string(), at(time));
+        reminder.recurrence = Some(Recurrence::parse("FREQ=DAILY").unwrap());
+        reminder
+    }
+
+    #[test]
+    fn snooze_counts_start_over_with_each_occurrence() {
+        let mut reminder = daily("2030-06-10T09:00:00+02:00");
+        reminder.snooze(10, at("2030-06-10T09:05:00+02:00"));
+        assert_eq!(reminder.snooze_count, 1);
+        assert_eq!(reminder.snoozed_until, Some(at("2030-06-10T09:15:00+02:00")));
+        reminder.snooze(10, at("2030-06-10T09:16:00+02:00"));
+        assert_eq!(reminder.snooze_count, 2, "still the same occurrence");
+
+        reminder.snooze(10, at("2030-06-11T09:02:00+02:00"));
+        assert_eq!(reminder.snooze_count, 1, "the next day's occurrence came up since");
+        assert_eq!(reminder.acknowledged_at, Some(at("2030-06-11T09:02:00+02:00")));
+        reminder.unsnooze();
+        assert_eq!((reminder.snoozed_until, reminder.snooze_count), (None, 0));
+    }
+
+    #[test]
+    fn a_snooze_swallows_the_occurrences_it_reaches_past() {
+        let mut reminder = daily("2030-06-10T09:00:00+02:00");
+        let now = at("2030-06-10T09:05:00+02:00");
+        assert_eq!(reminder.next_alert(now), Some(at("2030-06-11T09:00:00+02:00")));
+
+        reminder.snooze(25 * 60, now);
+        assert_eq!(reminder.next_alert(now), Some(at("2030-06-11T10:05:00+02:00")));
+        assert_eq!(reminder.snoozed(at("2030-06-11T09:00:00+02:00")), Some(at("2030-06-11T10:05:00+02:00")));
+        assert_eq!(reminder.next_alert(at("2030-06-11T10:05:01+02:00")), Some(at("2030-06-12T09:00:00+02:00")));
+
+        // The next morning brought an occurrence of its own since the snooze ended
+        reminder.snooze(10, at("2030-06-12T09:01:00+02:00"));
+        assert_eq!(reminder.snooze_count, 1);
+    }
+
+    #[test]
+    fn single_reminders_alert_once_and_completed_ones_never() {
+        let mut reminder = Reminder::new("Call".to_string(), at("2030-06-10T09:00:00+02:00"));
+        assert_eq!(reminder.next_alert(at("2030-06-10T08:00:00+02:00")), Some(reminder.time));
+        assert_eq!(reminder.next_alert(reminder.time), Some(reminder.time), "`from` is inclusive");
+        assert_eq!(reminder.next_alert(at("2030-06-10T09:00:01+02:00")), None);
+
+        reminder.snooze(5, at("2030-06-10T09:01:00+02:00"));
+        assert_eq!(reminder.next_alert(at("2030-06-10T09:02:00+02:00")), Some(at("2030-06-10T09:06:00+02:00")));
+        assert_eq!(reminder.last_alert(), Some(at("2030-06-10T09:06:00+02:00")));
+
+        reminder.complete(at("2030-06-10T09:03:00+02:00"));
+        assert_eq!(reminder.next_alert(at("2030-06-10T09:02:00+02:00")), None);
+    }
+
+    #[test]
+    fn unanswered_alarms_repeat_until_their_last_alert() {
+        let mut reminder = Reminder::new("Pills".to_string(), at("2030-06-10T09:00:00+02:00"));
+        reminder.alarm = Some(Alarm { sound: None, every_minutes: 5, repeats: 2 });
+        assert_eq!(reminder.next_alert(at("2030-06-10T09:00:01+02:00")), Some(at("2030-06-10T09:05:00+02:00")));
+        assert_eq!(reminder.alarm_repeat(at("2030-06-10T09:10:00+02:00")), Some((reminder.time, 2)));
+        assert_eq!(reminder.next_alert(at("2030-06-10T09:10:01+02:00")), None);
+        assert_eq!(reminder.last_alert(), Some(at("2030-06-10T09:10:00+02:00")));
+
+        reminder.acknowledged_at = Some(at("2030-06-10T09:01:00+02:00"));
+        assert_eq!(reminder.next_alert(at("2030-06-10T09:01:00+02:00")), None, "answered alarms stop");
+
+        reminder.alarm = Some(Alarm { sound: None, every_minutes: u32::MAX, repeats: u32::MAX });
+        assert_eq!(reminder.last_alert(), None, "too far off to tell");
+    }
+}
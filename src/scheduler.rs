@@ -0,0 +1,419 @@
+//! Works out when reminders go off. Every reminder's next alert waits in a
+//! priority queue, and the daemon sleeps until the earliest one instead of
+//! checking the store over and over. Only reminders that alert within the
+//! next `LOOKAHEAD` are read from the store, the daemon reads the following
+//! stretch once that time is up.
+//!
+//! Sleeping counts steady time, which neither follows the wall clock being set
+//! nor, on Linux, the time spent suspended. So the daemon also wakes up every
+//! `WATCHDOG` to compare both clocks and the time zone, and re-arms when they
+//! disagree with what it expected.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, FixedOffset, Local};
+
+use crate::missed::Missed;
+use crate::reminder::Reminder;
+
+// How long after its due time a reminder still gets a notification of its own,
+// anything later counts as missed
+pub const NOTIFY_GRACE: chrono::Duration = chrono::Duration::seconds(60);
+// The longest the daemon sleeps without looking at the clocks
+pub const WATCHDOG: Duration = Duration::from_secs(60);
+// How far the wall clock may drift from steady time before it counts as set
+const CLOCK_SLACK: chrono::Duration = chrono::Duration::seconds(2);
+// How far ahead of now reminders are read from the store
+pub const LOOKAHEAD: chrono::Duration = chrono::Duration::hours(24);
+// Alerts are looked up from a time on, this makes that strictly after it
+const JUST_AFTER: chrono::Duration = chrono::Duration::nanoseconds(1);
+
+/// Where the scheduler gets the time from, so tests can move it around.
+pub trait Clock {
+    /// The wall-clock time, with the current UTC offset.
+    fn now(&self) -> DateTime<FixedOffset>;
+    /// Time that passes steadily, whatever happens to the wall clock.
+    fn steady(&self) -> Duration;
+    /// Names the local time zone, so a change of zone can be noticed.
+    fn zone(&self) -> String;
+}
+
+pub struct SystemClock {
+    started: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        SystemClock { started: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        Local::now().fixed_offset()
+    }
+
+    fn steady(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    fn zone(&self) -> String {
+        iana_time_zone::get_timezone().unwrap_or_default()
+    }
+}
+
+/// What came up since the last look at the queue.
+#[derive(Debug, Default)]
+pub struct Due {
+    /// Reminders to notify about now, with the time they were due.
+    pub fire: Vec<(Reminder, DateTime<FixedOffset>)>,
+    /// Reminders whose time passed while the daemon was away or asleep.
+    pub missed: Vec<Missed>,
+}
+
+pub struct Scheduler<C: Clock> {
+    clock: C,
+    reminders: HashMap<String, Reminder>,
+    queue: BinaryHeap<Reverse<(DateTime<FixedOffset>, String)>>,
+    /// Alerts up to this time were handled.
+    handled_until: DateTime<FixedOffset>,
+    /// The reminders were read for alerts up to this time, nothing later is handled before the next read.
+    loaded_until: DateTime<FixedOffset>,
+    // The clocks and zone at the last look, to notice them changing
+    seen_at: DateTime<FixedOffset>,
+    seen_steady: Duration,
+    seen_zone: String,
+}
+
+impl<C: Clock> Scheduler<C> {
+    /// An empty scheduler, to be filled through `window` and `reload`. Alerts
+    /// after `since` that are already over by now come up as missed on the
+    /// first `poll`, without it nothing before now does.
+    pub fn new(clock: C, since: Option<DateTime<FixedOffset>>) -> Self {
+        let now = clock.now();
+        let handled_until = since.map_or(now, |since| since.min(now));
+        Scheduler {
+            reminders: HashMap::new(),
+            queue: BinaryHeap::new(),
+            handled_until,
+            loaded_until: handled_until,
+            seen_at: now,
+            seen_steady: clock.steady(),
+            seen_zone: clock.zone(),
+            clock,
+        }
+    }
+
+    /// The time range to read reminders for, as `from` and `to` of `ReminderStore::due_between`.
+    pub fn window(&self) -> (DateTime<FixedOffset>, DateTime<FixedOffset>) {
+        (self.handled_until + JUST_AFTER, self.clock.now().max(self.handled_until) + LOOKAHEAD)
+    }
+
+    /// Replaces the queued reminders with `reminders`, which hold every one
+    /// that alerts from the start of `window` up to `until`.
+    pub fn reload(&mut self, reminders: Vec<Reminder>, until: DateTime<FixedOffset>) {
+        self.reminders = reminders.into_iter().map(|reminder| (reminder.id.clone(), reminder)).collect();
+        self.loaded_until = until;
+        self.rearm();
+    }
+
+    /// Whether the reminders read last are used up, so `window` should be read again.
+    pub fn needs_reload(&self) -> bool {
+        self.clock.now() >= self.loaded_until
+    }
+
+    /// The time up to which alerts were handled, kept as the last run.
+    pub fn handled_until(&self) -> DateTime<FixedOffset> {
+        self.handled_until
+    }
+
+    /// The earliest queued alert.
+    pub fn next_alert(&self) -> Option<DateTime<FixedOffset>> {
+        self.queue.peek().map(|Reverse((due, _))| *due)
+    }
+
+    /// How long to sleep before calling `poll` again, or reading the next
+    /// window. After a failed read that is retried when the watchdog is up.
+    pub fn next_wake(&self) -> Duration {
+        let now = self.clock.now();
+        let mut wake = (self.loaded_until - now).to_std().unwrap_or(WATCHDOG).min(WATCHDOG);
+        if let Some(due) = self.next_alert().filter(|due| *due <= self.loaded_until) {
+            wake = wake.min((due - now).to_std().unwrap_or(Duration::ZERO));
+        }
+        wake
+    }
+
+    /// Takes everything that came up since the last call off the queue.
+    pub fn poll(&mut self) -> Due {
+        let now = self.clock.now();
+        let steady = self.clock.steady();
+        let zone = self.clock.zone();
+
+        let expected = self.seen_at + chrono::Duration::from_std(steady - self.seen_steady).unwrap_or_default();
+        if now < expected - CLOCK_SLACK {
+            // Alerts up to the old time were notified already, so they are not queued again
+            println!("The clock was set back by {}s", (expected - now).num_seconds());
+            self.rearm();
+        } else if zone != self.seen_zone || now.offset() != self.seen_at.offset() {
+            // Repeating reminders follow local time, so their next times move
+            println!("The time zone changed to {}", if zone.is_empty() { "an unknown zone" } else { &zone });
+            self.rearm();
+        }
+        // A clock set forward or a suspend needs nothing special, the queue just
+        // has more that is over, which comes up as missed
+        self.seen_at = now;
+        self.seen_steady = steady;
+        self.seen_zone = zone;
+
+        // Later alerts may belong to reminders that were not read yet
+        let until = now.min(self.loaded_until);
+        let mut due = Due::default();
+        while let Some(Reverse((time, _))) = self.queue.peek() {
+            if *time > until {
+                break;
+            }
+            let Reverse((time, id)) = self.queue.pop().unwrap();
+            let Some(reminder) = self.reminders.get(&id) else {
+                continue;
+            };
+            let skip_to = if time < now - NOTIFY_GRACE {
+                if !due.missed.iter().any(|missed| missed.id == id) {
+                    due.missed.push(Missed { id: id.clone(), name: reminder.name.clone(), due: time });
+                }
+                // Straight on to what is still worth a notification
+                (time + JUST_AFTER).max(now - NOTIFY_GRACE)
+            } else {
+                due.fire.push((reminder.clone(), time));
+                time + JUST_AFTER
+            };
+            if let Some(next) = reminder.next_alert(skip_to) {
+                self.queue.push(Reverse((next, id)));
+            }
+        }
+        self.handled_until = self.handled_until.max(until);
+        due
+    }
+
+    /// Queues every reminder's first alert after what was already handled.
+    fn rearm(&mut self) {
+        let from = self.handled_until + JUST_AFTER;
+        self.queue = self
+            .reminders
+            .values()
+            .filter_map(|reminder| Some(Reverse((reminder.next_alert(from)?, reminder.id.clone()))))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alarm::Alarm;
+    use crate::file_actions::XmlStore;
+    use crate::store::ReminderStore;
+    use crate::test_support::{scratch_store, FakeClock};
+    use crate::recurrence::Recurrence;
+    use crate::reminder;
+
+    fn time(text: &str) -> DateTime<FixedOffset> {
+        reminder::parse_time(text).unwrap()
+    }
+
+    // Later than any test gets to, so nothing needs reading twice
+    fn far_future() -> DateTime<FixedOffset> {
+        time("2100-01-01T00:00:00+00:00")
+    }
+
+    fn queued(clock: &FakeClock, reminders: Vec<Reminder>, since: Option<DateTime<FixedOffset>>) -> Scheduler<FakeClock> {
+        let mut scheduler = Scheduler::new(clock.clone(), since);
+        scheduler.reload(reminders, far_future());
+        scheduler
+    }
+
+    fn fired(due: &Due) -> Vec<(&str, DateTime<FixedOffset>)> {
+        due.fire.iter().map(|(reminder, time)| (reminder.name.as_str(), *time)).collect()
+    }
+
+    #[test]
+    fn sleeps_until_the_earliest_alert_and_fires_on_time() {
+        let clock = FakeClock::at("2026-10-18T08:59:30+02:00");
+        let tea = Reminder::new("Tea".to_string(), time("2026-10-18T09:00:00+02:00"));
+        let mut stretch = Reminder::new("Stretch".to_string(), time("2026-10-18T09:00:10+02:00"));
+        stretch.recurrence = Some(Recurrence::parse("FREQ=DAILY;COUNT=2").unwrap());
+        let mut scheduler = queued(&clock, vec![stretch, tea], None);
+
+        assert_eq!(scheduler.next_wake(), Duration::from_secs(30));
+        assert!(fired(&scheduler.poll()).is_empty());
+        clock.advance(30);
+        assert_eq!(fired(&scheduler.poll()), [("Tea", time("2026-10-18T09:00:00+02:00"))]);
+        assert_eq!(scheduler.next_wake(), Duration::from_secs(10));
+        clock.advance(10);
+        assert_eq!(fired(&scheduler.poll()), [("Stretch", time("2026-10-18T09:00:10+02:00"))]);
+
+        // Its second and last occurrence is queued next, and nothing comes twice
+        assert_eq!(scheduler.next_alert(), Some(time("2026-10-19T09:00:10+02:00")));
+        assert_eq!(scheduler.next_wake(), WATCHDOG);
+        clock.advance(1);
+        assert!(fired(&scheduler.poll()).is_empty());
+        clock.advance(24 * 3600);
+        assert_eq!(fired(&scheduler.poll()).len(), 1);
+        assert_eq!(scheduler.next_alert(), None);
+    }
+
+    #[test]
+    fn changes_to_the_store_rearm_the_queue() {
+        let clock = FakeClock::at("2026-10-18T08:00:00+02:00");
+        let mut tea = Reminder::new("Tea".to_string(), time("2026-10-18T09:00:00+02:00"));
+        let mut scheduler = queued(&clock, vec![tea.clone()], None);
+        assert_eq!(scheduler.next_alert(), Some(tea.time));
+
+        tea.time = time("2026-10-18T08:30:00+02:00");
+        let walk = Reminder::new("Walk".to_string(), time("2026-10-18T08:10:00+02:00"));
+        scheduler.reload(vec![tea.clone(), walk], far_future());
+        assert_eq!(scheduler.next_alert(), Some(time("2026-10-18T08:10:00+02:00")));
+
+        clock.advance(30 * 60);
+        let due = scheduler.poll();
+        assert_eq!(due.missed.len(), 1, "the walk was 20 minutes ago when the daemon looked");
+        assert_eq!(fired(&due), [("Tea", tea.time)]);
+
+        // Snoozing from the notification brings it back later
+        tea.snooze(10, clock.now());
+        scheduler.reload(vec![tea.clone()], far_future());
+        assert_eq!(scheduler.next_alert(), tea.snoozed_until);
+        clock.advance(10 * 60);
+        assert_eq!(fired(&scheduler.poll()), [("Tea", tea.snoozed_until.unwrap())]);
+        assert_eq!(scheduler.next_alert(), None);
+    }
+
+    #[test]
+    fn time_away_is_caught_up_as_missed() {
+        let clock = FakeClock::at("2026-10-17T22:00:00+02:00");
+        let mut daily = Reminder::new("Stretch".to_string(), time("2026-10-01T08:00:00+02:00"));
+        daily.recurrence = Some(Recurrence::parse("FREQ=DAILY").unwrap());
+        let bank = Reminder::new("Call the bank".to_string(), time("2026-10-17T23:00:00+02:00"));
+        let door = Reminder::new("Lock the door".to_string(), time("2026-10-19T07:59:30+02:00"));
+        let reminders = vec![daily, bank, door];
+
+        // Not running from Friday 22:00 until Monday morning
+        let clock_now = FakeClock::at("2026-10-19T07:59:45+02:00");
+        let mut scheduler = queued(&clock_now, reminders.clone(), Some(clock.now()));
+        let due = scheduler.poll();
+        let missed: Vec<&str> = due.missed.iter().map(|missed| missed.name.as_str()).collect();
+        assert_eq!(missed, ["Call the bank", "Stretch"], "each reminder once");
+        assert_eq!(due.missed[1].due, time("2026-10-18T08:00:00+02:00"), "from the first time it was missed");
+        assert_eq!(fired(&due), [("Lock the door", time("2026-10-19T07:59:30+02:00"))], "still within the grace time");
+
+        // A first start knows nothing about the past
+        let mut fresh = queued(&clock_now, reminders, None);
+        let due = fresh.poll();
+        assert!(due.missed.is_empty() && due.fire.is_empty());
+
+        // Suspended overnight: the wall clock jumps while steady time stands still
+        clock_now.jump(24 * 3600);
+        let due = scheduler.poll();
+        assert_eq!(due.missed.len(), 1);
+        assert_eq!(due.missed[0].due, time("2026-10-19T08:00:00+02:00"));
+    }
+
+    #[test]
+    fn alarms_repeat_until_answered() {
+        let clock = FakeClock::at("2026-10-18T08:59:00+02:00");
+        let mut pills = Reminder::new("Pills".to_string(), time("2026-10-18T09:00:00+02:00"));
+        pills.alarm = Some(Alarm { sound: None, every_minutes: 5, repeats: 2 });
+        let mut stand_up = Reminder::new("Stand-up".to_string(), time("2026-10-18T09:30:00+02:00"));
+        stand_up.recurrence = Some(Recurrence::parse("FREQ=DAILY").unwrap());
+        stand_up.alarm = pills.alarm.clone();
+        let mut scheduler = queued(&clock, vec![pills.clone(), stand_up.clone()], None);
+
+        // Nobody answers: it comes back twice, then gives up
+        clock.advance(60);
+        assert_eq!(fired(&scheduler.poll()), [("Pills", pills.time)]);
+        assert_eq!(pills.alarm_repeat(pills.time), None);
+        for repeat in 1..=2 {
+            clock.advance(5 * 60);
+            let again = pills.time + chrono::Duration::minutes(5 * repeat);
+            assert_eq!(fired(&scheduler.poll()), [("Pills", again)]);
+            assert_eq!(pills.alarm_repeat(again), Some((pills.time, repeat as u32)));
+        }
+        assert_eq!(scheduler.next_alert(), Some(stand_up.time));
+
+        // Marked done right after it went off
+        clock.advance(20 * 60);
+        assert_eq!(fired(&scheduler.poll()), [("Stand-up", stand_up.time)]);
+        assert_eq!(scheduler.next_alert(), Some(time("2026-10-18T09:35:00+02:00")));
+        clock.advance(60);
+        assert!(stand_up.mark_done(stand_up.time, clock.now()));
+        scheduler.reload(vec![stand_up.clone()], far_future());
+        assert_eq!(scheduler.next_alert(), Some(time("2026-10-19T09:30:00+02:00")));
+
+        // Snoozed the next day: quiet until the snooze is over, which repeats again
+        clock.advance(24 * 3600 - 60);
+        assert_eq!(fired(&scheduler.poll()).len(), 1);
+        stand_up.snooze(15, clock.now());
+        scheduler.reload(vec![stand_up.clone()], far_future());
+        assert_eq!(scheduler.next_alert(), stand_up.snoozed_until);
+        clock.advance(15 * 60);
+        assert_eq!(fired(&scheduler.poll()).len(), 1);
+        assert_eq!(scheduler.next_alert(), Some(time("2026-10-19T09:50:00+02:00")));
+    }
+
+    #[test]
+    fn only_the_coming_window_is_read_from_the_store() {
+        let (_dir, mut store) = scratch_store("scheduler-window", "reminders.xml", XmlStore::new);
+        let clock = FakeClock::at("2026-10-18T08:00:00+02:00");
+        let tea = Reminder::new("Tea".to_string(), time("2026-10-18T09:00:00+02:00"));
+        let dentist = Reminder::new("Dentist".to_string(), time("2026-10-21T10:00:00+02:00"));
+        store.insert_all(&[tea.clone(), dentist.clone()]).unwrap();
+        let read = |scheduler: &mut Scheduler<FakeClock>, store: &mut XmlStore| {
+            let (from, to) = scheduler.window();
+            scheduler.reload(store.due_between(from, to).unwrap(), to);
+        };
+
+        let mut scheduler = Scheduler::new(clock.clone(), None);
+        assert!(scheduler.needs_reload());
+        read(&mut scheduler, &mut store);
+        assert!(!scheduler.reminders.contains_key(&dentist.id), "days away");
+        assert_eq!(scheduler.next_wake(), WATCHDOG);
+        clock.advance(3600);
+        assert_eq!(fired(&scheduler.poll()), [("Tea", tea.time)]);
+        clock.advance(23 * 3600 - 30);
+        assert_eq!(scheduler.next_wake(), Duration::from_secs(30), "wakes up to read the next day");
+
+        // Suspended over the dentist: it only comes up once its day is read
+        clock.jump(4 * 24 * 3600);
+        assert!(scheduler.poll().missed.is_empty());
+        assert!(scheduler.needs_reload());
+        read(&mut scheduler, &mut store);
+        let due = scheduler.poll();
+        assert_eq!(due.missed.len(), 1);
+        assert_eq!(due.missed[0].id, dentist.id);
+        assert!(!scheduler.needs_reload());
+    }
+
+    #[test]
+    fn clock_and_zone_changes_rearm_without_repeats() {
+        let clock = FakeClock::at("2026-10-18T08:59:00+02:00");
+        let mut stretch = Reminder::new("Stretch".to_string(), time("2026-10-18T09:00:00+02:00"));
+        stretch.recurrence = Some(Recurrence::parse("FREQ=DAILY").unwrap());
+        let mut scheduler = queued(&clock, vec![stretch], None);
+
+        clock.advance(60);
+        assert_eq!(fired(&scheduler.poll()).len(), 1);
+
+        // Set back by a few minutes: 09:00 comes around again, but was notified already
+        clock.jump(-300);
+        assert!(fired(&scheduler.poll()).is_empty());
+        clock.advance(300);
+        assert!(fired(&scheduler.poll()).is_empty());
+        assert_eq!(scheduler.next_alert(), Some(time("2026-10-19T09:00:00+02:00")));
+
+        clock.set_zone("America/New_York");
+        assert!(fired(&scheduler.poll()).is_empty());
+        assert_eq!(scheduler.next_alert(), Some(time("2026-10-19T09:00:00+02:00")));
+        clock.advance(24 * 3600);
+        assert_eq!(fired(&scheduler.poll()).len(), 1);
+    }
+}
@@ -0,0 +1,270 @@
+//! Starting the daemon at login. Where systemd manages the session this is a
+//! `--user` unit, everywhere else an XDG autostart entry.
+
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::{DateTime, FixedOffset, Local};
+
+use crate::config;
+use crate::daemon::{self, DaemonClient};
+use crate::reminder::Reminder;
+use crate::store::ReminderStore;
+
+const UNIT_NAME: &str = "rewind.service";
+const AUTOSTART_NAME: &str = "rewind-daemon.desktop";
+
+fn unit_path() -> PathBuf {
+    config::config_home().join("systemd").join("user").join(UNIT_NAME)
+}
+
+fn autostart_path() -> PathBuf {
+    config::config_home().join("autostart").join(AUTOSTART_NAME)
+}
+
+/// Sets the daemon up to start at login and starts it now. Uses a systemd user
+/// unit, unless `autostart` is set or systemd is not managing this session.
+pub fn install(args: &[OsString], autostart: bool) -> Result<String, Box<dyn Error>> {
+    let command = daemon_command(args)?;
+
+    if autostart || !has_systemd_user() {
+        // Only one of them should start it
+        remove_unit()?;
+        let path = autostart_path();
+        write_file(&path, &autostart_entry(&command))?;
+        DaemonClient::connect(args)?;
+        return Ok(format!("Installed {}, the background service is running and will start at every login", path.display()));
+    }
+
+    remove_file_if_present(&autostart_path())?;
+    let path = unit_path();
+    write_file(&path, &unit_file(&command))?;
+    systemctl(&["daemon-reload"])?;
+    systemctl(&["enable", UNIT_NAME])?;
+    // A copy the window started would keep the unit from claiming the bus name
+    daemon::stop_running()?;
+    systemctl(&["restart", UNIT_NAME])?;
+    Ok(format!("Installed and started {}, the background service will start at every login", path.display()))
+}
+
+/// Stops starting the daemon at login. The systemd unit is stopped as well,
+/// a daemon started some other way keeps running until it is told to quit.
+pub fn remove() -> Result<String, Box<dyn Error>> {
+    let mut removed = Vec::new();
+    if remove_unit()? {
+        removed.push(unit_path().display().to_string());
+    }
+    if remove_file_if_present(&autostart_path())? {
+        removed.push(autostart_path().display().to_string());
+    }
+
+    if removed.is_empty() {
+        Ok("The background service was not set up to start at login".to_string())
+    } else {
+        Ok(format!("Removed {}", removed.join(" and ")))
+    }
+}
+
+/// Describes how the daemon is started, whether it runs, and what comes next.
+pub fn status(store: &mut dyn ReminderStore) -> Result<String, Box<dyn Error>> {
+    let mut lines = Vec::new();
+    let unit = unit_path();
+    let entry = autostart_path();
+    if unit.exists() {
+        let enabled = systemctl_output(&["is-enabled", UNIT_NAME]).unwrap_or_else(|| "unknown".to_string());
+        lines.push(format!("Starts at login: through {} ({})", unit.display(), enabled));
+    } else if entry.exists() {
+        lines.push(format!("Starts at login: through {}", entry.display()));
+    } else {
+        lines.push("Starts at login: no, `rewind service install` sets that up".to_string());
+    }
+
+    let state = match daemon::is_running() {
+        Ok(true) => "running".to_string(),
+        Ok(false) => "not running".to_string(),
+        Err(e) => format!("unknown ({})", e),
+    };
+    let loaded = store.load()?;
+    match next_reminder(&loaded.reminders, Local::now().fixed_offset()) {
+        Some((reminder, due)) => lines.push(format!(
+            "Status: {}, next reminder at {} ({})",
+            state,
+            due.format("%a %-d %b %Y %H:%M"),
+            reminder.name
+        )),
+        None => lines.push(format!("Status: {}, no upcoming reminders", state)),
+    }
+    Ok(lines.join("\n"))
+}
+
+/// The reminder that comes up first after `now`, with the time it is due.
+fn next_reminder(reminders: &[Reminder], now: DateTime<FixedOffset>) -> Option<(&Reminder, DateTime<FixedOffset>)> {
+    reminders
+        .iter()
+        .filter_map(|reminder| reminder.next_alert(now).map(|due| (reminder, due)))
+        .min_by_key(|(_, due)| *due)
+}
+
+/// Whether a systemd user instance is managing this session.
+fn has_systemd_user() -> bool {
+    Command::new("systemctl")
+        .args(["--user", "show-environment"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn systemctl(args: &[&str]) -> Result<(), Box<dyn Error>> {
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .map_err(|e| format!("could not run systemctl: {}", e))?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(format!(
+            "systemctl --user {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+        .into())
+    }
+}
+
+/// What systemctl prints, whatever its exit code. Queries like `is-enabled` fail on a "no".
+fn systemctl_output(args: &[&str]) -> Option<String> {
+    let output = Command::new("systemctl").arg("--user").args(args).output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if text.is_empty() { None } else { Some(text) }
+}
+
+/// Disables and deletes the systemd unit. Returns whether there was one.
+fn remove_unit() -> Result<bool, Box<dyn Error>> {
+    let path = unit_path();
+    if !path.exists() {
+        return Ok(false);
+    }
+    if has_systemd_user() {
+        systemctl(&["disable", "--now", UNIT_NAME])?;
+    }
+    fs::remove_file(&path).map_err(|e| format!("could not remove {}: {}", path.display(), e))?;
+    if has_systemd_user() {
+        systemctl(&["daemon-reload"])?;
+    }
+    Ok(true)
+}
+
+fn remove_file_if_present(path: &Path) -> Result<bool, Box<dyn Error>> {
+    match fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(format!("could not remove {}: {}", path.display(), e).into()),
+    }
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("could not create {}: {}", dir.display(), e))?;
+    }
+    fs::write(path, contents).map_err(|e| format!("could not write {}: {}", path.display(), e).into())
+}
+
+/// This executable with `--daemon` and `args`.
+fn daemon_command(args: &[OsString]) -> Result<Vec<String>, Box<dyn Error>> {
+    let executable = std::env::current_exe().map_err(|e| format!("could not find the rewind executable: {}", e))?;
+    let mut command = vec![executable.into_os_string(), OsString::from("--daemon")];
+    command.extend(args.iter().cloned());
+    command
+        .into_iter()
+        .map(|arg| {
+            arg.into_string()
+                .map_err(|arg| format!("{} can't be written to a service file", arg.to_string_lossy()).into())
+        })
+        .collect()
+}
+
+fn unit_file(command: &[String]) -> String {
+    // systemd takes C-style escapes inside quotes, and expands % specifiers and $ variables
+    let exec: Vec<String> = command
+        .iter()
+        .map(|arg| {
+            let escaped = arg.replace('\\', "\\\\").replace('"', "\\\"").replace('%', "%%").replace('$', "$$");
+            format!("\"{}\"", escaped)
+        })
+        .collect();
+    format!(
+        "[Unit]\n\
+         Description=Rewind reminder notifications\n\
+         \n\
+         [Service]\n\
+         Type=dbus\n\
+         BusName={}\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         \n\
+         [Install]\n\
+         WantedBy=default.target\n",
+        daemon::BUS_NAME,
+        exec.join(" ")
+    )
+}
+
+fn autostart_entry(command: &[String]) -> String {
+    // Quoting rules of the Exec key, then backslashes escaped once more for the string value
+    let exec: Vec<String> = command
+        .iter()
+        .map(|arg| {
+            let mut quoted = String::from("\"");
+            for c in arg.chars() {
+                match c {
+                    '"' | '`' | '$' | '\\' => {
+                        quoted.push('\\');
+                        quoted.push(c);
+                    }
+                    '%' => quoted.push_str("%%"),
+                    _ => quoted.push(c),
+                }
+            }
+            quoted.push('"');
+            quoted.replace('\\', "\\\\")
+        })
+        .collect();
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=Rewind Reminders\n\
+         Comment=Sends notifications when reminders are due\n\
+         Exec={}\n\
+         NoDisplay=true\n\
+         X-GNOME-Autostart-enabled=true\n",
+        exec.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn service_files_quote_unusual_paths() {
+        let command = vec![
+            "/opt/my apps/rewind".to_string(),
+            "--daemon".to_string(),
+            "--data-dir".to_string(),
+            "/home/me/100% \"done\"".to_string(),
+        ];
+
+        let unit = unit_file(&command);
+        assert!(unit.contains(r#"ExecStart="/opt/my apps/rewind" "--daemon" "--data-dir" "/home/me/100%% \"done\"""#));
+        assert!(unit.contains("BusName=org.rewind.Reminders\n"));
+
+        let entry = autostart_entry(&command);
+        assert!(entry.contains(r#"Exec="/opt/my apps/rewind" "--daemon" "--data-dir" "/home/me/100%% \\"done\\"""#));
+    }
+}
@@ -0,0 +1,300 @@
+//! A SQLite backend for long reminder lists. Changes touch a single row
+//! instead of rewriting everything, and due reminders are found through an
+//! index on their first and last occurrence instead of reading every entry.
+//!
+//! Each row keeps the whole reminder as a `<reminder>` element, the same one
+//! the XML file uses, so both backends share one format and one set of
+//! migrations. The database's `user_version` holds the format version.
+
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+use chrono::{DateTime, FixedOffset};
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::file_actions::{reminder_from_element, reminder_to_element};
+use crate::migrations::{self, CURRENT_VERSION};
+use crate::reminder::Reminder;
+use crate::store::{LoadError, LoadedReminders, ReminderStore};
+use crate::xml_tree::{self, Element};
+
+// `starts_at` and `ends_at` are Unix timestamps of the first and last
+// occurrence, `ends_at` is NULL for reminders that repeat forever
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS reminders (
+        id TEXT PRIMARY KEY NOT NULL,
+        name TEXT NOT NULL,
+        starts_at INTEGER NOT NULL,
+        ends_at INTEGER,
+        body TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS reminders_by_start ON reminders (starts_at);
+    CREATE INDEX IF NOT EXISTS reminders_by_end ON reminders (ends_at);
+";
+
+pub struct SqliteStore {
+    path: PathBuf,
+    // Opened on first use, so that a broken database is reported by `load`
+    connection: Option<Connection>,
+}
+
+impl SqliteStore {
+    pub fn new(path: PathBuf) -> Self {
+        SqliteStore { path, connection: None }
+    }
+
+    fn connection(&mut self) -> Result<&mut Connection, Box<dyn Error>> {
+        if self.connection.is_none() {
+            if let Some(dir) = self.path.parent() {
+                fs::create_dir_all(dir)?;
+            }
+            let mut connection = Connection::open(&self.path)?;
+            prepare(&mut connection)?;
+            self.connection = Some(connection);
+        }
+        Ok(self.connection.as_mut().expect("connection was just opened"))
+    }
+}
+
+/// Creates the tables in a new database and upgrades the rows of an old one.
+fn prepare(connection: &mut Connection) -> Result<(), Box<dyn Error>> {
+    let transaction = connection.transaction()?;
+    let is_new: bool = transaction.query_row(
+        "SELECT NOT EXISTS (SELECT 1 FROM sqlite_master WHERE name = 'reminders')",
+        [],
+        |row| row.get(0),
+    )?;
+    transaction.execute_batch(SCHEMA)?;
+
+    let version: u32 = transaction.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+    if is_new {
+        transaction.pragma_update(None, "user_version", CURRENT_VERSION)?;
+    } else if version < CURRENT_VERSION {
+        let rows: Vec<(String, String)> = transaction
+            .prepare("SELECT id, body FROM reminders")?
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<_, _>>()?;
+        for (id, body) in rows {
+            // Rows that can't be upgraded are left for `load` to report
+            if let Ok(upgraded) = migrate_body(&body, version) {
+                transaction.execute("UPDATE reminders SET body = ?1 WHERE id = ?2", params![upgraded, id])?;
+            }
+        }
+        transaction.pragma_update(None, "user_version", CURRENT_VERSION)?;
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+/// Runs the document migrations on a single stored entry.
+fn migrate_body(body: &str, version: u32) -> Result<String, Box<dyn Error>> {
+    let mut root = Element::new("reminders");
+    root.set_attribute("version", &version.to_string());
+    root.push(xml_tree::parse(body.as_bytes())?);
+    migrations::migrate(&mut root)?;
+    let entry = root.elements().next().ok_or("the entry disappeared while migrating")?;
+    element_text(entry)
+}
+
+fn element_text(entry: &Element) -> Result<String, Box<dyn Error>> {
+    let mut body = Vec::new();
+    xml_tree::write(&mut body, entry)?;
+    Ok(String::from_utf8(body)?)
+}
+
+fn parse_body(body: &str) -> Result<Reminder, String> {
+    let entry = xml_tree::parse(body.as_bytes()).map_err(|e| e.to_string())?;
+    reminder_from_element(&entry)
+}
+
+// id, name, first and last occurrence, body
+type RowValues = (String, String, i64, Option<i64>, String);
+
+/// The values stored for `reminder`.
+fn row_values(reminder: &Reminder) -> Result<RowValues, Box<dyn Error>> {
+    Ok((
+        reminder.id.clone(),
+        reminder.name.clone(),
+        reminder.time.timestamp(),
+        reminder.last_alert().map(|time| time.timestamp()),
+        element_text(&reminder_to_element(reminder))?,
+    ))
+}
+
+fn insert_row(connection: &Connection, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+    let (id, name, starts_at, ends_at, body) = row_values(reminder)?;
+    connection.execute(
+        "INSERT INTO reminders (id, name, starts_at, ends_at, body) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![id, name, starts_at, ends_at, body],
+    )?;
+    Ok(())
+}
+
+impl ReminderStore for SqliteStore {
+    fn load(&mut self) -> Result<LoadedReminders, Box<dyn Error>> {
+        let connection = self.connection()?;
+        let version: u32 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let mut statement = connection.prepare("SELECT name, body FROM reminders ORDER BY rowid")?;
+        let rows = statement.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+
+        let mut loaded = LoadedReminders { reminders: Vec::new(), errors: Vec::new(), needs_save: false, version };
+        for (position, row) in rows.enumerate() {
+            let (name, body) = row?;
+            match parse_body(&body) {
+                Ok(reminder) => loaded.reminders.push(reminder),
+                Err(reason) => loaded.errors.push(LoadError { position, name, reason }),
+            }
+        }
+        Ok(loaded)
+    }
+
+    fn insert(&mut self, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+        let connection = self.connection()?;
+        let exists = connection
+            .query_row("SELECT 1 FROM reminders WHERE id = ?1", [&reminder.id], |_| Ok(()))
+            .optional()?
+            .is_some();
+        if exists {
+            return Err(format!("a reminder with id {} already exists", reminder.id).into());
+        }
+        insert_row(connection, reminder)
+    }
+
+    fn update(&mut self, reminder: &Reminder) -> Result<(), Box<dyn Error>> {
+        let (id, name, starts_at, ends_at, body) = row_values(reminder)?;
+        let changed = self.connection()?.execute(
+            "UPDATE reminders SET name = ?2, starts_at = ?3, ends_at = ?4, body = ?5 WHERE id = ?1",
+            params![id, name, starts_at, ends_at, body],
+        )?;
+        if changed == 0 {
+            return Err(format!("no reminder with id {}", reminder.id).into());
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, id: &str) -> Result<(), Box<dyn Error>> {
+        let changed = self.connection()?.execute("DELETE FROM reminders WHERE id = ?1", [id])?;
+        if changed == 0 {
+            return Err(format!("no reminder with id {}", id).into());
+        }
+        Ok(())
+    }
+
+    fn replace_all(&mut self, reminders: &[Reminder]) -> Result<(), Box<dyn Error>> {
+        let transaction = self.connection()?.transaction()?;
+        transaction.execute("DELETE FROM reminders", [])?;
+        for reminder in reminders {
+            insert_row(&transaction, reminder)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn due_between(
+        &mut self,
+        from: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> Result<Vec<Reminder>, Box<dyn Error>> {
+        let connection = self.connection()?;
+        // The index narrows it down to series running during the range, the
+        // rule then decides whether one of their occurrences falls inside it
+        let mut statement = connection.prepare(
+            "SELECT body FROM reminders
+             WHERE starts_at <= ?2 AND (ends_at IS NULL OR ends_at >= ?1)
+             ORDER BY rowid",
+        )?;
+        let rows = statement.query_map(params![from.timestamp(), to.timestamp()], |row| row.get::<_, String>(0))?;
+
+        let mut due = Vec::new();
+        for body in rows {
+            // Unreadable rows are reported by `load`
+            let Ok(reminder) = parse_body(&body?) else {
+                continue;
+            };
+            if reminder.next_alert(from).is_some_and(|time| time <= to) {
+                due.push(reminder);
+            }
+        }
+        Ok(due)
+    }
+
+    fn location(&self) -> PathBuf {
+        self.path.clone()
+    }
+
+    fn set_aside_unreadable(&mut self) -> std::io::Result<PathBuf> {
+        self.connection = None;
+        let mut aside = self.path.clone().into_os_string();
+        aside.push(format!(".unreadable-{}", chrono::Local::now().format("%Y%m%d-%H%M%S")));
+        fs::rename(&self.path, &aside)?;
+        Ok(PathBuf::from(aside))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recurrence::{Frequency, Recurrence};
+    use crate::test_support::scratch_store;
+    use chrono::Duration;
+
+    /// A store in a directory of its own, removed when the directory is dropped.
+    fn at(text: &str) -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339(text).unwrap()
+    }
+
+    #[test]
+    fn reminders_survive_reopening() {
+        let (_dir, mut store) = scratch_store("sqlite-reopen", "reminders.sqlite", SqliteStore::new);
+        let mut weekly = Reminder::new("Stand-up <daily> & \"sync\"".to_string(), at("2025-06-23T09:00:00+02:00"));
+        weekly.recurrence = Some(Recurrence::new(Frequency::Weekly));
+        let once = Reminder::new("Dentist".to_string(), at("2025-06-24T15:30:00+02:00"));
+        store.insert(&weekly).unwrap();
+        store.insert(&once).unwrap();
+        assert!(store.insert(&once).is_err(), "ids must stay unique");
+
+        let mut renamed = once.clone();
+        renamed.name = "Dentist, moved".to_string();
+        store.update(&renamed).unwrap();
+
+        let mut reopened = SqliteStore::new(store.location());
+        let loaded = reopened.load().unwrap();
+        assert!(loaded.errors.is_empty());
+        assert_eq!(loaded.version, CURRENT_VERSION);
+        let names: Vec<&str> = loaded.reminders.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(names, ["Stand-up <daily> & \"sync\"", "Dentist, moved"]);
+        assert_eq!(loaded.reminders[0].recurrence.as_ref().unwrap().to_rrule(), "FREQ=WEEKLY");
+
+        reopened.delete(&weekly.id).unwrap();
+        assert!(reopened.delete(&weekly.id).is_err());
+        assert_eq!(reopened.load().unwrap().reminders.len(), 1);
+    }
+
+    #[test]
+    fn due_between_finds_single_and_repeating_reminders() {
+        let (_dir, mut store) = scratch_store("sqlite-due", "reminders.sqlite", SqliteStore::new);
+        let start = at("2025-06-23T09:00:00+00:00");
+        let mut daily = Reminder::new("Daily".to_string(), start);
+        daily.recurrence = Some(Recurrence::new(Frequency::Daily));
+        let mut three_times = Reminder::new("Three times".to_string(), start);
+        let mut rule = Recurrence::new(Frequency::Daily);
+        rule.count = Some(3);
+        three_times.recurrence = Some(rule);
+        let later = Reminder::new("Later".to_string(), start + Duration::days(30));
+        store.replace_all(&[daily, three_times, later]).unwrap();
+
+        let names = |store: &mut SqliteStore, from: DateTime<FixedOffset>, hours: i64| -> Vec<String> {
+            store
+                .due_between(from, from + Duration::hours(hours))
+                .unwrap()
+                .into_iter()
+                .map(|r| r.name)
+                .collect()
+        };
+        assert_eq!(names(&mut store, start + Duration::days(1), 0), ["Daily", "Three times"]);
+        assert_eq!(names(&mut store, start + Duration::days(10), 1), ["Daily"]);
+        assert_eq!(names(&mut store, start + Duration::days(10) + Duration::hours(1), 1), Vec::<String>::new());
+        assert_eq!(names(&mut store, start + Duration::days(30), 0), ["Daily", "Later"]);
+    }
+}
@@ -0,0 +1,126 @@
+//! Where reminders are kept. Everything that reads or changes reminders goes
+//! through `ReminderStore`, so the XML file and the SQLite database can be
+//! swapped through the `[storage]` section of the configuration.
+
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::config::Backend;
+use crate::file_actions::{self, XmlStore};
+use crate::reminder::Reminder;
+use crate::sqlite_store::SqliteStore;
+
+/// Reminders read from a store, plus any entries that had to be skipped.
+pub struct LoadedReminders {
+    pub reminders: Vec<Reminder>,
+    pub errors: Vec<LoadError>,
+    /// Set when the data was upgraded or had ids repaired, so it should be saved again.
+    pub needs_save: bool,
+    /// The format version the data was written in.
+    pub version: u32,
+}
+
+/// A single stored entry that could not be turned into a `Reminder`.
+#[derive(Debug)]
+pub struct LoadError {
+    pub position: usize,
+    pub name: String,
+    pub reason: String,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.name.is_empty() {
+            write!(f, "Reminder #{}: {}", self.position + 1, self.reason)
+        } else {
+            write!(f, "Reminder #{} ('{}'): {}", self.position + 1, self.name, self.reason)
+        }
+    }
+}
+
+/// Stores are `Send` so the daemon can serve bus calls from another thread.
+pub trait ReminderStore: Send {
+    /// Reads every reminder, reporting entries that could not be read instead of failing.
+    fn load(&mut self) -> Result<LoadedReminders, Box<dyn Error>>;
+
+    /// Adds a new reminder. Fails if one with the same id already exists.
+    fn insert(&mut self, reminder: &Reminder) -> Result<(), Box<dyn Error>>;
+
+    /// Adds several new reminders at once.
+    fn insert_all(&mut self, reminders: &[Reminder]) -> Result<(), Box<dyn Error>> {
+        for reminder in reminders {
+            self.insert(reminder)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the stored reminder that has the same id.
+    fn update(&mut self, reminder: &Reminder) -> Result<(), Box<dyn Error>>;
+
+    fn delete(&mut self, id: &str) -> Result<(), Box<dyn Error>>;
+
+    /// Replaces everything in the store, for upgrades and restores.
+    fn replace_all(&mut self, reminders: &[Reminder]) -> Result<(), Box<dyn Error>>;
+
+    /// Reminders that alert between `from` and `to`, both inclusive, whether
+    /// for a regular occurrence or at the end of a snooze.
+    fn due_between(
+        &mut self,
+        from: DateTime<FixedOffset>,
+        to: DateTime<FixedOffset>,
+    ) -> Result<Vec<Reminder>, Box<dyn Error>>;
+
+    /// The file the store keeps its data in.
+    fn location(&self) -> PathBuf;
+
+    /// Moves data that could not be read out of the way, so that the next
+    /// save starts fresh instead of overwriting whatever is left in it.
+    fn set_aside_unreadable(&mut self) -> std::io::Result<PathBuf>;
+
+    fn has_backups(&self) -> bool {
+        false
+    }
+
+    /// Replaces the stored reminders with the newest backup that can still be read.
+    /// Returns the backup that was used along with its reminders.
+    fn restore_latest_backup(&mut self) -> Result<(PathBuf, LoadedReminders), Box<dyn Error>> {
+        Err("this storage backend does not keep backups".into())
+    }
+}
+
+pub fn open_store(backend: Backend) -> Box<dyn ReminderStore> {
+    let dir = file_actions::data_dir();
+    match backend {
+        Backend::Xml => Box::new(XmlStore::new(dir.join("reminders.xml"))),
+        Backend::Sqlite => Box::new(SqliteStore::new(dir.join("reminders.sqlite"))),
+    }
+}
+
+/// Fills a new SQLite database from the XML file the first time the SQLite
+/// backend is chosen, so switching backends keeps existing reminders.
+/// Returns what was copied. The XML file itself is left untouched.
+pub fn copy_xml_into_new_database(backend: Backend) -> Result<Option<String>, Box<dyn Error>> {
+    let dir = file_actions::data_dir();
+    let mut database = SqliteStore::new(dir.join("reminders.sqlite"));
+    let mut xml = XmlStore::new(dir.join("reminders.xml"));
+    if backend != Backend::Sqlite || database.location().exists() || !xml.location().exists() {
+        return Ok(None);
+    }
+
+    let loaded = xml.load()?;
+    if !loaded.errors.is_empty() {
+        return Err(format!("{} entries of {} could not be read", loaded.errors.len(), xml.location().display()).into());
+    }
+    database.replace_all(&loaded.reminders)?;
+    let report = format!(
+        "Copied {} reminders from {} into {}",
+        loaded.reminders.len(),
+        xml.location().display(),
+        database.location().display()
+    );
+    println!("{}", report);
+    Ok(Some(report))
+}
@@ -0,0 +1,89 @@
+//! Helpers shared by the tests of several modules.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset};
+
+use crate::reminder;
+use crate::scheduler::Clock;
+
+/// An empty directory of its own under the system's temp dir, removed with
+/// everything in it when dropped.
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn new(name: &str) -> Self {
+        // Tests run in parallel, and some use the same name more than once
+        static CREATED: AtomicU32 = AtomicU32::new(0);
+        let count = CREATED.fetch_add(1, Ordering::Relaxed);
+        let dir = std::env::temp_dir().join(format!("rewind-{}-{}-{}", name, std::process::id(), count));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        ScratchDir(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: impl AsRef<Path>) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// A store kept in `file` inside a scratch directory of its own, opened with `open`.
+pub fn scratch_store<S>(name: &str, file: &str, open: impl FnOnce(PathBuf) -> S) -> (ScratchDir, S) {
+    let dir = ScratchDir::new(name);
+    let store = open(dir.join(file));
+    (dir, store)
+}
+
+/// A clock the test moves by hand. Clones share the same time, also across threads.
+#[derive(Clone)]
+pub struct FakeClock(Arc<Mutex<(DateTime<FixedOffset>, Duration, String)>>);
+
+impl FakeClock {
+    pub fn at(time: &str) -> Self {
+        FakeClock(Arc::new(Mutex::new((reminder::parse_time(time).unwrap(), Duration::ZERO, "Europe/Berlin".to_string()))))
+    }
+
+    /// Time passing normally.
+    pub fn advance(&self, seconds: i64) {
+        let mut state = self.0.lock().unwrap();
+        state.0 += chrono::Duration::seconds(seconds);
+        state.1 += Duration::from_secs(seconds as u64);
+    }
+
+    /// The wall clock moving without steady time, like being set or a suspend.
+    pub fn jump(&self, seconds: i64) {
+        self.0.lock().unwrap().0 += chrono::Duration::seconds(seconds);
+    }
+
+    pub fn set_zone(&self, zone: &str) {
+        self.0.lock().unwrap().2 = zone.to_string();
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<FixedOffset> {
+        self.0.lock().unwrap().0
+    }
+
+    fn steady(&self) -> Duration {
+        self.0.lock().unwrap().1
+    }
+
+    fn zone(&self) -> String {
+        self.0.lock().unwrap().2.clone()
+    }
+}
@@ -0,0 +1,257 @@
+//! Posts fired reminders to a URL, for chat rooms and home automation.
+//! Failed deliveries are tried again after growing pauses, and every
+//! attempt is written to the delivery log.
+
+use std::io::Write;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+use chrono::{DateTime, FixedOffset, Local};
+use serde_json::Value;
+
+use crate::config::WebhookConfig;
+use crate::file_actions;
+use crate::hook;
+use crate::reminder::{self, Reminder};
+
+/// What is sent unless the configuration has its own template. In a
+/// template, `{{id}}`, `{{name}}`, `{{due}}` and `{{tags}}` are filled in
+/// inside any string, and a string that is only `{{tags}}` becomes a list.
+pub const DEFAULT_TEMPLATE: &str = r#"{"id": "{{id}}", "name": "{{name}}", "due": "{{due}}", "tags": "{{tags}}"}"#;
+
+/// More retries in the configuration are cut down to this. The last one
+/// then comes over half an hour after the first attempt.
+pub const MAX_RETRIES: u32 = 10;
+
+const LOG_FILE: &str = "webhooks.log";
+// The pause before the first retry, it doubles with every one after
+const FIRST_BACKOFF: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct Webhook {
+    url: String,
+    template: Value,
+    retries: u32,
+    backoff: Duration,
+    log: PathBuf,
+    agent: ureq::Agent,
+}
+
+/// The delivery log, next to the reminders.
+pub fn log_path() -> PathBuf {
+    file_actions::data_dir().join(LOG_FILE)
+}
+
+impl Webhook {
+    /// The webhook `config` describes, or `None` when it has no URL.
+    /// Fails when the template is not JSON.
+    pub fn from_config(config: &WebhookConfig, log: PathBuf) -> Result<Option<Self>, String> {
+        let url = config.url.trim();
+        if url.is_empty() {
+            return Ok(None);
+        }
+        let template = config.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+        let template = serde_json::from_str(template).map_err(|e| format!("the webhook template is not JSON: {}", e))?;
+        let agent = ureq::Agent::config_builder().timeout_global(Some(REQUEST_TIMEOUT)).build().into();
+        Ok(Some(Webhook {
+            url: url.to_string(),
+            template,
+            retries: config.retries.min(MAX_RETRIES),
+            backoff: FIRST_BACKOFF,
+            log,
+            agent,
+        }))
+    }
+
+    /// The template filled in for `reminder`, due at `due`.
+    pub fn payload(&self, reminder: &Reminder, due: DateTime<FixedOffset>) -> Value {
+        let mut payload = self.template.clone();
+        fill(&mut payload, reminder, &reminder::format_time(&due));
+        payload
+    }
+
+    /// Delivers `reminder` on another thread.
+    pub fn send(&self, reminder: &Reminder, due: DateTime<FixedOffset>) {
+        let webhook = self.clone();
+        let reminder = reminder.clone();
+        thread::spawn(move || {
+            if let Err(e) = webhook.deliver(&reminder, due) {
+                println!("Webhook for '{}' failed: {}", reminder.name, e);
+            }
+        });
+    }
+
+    /// Posts `reminder` and waits until it got through or all retries failed.
+    pub fn deliver(&self, reminder: &Reminder, due: DateTime<FixedOffset>) -> Result<(), String> {
+        let body = self.payload(reminder, due).to_string();
+        let mut backoff = self.backoff;
+        let mut attempt = 1;
+        loop {
+            let sent = self
+                .agent
+                .post(&self.url)
+                .header("Content-Type", "application/json")
+                .send(body.as_bytes());
+            let (result, retry) = match sent {
+                Ok(response) => (Ok(response.status().as_u16()), false),
+                // Only the server's own trouble is worth asking again
+                Err(ureq::Error::StatusCode(status)) => (Err(format!("status {}", status)), status >= 500 || status == 429),
+                Err(e @ (ureq::Error::BadUri(_) | ureq::Error::Http(_))) => (Err(e.to_string()), false),
+                Err(e) => (Err(e.to_string()), true),
+            };
+            let retry = retry && attempt <= self.retries;
+            let logged = match &result {
+                Ok(status) => format!("delivered, status {}", status),
+                Err(e) if retry => format!("{}, trying again in {}s", e, backoff.as_secs_f32()),
+                Err(e) => e.clone(),
+            };
+            self.log_attempt(reminder, attempt, &logged);
+            match result {
+                Ok(_) => return Ok(()),
+                Err(e) if !retry => return Err(e),
+                Err(_) => {
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn log_attempt(&self, reminder: &Reminder, attempt: u32, outcome: &str) {
+        let line = format!(
+            "{} {} '{}' attempt {}: {}\n",
+            reminder::format_time(&Local::now().fixed_offset()),
+            reminder.id,
+            reminder.name,
+            attempt,
+            outcome
+        );
+        let written = hook::open_log(&self.log).and_then(|mut log| log.write_all(line.as_bytes()));
+        if let Err(e) = written {
+            println!("Failed to write {}: {}", self.log.display(), e);
+        }
+    }
+}
+
+/// Fills in the placeholders in the strings of `value`.
+fn fill(value: &mut Value, reminder: &Reminder, due: &str) {
+    match value {
+        Value::String(text) if text == "{{tags}}" => {
+            *value = Value::from(reminder.tags.clone());
+        }
+        Value::String(text) => {
+            *text = text
+                .replace("{{id}}", &reminder.id)
+                .replace("{{name}}", &reminder.name)
+                .replace("{{due}}", due)
+                .replace("{{tags}}", &reminder.tags.join(", "));
+        }
+        Value::Array(values) => values.iter_mut().for_each(|value| fill(value, reminder, due)),
+        Value::Object(fields) => fields.values_mut().for_each(|value| fill(value, reminder, due)),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::ScratchDir;
+    use serde_json::json;
+    use std::fs;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+
+    /// Answers each request with the next of `statuses` and passes on the bodies.
+    fn listener(statuses: Vec<u16>) -> (String, mpsc::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hooks/rewind", listener.local_addr().unwrap());
+        let (bodies, received) = mpsc::channel();
+        thread::spawn(move || {
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':')
+                        && name.eq_ignore_ascii_case("content-length")
+                    {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                bodies.send(String::from_utf8(body).unwrap()).unwrap();
+                let answer = format!("HTTP/1.1 {} Whatever\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                reader.into_inner().write_all(answer.as_bytes()).unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    fn webhook(url: String, template: Option<&str>, log: PathBuf) -> Webhook {
+        let config = WebhookConfig { url, template: template.map(str::to_string), retries: 2 };
+        let mut webhook = Webhook::from_config(&config, log).unwrap().unwrap();
+        webhook.backoff = Duration::from_millis(10);
+        webhook
+    }
+
+    #[test]
+    fn templates_are_filled_in_as_json() {
+        let mut reminder = Reminder::new("Say \"hi\"".to_string(), reminder::parse_time("2026-10-20T14:00:00+02:00").unwrap());
+        reminder.tags = vec!["team".to_string(), "chat".to_string()];
+        let plain = webhook("http://127.0.0.1:9".to_string(), None, PathBuf::new());
+        assert_eq!(
+            plain.payload(&reminder, reminder.time),
+            json!({"id": reminder.id, "name": "Say \"hi\"", "due": "2026-10-20T14:00:00+02:00", "tags": ["team", "chat"]})
+        );
+
+        let chat = webhook("http://127.0.0.1:9".to_string(), Some(r#"{"text": "{{name}} ({{tags}}) is due"}"#), PathBuf::new());
+        assert_eq!(chat.payload(&reminder, reminder.time), json!({"text": "Say \"hi\" (team, chat) is due"}));
+
+        let broken = WebhookConfig { url: "http://127.0.0.1:9".to_string(), template: Some("{{name}}".to_string()), retries: 0 };
+        assert!(Webhook::from_config(&broken, PathBuf::new()).is_err());
+        let eager = WebhookConfig { url: "http://127.0.0.1:9".to_string(), template: None, retries: u32::MAX };
+        assert_eq!(Webhook::from_config(&eager, PathBuf::new()).unwrap().unwrap().retries, MAX_RETRIES);
+        assert!(Webhook::from_config(&WebhookConfig::default(), PathBuf::new()).unwrap().is_none());
+    }
+
+    #[test]
+    fn failed_deliveries_are_retried_and_logged() {
+        let dir = ScratchDir::new("webhook");
+        let log = dir.join(LOG_FILE);
+        let reminder = Reminder::new("Water plants".to_string(), Local::now().fixed_offset());
+
+        let (url, received) = listener(vec![503, 200]);
+        webhook(url, None, log.clone()).deliver(&reminder, reminder.time).unwrap();
+        for _ in 0..2 {
+            let body: Value = serde_json::from_str(&received.recv().unwrap()).unwrap();
+            assert_eq!(body["name"], "Water plants");
+        }
+
+        // Nothing the server says about the request itself gets better by asking again
+        let (url, received) = listener(vec![404, 200]);
+        assert_eq!(webhook(url, None, log.clone()).deliver(&reminder, reminder.time), Err("status 404".to_string()));
+        received.recv().unwrap();
+        assert!(received.try_recv().is_err());
+
+        let logged = fs::read_to_string(&log).unwrap();
+        let outcomes: Vec<&str> = logged.lines().map(|line| line.split_once(" attempt ").unwrap().1).collect();
+        assert_eq!(outcomes, ["1: status 503, trying again in 0.01s", "2: delivered, status 200", "1: status 404"]);
+
+        // A full log is moved aside rather than growing without end
+        fs::write(&log, vec![b'.'; hook::LOG_LIMIT as usize + 1]).unwrap();
+        let (url, _received) = listener(vec![200]);
+        webhook(url, None, log.clone()).deliver(&reminder, reminder.time).unwrap();
+        assert_eq!(fs::metadata(log.with_extension("log.old")).unwrap().len(), hook::LOG_LIMIT + 1);
+        assert!(fs::read_to_string(&log).unwrap().ends_with("attempt 1: delivered, status 200\n"));
+    }
+}
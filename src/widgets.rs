@@ -0,0 +1,25 @@
+//! Small pieces of the reminder dialog shared by its sections.
+
+use gtk::prelude::{BoxExt, DialogExt, GtkWindowExt, IsA};
+use relm4::gtk;
+
+/// `widget` in a row between a label and a unit, which is left out when empty.
+pub fn labelled_row(label: &str, widget: &impl IsA<gtk::Widget>, unit: &str) -> gtk::Box {
+    let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+    row.append(&gtk::Label::new(Some(label)));
+    row.append(widget);
+    if !unit.is_empty() {
+        row.append(&gtk::Label::new(Some(unit)));
+    }
+    row
+}
+
+/// Tells about a mistake in what was entered, over the window it was entered in.
+pub fn show_error(parent: &impl IsA<gtk::Window>, text: &str, details: &str) {
+    let dialog = gtk::MessageDialog::new(Some(parent), gtk::DialogFlags::MODAL, gtk::MessageType::Error, gtk::ButtonsType::Ok, text);
+    if !details.is_empty() {
+        dialog.set_secondary_text(Some(details));
+    }
+    dialog.connect_response(|dialog, _| dialog.close());
+    dialog.present();
+}
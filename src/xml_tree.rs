@@ -0,0 +1,182 @@
+use std::io::{Read, Write};
+use xml::escape::escape_str_attribute;
+use xml::reader::{ParserConfig, XmlEvent};
+use xml::writer::{EmitterConfig, EventWriter, XmlEvent as WriterEvent};
+
+/// A minimal in-memory XML element, enough to migrate documents and to carry
+/// elements we don't understand through a load and save unchanged.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Node>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+}
+
+impl Element {
+    pub fn new(name: &str) -> Self {
+        Element { name: name.to_string(), attributes: Vec::new(), children: Vec::new() }
+    }
+
+    pub fn with_text(name: &str, text: &str) -> Self {
+        let mut element = Element::new(name);
+        element.children.push(Node::Text(text.to_string()));
+        element
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_attribute(&mut self, name: &str, value: &str) {
+        match self.attributes.iter_mut().find(|(key, _)| key == name) {
+            Some(attribute) => attribute.1 = value.to_string(),
+            None => self.attributes.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    /// All text directly inside this element.
+    pub fn text(&self) -> String {
+        self.children
+            .iter()
+            .filter_map(|node| match node {
+                Node::Text(text) => Some(text.as_str()),
+                Node::Element(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn elements_mut(&mut self) -> impl Iterator<Item = &mut Element> {
+        self.children.iter_mut().filter_map(|node| match node {
+            Node::Element(element) => Some(element),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.elements().find(|element| element.name == name)
+    }
+
+    pub fn push(&mut self, element: Element) {
+        self.children.push(Node::Element(element));
+    }
+
+    /// Replaces the text of the first child called `name`, adding the child if needed.
+    pub fn set_child_text(&mut self, name: &str, text: &str) {
+        if let Some(child) = self.elements_mut().find(|element| element.name == name) {
+            child.children = vec![Node::Text(text.to_string())];
+            return;
+        }
+        self.push(Element::with_text(name, text));
+    }
+}
+
+pub fn parse<R: Read>(source: R) -> xml::reader::Result<Element> {
+    // Keep whitespace-only text and CDATA so values round-trip exactly
+    let parser = ParserConfig::new()
+        .whitespace_to_characters(true)
+        .cdata_to_characters(true)
+        .create_reader(source);
+
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    for event in parser {
+        match event? {
+            XmlEvent::StartElement { name, attributes, .. } => {
+                let mut element = Element::new(&name.local_name);
+                element.attributes = attributes
+                    .into_iter()
+                    .map(|attribute| (attribute.name.local_name, attribute.value))
+                    .collect();
+                stack.push(element);
+            }
+            XmlEvent::Characters(text) => {
+                if let Some(parent) = stack.last_mut() {
+                    match parent.children.last_mut() {
+                        Some(Node::Text(existing)) => existing.push_str(&text),
+                        _ => parent.children.push(Node::Text(text)),
+                    }
+                }
+            }
+            XmlEvent::EndElement { .. } => {
+                if let Some(element) = stack.pop() {
+                    match stack.last_mut() {
+                        Some(parent) => parent.push(element),
+                        None => root = Some(element),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    // The parser already rejects documents without a root element
+    Ok(root.unwrap_or_else(|| Element::new("reminders")))
+}
+
+pub fn write<W: Write>(sink: W, root: &Element) -> xml::writer::Result<()> {
+    // Escaping is done by `xml_text` so carriage returns survive the round trip
+    let mut config = EmitterConfig::new().perform_indent(true);
+    config.perform_escaping = false;
+    let mut writer = config.create_writer(sink);
+    write_element(&mut writer, root)
+}
+
+fn write_element<W: Write>(writer: &mut EventWriter<W>, element: &Element) -> xml::writer::Result<()> {
+    let escaped: Vec<(&str, String)> = element
+        .attributes
+        .iter()
+        .map(|(key, value)| (key.as_str(), escape_str_attribute(value).into_owned()))
+        .collect();
+    let mut start = WriterEvent::start_element(element.name.as_str());
+    for (key, value) in &escaped {
+        start = start.attr(*key, value);
+    }
+    writer.write(start)?;
+
+    // Indentation between child elements is regenerated by the writer
+    let has_elements = element.elements().next().is_some();
+    for child in &element.children {
+        match child {
+            Node::Element(child) => write_element(writer, child)?,
+            Node::Text(text) if has_elements && text.trim().is_empty() => {}
+            Node::Text(text) => {
+                let escaped = xml_text(text);
+                if !escaped.is_empty() {
+                    writer.write(WriterEvent::characters(&escaped))?;
+                }
+            }
+        }
+    }
+    writer.write(WriterEvent::end_element())
+}
+
+/// Escapes text for use as element content. Characters that XML 1.0 cannot
+/// represent at all (most control characters) are dropped so the file stays readable.
+fn xml_text(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            // A literal carriage return would be normalized to a newline when read back
+            '\r' => escaped.push_str("&#xD;"),
+            '\t' | '\n' => escaped.push(c),
+            '\u{0}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}